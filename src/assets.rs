@@ -1,25 +1,311 @@
-use reqwest::blocking::Client;
-use serde::Deserialize;
+//! The GitHub REST API: the release, tag, repository and asset types
+//! every provider maps its responses to, and the requests behind them, with
+//! rate limit handling, pagination and conditional requests
+
+use chrono::{DateTime, Local};
+use reqwest::blocking::Response;
+use reqwest::header::{HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH, LINK};
+use reqwest::{ResponseBuilderExt, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use crate::cache;
+use crate::client::{self, Client};
+use crate::output;
+
+static WAIT_FOR_RATE_LIMIT: AtomicBool = AtomicBool::new(false);
+static API_URL: OnceLock<String> = OnceLock::new();
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Sleep until the rate limit resets instead of failing (`--wait-for-rate-limit`)
+pub fn set_wait_for_rate_limit(wait: bool) {
+    WAIT_FOR_RATE_LIMIT.store(wait, Ordering::Relaxed);
+}
+
+/// Point the GitHub helpers at another API, e.g. a GitHub Enterprise Server
+/// (`--api-url`)
+pub fn set_api_url(url: String) {
+    let _ = API_URL.set(url);
+}
+
+/// The base URL of the GitHub API, `https://api.github.com` unless set
+pub fn api_url() -> &'static str {
+    API_URL.get().map_or(DEFAULT_API_URL, String::as_str)
+}
+
+/// Parse a base URL given to `--api-url` or `EGIT_GITHUB_API`
+pub fn parse_api_url(value: &str) -> Result<String, String> {
+    let url = Url::parse(value).map_err(|e| format!("invalid API URL `{}`: {}", value, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("expected an http(s) URL, got `{}`", value));
+    }
+    Ok(value.trim_end_matches('/').to_string())
+}
+
+/// Error returned by the GitHub API helpers
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request failed, or the API answered with an error status
+    Http(reqwest::Error),
+    /// The API rate limit is exhausted
+    RateLimited {
+        /// When the limit resets, as a Unix timestamp, if the API said
+        reset_at: Option<i64>,
+    },
+    /// `--offline` is set and the response was never cached
+    Offline {
+        /// The API request that would have been needed
+        url: String,
+    },
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        ApiError::Http(e)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Http(e) => write!(f, "{}", e),
+            ApiError::RateLimited { reset_at } => {
+                write!(f, "GitHub API rate limit exceeded")?;
+                let reset = reset_at.and_then(|t| DateTime::from_timestamp(t, 0));
+                if let Some(reset) = reset {
+                    write!(f, ", resets at {}", reset.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"))?;
+                }
+                write!(f, ". Set a token with --token or GITHUB_TOKEN to raise the limit, or pass --wait-for-rate-limit")
+            },
+            ApiError::Offline { url } => {
+                write!(f, "`{}` is not cached and --offline prevents fetching it. Run the command once without --offline to cache it", url)
+            },
+        }
+    }
+}
 
-#[derive(Deserialize, Debug)]
+/// Turn rate-limit responses and other HTTP errors into an `ApiError` before
+/// the body is parsed as JSON
+fn check_response(response: Response) -> Result<Response, ApiError> {
+    let status = response.status();
+    if status == StatusCode::FORBIDDEN || status == StatusCode::TOO_MANY_REQUESTS {
+        let header = |name: &str| response.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<i64>().ok());
+        if header("x-ratelimit-remaining") == Some(0) {
+            return Err(ApiError::RateLimited { reset_at: header("x-ratelimit-reset") });
+        }
+    }
+    Ok(response.error_for_status()?)
+}
+
+/// GET an API URL, waiting out an exhausted rate limit when requested. The
+/// response is kept in the cache and revalidated with its ETag next time;
+/// with `--offline` it is served from there.
+pub fn get(client: &Client, url: &str) -> Result<Response, ApiError> {
+    if client::is_offline() {
+        let cached = cache::lookup_response(url).ok_or_else(|| ApiError::Offline { url: url.to_string() })?;
+        return check_response(cached.into_response(url));
+    }
+    // A stored 404 is not worth revalidating
+    let cached = cache::lookup_response(url).filter(|cached| cached.status == StatusCode::OK.as_u16());
+    loop {
+        let mut request = client.get(url)
+            .header("User-Agent", "egit-cli");
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = client.send(request)?;
+        let response = match &cached {
+            // Unchanged since it was cached. GitHub doesn't count these
+            // against the rate limit.
+            Some(cached) if response.status() == StatusCode::NOT_MODIFIED => {
+                log::debug!("< {} is unchanged, using the cached response", url);
+                cached.clone().into_response(url)
+            },
+            _ => keep_response(url, response)?,
+        };
+        match check_response(response) {
+            Err(ApiError::RateLimited { reset_at: Some(reset_at) }) if WAIT_FOR_RATE_LIMIT.load(Ordering::Relaxed) => {
+                // One extra second so the request lands after the reset
+                let wait = (reset_at - chrono::Utc::now().timestamp()).max(0) as u64 + 1;
+                status!("- GitHub API rate limit exceeded, waiting {}s for it to reset...", wait);
+                thread::sleep(Duration::from_secs(wait));
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Store a response for later requests, handing back an equivalent one as
+/// the body can only be read once. Failing to store it is only logged.
+fn keep_response(url: &str, response: Response) -> Result<Response, ApiError> {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let final_url = response.url().clone();
+    let body = response.text()?;
+    if let Err(e) = cache::store_response(url, status, &headers, &body) {
+        log::debug!("Failed to cache the response to {}: {}", url, e);
+    }
+    let mut rebuilt = http::Response::builder()
+        .status(status)
+        .url(final_url)
+        .body(body)
+        .expect("valid response");
+    *rebuilt.headers_mut() = headers;
+    Ok(Response::from(rebuilt))
+}
+
+/// A repository, as returned by the repositories API
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GitHubRepo {
+    /// `owner/repo`
+    pub full_name: String,
+    /// The branch source archives are taken from when no ref is given
+    pub default_branch: String,
+}
+
+/// A git tag of a repository
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GitHubTag {
+    /// The tag name, e.g. `v1.2.3`
     pub name: String,
 }
 
-#[derive(Deserialize, Debug)]
+/// A release and its assets
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct GitHubRelease {
+    /// The tag the release was made from
     pub tag_name: String,
+    /// The release title, if it has one
     pub name: Option<String>,
+    /// When the release was published (RFC 3339); unset for drafts
     pub published_at: Option<String>,
+    /// When the release was created (RFC 3339)
+    pub created_at: Option<String>,
+    /// The release page in the browser
+    pub html_url: Option<String>,
+    /// Who published the release
+    pub author: Option<GitHubUser>,
+    /// Set for unpublished releases, which only show up with a token
+    #[serde(default)]
+    pub draft: bool,
+    /// Set for releases marked as pre-releases
+    #[serde(default)]
+    pub prerelease: bool,
+    /// Release notes in Markdown
+    pub body: Option<String>,
+    /// The files attached to the release
     pub assets: Vec<GitHubAsset>,
+    /// Source code of the tag as a zip archive
+    pub zipball_url: String,
+    /// Source code of the tag as a gzipped tarball
+    pub tarball_url: String,
 }
 
-#[derive(Deserialize, Debug)]
+/// A file attached to a release
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct GitHubAsset {
+    /// The file name
     pub name: String,
+    /// The API endpoint of the asset, which serves its bytes to
+    /// `Accept: application/octet-stream`
+    pub url: String,
+    /// The public download link
     pub browser_download_url: String,
+    /// Size in bytes
     pub size: u64,
+    /// MIME type given by the uploader
+    pub content_type: Option<String>,
+    /// How many times the asset was downloaded
+    #[serde(default)]
+    pub download_count: u64,
+    /// `sha256:<hex>`, computed by GitHub for assets uploaded since mid-2025
+    #[serde(default)]
+    pub digest: Option<String>,
+}
+
+/// A file or directory entry returned by the contents API
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GitHubContent {
+    /// The file name
+    pub name: String,
+    /// Path from the root of the repository
+    pub path: String,
+    /// `file`, `dir`, `symlink` or `submodule`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Size in bytes, 0 for directories
+    pub size: u64,
+    /// Missing for directories and submodules
+    pub download_url: Option<String>,
+}
+
+/// The contents API returns an object for a file and an array for a directory
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Contents {
+    /// The requested path is a file
+    File(GitHubContent),
+    /// The requested path is a directory, with these entries
+    Dir(Vec<GitHubContent>),
+}
+
+/// A recursive listing of a git tree
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitTree {
+    /// Every file and directory in the tree
+    pub tree: Vec<GitTreeEntry>,
+    /// Set when the tree is too large to be returned in one response
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// A file or directory of a git tree
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitTreeEntry {
+    /// Path from the root of the tree
+    pub path: String,
+    /// `blob` for files, `tree` for directories, `commit` for submodules
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Size in bytes, for blobs only
+    pub size: Option<u64>,
+}
+
+/// A GitHub account
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GitHubUser {
+    /// The user name
+    pub login: String,
+}
+
+impl GitHubAsset {
+    /// URL to fetch the asset's bytes from. The API endpoint also works for
+    /// private repositories but needs a token and `Accept: application/octet-stream`.
+    pub fn download_url(&self, authenticated: bool) -> &str {
+        if authenticated {
+            &self.url
+        } else {
+            &self.browser_download_url
+        }
+    }
+
+    /// The SHA-256 from `digest`, when GitHub provides one
+    pub fn sha256(&self) -> Option<&str> {
+        self.digest.as_deref()?.strip_prefix("sha256:")
+    }
 }
 
 impl fmt::Display for GitHubTag {
@@ -46,46 +332,190 @@ impl fmt::Display for GitHubRelease {
     }
 }
 
+/// Print the assets of a release, as JSON with `--format json`
 pub fn display_assets(release: &GitHubRelease) {
-    println!("=== Assets for Release '{}' ===", release.tag_name);
+    if output::is_json() {
+        output::print_json(&release.assets);
+        return;
+    }
+    output!("=== Assets for Release '{}' ===", release.tag_name);
     if release.assets.is_empty() {
-        println!("- No assets found for this release");
+        output!("- No assets found for this release");
     } else {
         for asset in &release.assets {
-            println!("{}", asset);
+            output!("{}", asset);
         }
     }
-    println!("=== Total: {} assets ===", release.assets.len());
+    output!("=== Total: {} assets ===", release.assets.len());
+}
+
+/// Fetch a repository
+pub fn fetch_repo(client: &Client, owner: &str, repo: &str) -> Result<GitHubRepo, ApiError> {
+    let url = format!("{}/repos/{}/{}", api_url(), owner, repo);
+    Ok(get(client, &url)?.json()?)
 }
 
-pub fn fetch_tags(client: &Client, owner: &str, repo: &str) -> Result<Vec<GitHubTag>, reqwest::Error> {
-    let url = format!("https://api.github.com/repos/{}/{}/tags", owner, repo);
-    client.get(&url)
-        .header("User-Agent", "egit-cli")
-        .send()?
-        .json()
+/// The newest stable release according to GitHub, if there is one
+pub fn fetch_latest_release(client: &Client, owner: &str, repo: &str) -> Result<Option<GitHubRelease>, ApiError> {
+    let url = format!("{}/repos/{}/{}/releases/latest", api_url(), owner, repo);
+    fetch_optional(client, &url)
 }
 
-pub fn fetch_releases(client: &Client, owner: &str, repo: &str) -> Result<Vec<GitHubRelease>, reqwest::Error> {
-    let url = format!("https://api.github.com/repos/{}/{}/releases", owner, repo);
-    client.get(&url)
-        .header("User-Agent", "egit-cli")
-        .send()?
-        .json()
+/// The published release for `tag`, if there is one
+pub fn fetch_release_by_tag(client: &Client, owner: &str, repo: &str, tag: &str) -> Result<Option<GitHubRelease>, ApiError> {
+    let mut url = Url::parse(&format!("{}/repos/{}/{}/releases/tags", api_url(), owner, repo))
+        .expect("valid API URL");
+    // Tags may contain `/` or `+`, so encode it as a single path segment
+    url.path_segments_mut().expect("API URL has a path").push(tag);
+    fetch_optional(client, url.as_str())
 }
 
+/// URL of the `tarball` or `zipball` archive of any branch, tag or commit
+pub fn archive_url(owner: &str, repo: &str, kind: &str, git_ref: &str) -> String {
+    let mut url = Url::parse(&format!("{}/repos/{}/{}/{}", api_url(), owner, repo, kind))
+        .expect("valid API URL");
+    // Branch names may contain `/`, so encode the ref as a single path segment
+    url.path_segments_mut().expect("API URL has a path").push(git_ref);
+    url.into()
+}
+
+/// Append each `/`-separated part of `path` as its own path segment
+fn push_path(url: &mut Url, path: &str) {
+    url.path_segments_mut()
+        .expect("API URL has a path")
+        .extend(path.split('/').filter(|part| !part.is_empty()));
+}
+
+/// The file or directory listing at `path` on `git_ref` (default branch when
+/// `None`), if it exists
+pub fn fetch_contents(client: &Client, owner: &str, repo: &str, path: &str, git_ref: Option<&str>) -> Result<Option<Contents>, ApiError> {
+    let mut url = Url::parse(&format!("{}/repos/{}/{}/contents", api_url(), owner, repo))
+        .expect("valid API URL");
+    push_path(&mut url, path);
+    if let Some(git_ref) = git_ref {
+        url.query_pairs_mut().append_pair("ref", git_ref);
+    }
+    fetch_optional(client, url.as_str())
+}
+
+/// Every file and directory of `git_ref`, recursively
+pub fn fetch_tree(client: &Client, owner: &str, repo: &str, git_ref: &str) -> Result<GitTree, ApiError> {
+    let mut url = Url::parse(&format!("{}/repos/{}/{}/git/trees", api_url(), owner, repo))
+        .expect("valid API URL");
+    url.path_segments_mut().expect("API URL has a path").push(git_ref);
+    url.query_pairs_mut().append_pair("recursive", "1");
+    Ok(get(client, url.as_str())?.json()?)
+}
+
+/// URL of the raw bytes of the file at `path` on `git_ref`
+pub fn raw_url(owner: &str, repo: &str, git_ref: &str, path: &str) -> String {
+    // GitHub Enterprise Server serves raw files from `https://HOST/raw`
+    // instead of a separate domain
+    let base = match API_URL.get() {
+        Some(api) => format!("{}/raw", api.trim_end_matches("/api/v3")),
+        None => "https://raw.githubusercontent.com".to_string(),
+    };
+    let mut url = Url::parse(&base).expect("valid raw URL");
+    url.path_segments_mut().expect("raw URL has a path").pop_if_empty().extend([owner, repo, git_ref]);
+    push_path(&mut url, path);
+    url.into()
+}
+
+/// GET a single object, treating 404 Not Found as `None`
+pub fn fetch_optional<T: DeserializeOwned>(client: &Client, url: &str) -> Result<Option<T>, ApiError> {
+    match get(client, url) {
+        Ok(response) => Ok(Some(response.json()?)),
+        Err(ApiError::Http(e)) if e.status() == Some(StatusCode::NOT_FOUND) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Fetch tags, newest first, stopping once `limit` tags have been fetched
+pub fn fetch_tags(client: &Client, owner: &str, repo: &str, limit: Option<usize>) -> Result<Vec<GitHubTag>, ApiError> {
+    let url = format!("{}/repos/{}/{}/tags", api_url(), owner, repo);
+    let mut tags = fetch_pages(client, &url, stop_after(limit))?;
+    tags.truncate(limit.unwrap_or(usize::MAX));
+    Ok(tags)
+}
+
+/// Page predicate that stops once `limit` items have been seen in total
+pub fn stop_after<T>(limit: Option<usize>) -> impl FnMut(&[T]) -> bool {
+    let mut seen = 0;
+    move |page| {
+        seen += page.len();
+        limit.is_some_and(|limit| seen >= limit)
+    }
+}
+
+/// Fetch releases page by page, stopping early once `stop` returns true for
+/// the page just fetched
+pub fn fetch_releases_until(
+    client: &Client,
+    owner: &str,
+    repo: &str,
+    stop: impl FnMut(&[GitHubRelease]) -> bool,
+) -> Result<Vec<GitHubRelease>, ApiError> {
+    let url = format!("{}/repos/{}/{}/releases", api_url(), owner, repo);
+    fetch_pages(client, &url, stop)
+}
+
+/// Follow the `Link: <...>; rel="next"` headers of a paginated endpoint
+pub fn fetch_pages<T: DeserializeOwned>(
+    client: &Client,
+    url: &str,
+    mut stop: impl FnMut(&[T]) -> bool,
+) -> Result<Vec<T>, ApiError> {
+    let mut items = Vec::new();
+    let mut next = Some(format!("{}?per_page=100", url));
+    
+    while let Some(url) = next {
+        let response = get(client, &url)?;
+        next = next_page_url(response.headers());
+        
+        let page: Vec<T> = response.json()?;
+        let done = stop(&page);
+        items.extend(page);
+        if done {
+            break;
+        }
+    }
+    
+    Ok(items)
+}
+
+fn next_page_url(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    link.split(',')
+        .find(|part| part.contains("rel=\"next\""))
+        .and_then(|part| {
+            let start = part.find('<')? + 1;
+            let end = part.find('>')?;
+            Some(part[start..end].to_string())
+        })
+}
+
+/// Print tags, as JSON with `--format json`
 pub fn display_tags(tags: &[GitHubTag]) {
-    println!("=== Tags ===");
+    if output::is_json() {
+        output::print_json(tags);
+        return;
+    }
+    output!("=== Tags ===");
     for tag in tags {
-        println!("- {}", tag);
+        output!("- {}", tag);
     }
-    println!("=== Total: {} tags ===", tags.len());
+    output!("=== Total: {} tags ===", tags.len());
 }
 
+/// Print releases, as JSON with `--format json`
 pub fn display_releases(releases: &[GitHubRelease]) {
-    println!("=== Releases ===");
+    if output::is_json() {
+        output::print_json(releases);
+        return;
+    }
+    output!("=== Releases ===");
     for release in releases {
-        println!("- {}", release);
+        output!("- {}", release);
     }
-    println!("=== Total: {} releases ===", releases.len());
+    output!("=== Total: {} releases ===", releases.len());
 }
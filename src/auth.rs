@@ -0,0 +1,469 @@
+//! Tokens saved by `egit auth login`. They are kept in the OS keyring: the
+//! login Keychain on macOS, and the Secret Service (GNOME Keyring, KWallet)
+//! on Linux and the BSDs, through the `security` and `secret-tool` commands.
+//! With `--insecure-storage`, or where there is no keyring, they go to
+//! `~/.config/egit/credentials.toml` instead, readable only by the user.
+
+use dialoguer::Password;
+use reqwest::StatusCode;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+#[cfg(unix)]
+use std::process::{Output, Stdio};
+use crate::assets;
+use crate::client::{self, Client};
+use crate::config;
+use crate::confirm;
+use crate::error::{Context, EgitError};
+
+/// Service name the tokens are filed under in the keyring
+const SERVICE: &str = "egit";
+
+/// Where a saved token is kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Store {
+    /// The OS keyring
+    Keyring,
+    /// `credentials.toml` in the config directory
+    File,
+}
+
+/// Where the token in effect came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// `--token`
+    Flag,
+    /// The environment variable of this name
+    Env(&'static str),
+    /// The `token` or `tokens` setting of the config file
+    Config,
+    /// A token saved by `egit auth login`
+    Stored(Store),
+    /// `gh auth token`, from a login with the GitHub CLI
+    Gh,
+    /// The `password` of the host's entry in `~/.netrc`
+    Netrc,
+}
+
+impl Source {
+    /// Whether the token was given to egit directly rather than saved or
+    /// borrowed from another tool
+    pub fn is_explicit(&self) -> bool {
+        matches!(self, Source::Flag | Source::Env(_) | Source::Config)
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Flag => write!(f, "--token"),
+            Source::Env(var) => write!(f, "${}", var),
+            Source::Config => write!(f, "the config file"),
+            Source::Stored(Store::Keyring) => write!(f, "the {}", keyring::NAME),
+            Source::Stored(Store::File) => write!(f, "`{}`", credentials_path().map_or_else(|_| "credentials.toml".to_string(), |p| p.display().to_string())),
+            Source::Gh => write!(f, "`gh auth token`"),
+            Source::Netrc => write!(f, "`{}`", netrc_path().map_or_else(|| ".netrc".to_string(), |p| p.display().to_string())),
+        }
+    }
+}
+
+/// The host tokens for the GitHub API are saved under: `github.com` for
+/// api.github.com, or the host of a GitHub Enterprise Server
+pub fn github_host() -> String {
+    match host_key(assets::api_url()) {
+        Some(host) if host != "api.github.com" => host,
+        _ => "github.com".to_string(),
+    }
+}
+
+/// The host of `url` as tokens are keyed by it: `gitlab.com`, or
+/// `git.example.com:8443` with a port
+pub fn host_key(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Pick the token from `--token`, then `GITHUB_TOKEN` or `GH_TOKEN`, then the
+/// config file, then the token `egit auth login` saved for `host`, and last
+/// the credentials of the GitHub CLI or `~/.netrc`
+pub fn resolve(flag: Option<String>, config: Option<&str>, host: &str) -> Option<(String, Source)> {
+    let usable = |token: String| Some(token.trim().to_string()).filter(|token| !token.is_empty());
+    if let Some(token) = flag.and_then(usable) {
+        return Some((token, Source::Flag));
+    }
+    for var in ["GITHUB_TOKEN", "GH_TOKEN"] {
+        if let Some(token) = env::var(var).ok().and_then(usable) {
+            return Some((token, Source::Env(var)));
+        }
+    }
+    if let Some(token) = config.map(str::to_string).and_then(usable) {
+        return Some((token, Source::Config));
+    }
+    lookup(host).map(|(token, store)| (token, Source::Stored(store)))
+        .or_else(|| borrow(host))
+}
+
+/// A token for `host` from another tool the user already logged in with
+pub fn borrow(host: &str) -> Option<(String, Source)> {
+    if let Some(token) = gh_token(host) {
+        return Some((token, Source::Gh));
+    }
+    let contents = fs::read_to_string(netrc_path()?).ok()?;
+    // The API host of github.com is listed as often as github.com itself
+    let machines: &[&str] = if host == "github.com" { &[host, "api.github.com"] } else { &[host] };
+    machines.iter()
+        .find_map(|machine| netrc_password(&contents, machine))
+        .map(|token| (token, Source::Netrc))
+}
+
+/// The token the GitHub CLI is logged in with on `host`, if it is installed
+fn gh_token(host: &str) -> Option<String> {
+    let output = Command::new("gh")
+        .args(["auth", "token", "--hostname", host])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .inspect_err(|e| log::debug!("- Could not run `gh auth token`: {}", e))
+        .ok()?;
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !token.is_empty()).then_some(token)
+}
+
+/// `$NETRC`, or `~/.netrc` (`~/_netrc` on Windows)
+fn netrc_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NETRC").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let name = if cfg!(windows) { "_netrc" } else { ".netrc" };
+    env::home_dir().map(|home| home.join(name))
+}
+
+/// The password of the `machine` entry in the contents of a netrc file. The
+/// `default` entry is ignored, so a password meant for another service is
+/// never sent to GitHub.
+pub fn netrc_password(contents: &str, machine: &str) -> Option<String> {
+    let mut current = None;
+    // The keyword whose value is the next word
+    let mut keyword = None;
+    let mut in_macro = false;
+    for line in contents.lines() {
+        // A macro runs until the next blank line
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        for word in line.split_whitespace() {
+            match (keyword.take(), word) {
+                (Some("machine"), name) => current = Some(name),
+                (Some("password"), password) if current == Some(machine) => return Some(password.to_string()),
+                (Some(_), _) => {},
+                (None, "default") => current = None,
+                (None, "macdef") => {
+                    in_macro = true;
+                    break;
+                },
+                (None, comment) if comment.starts_with('#') => break,
+                (None, word @ ("machine" | "login" | "password" | "account")) => keyword = Some(word),
+                (None, _) => {},
+            }
+        }
+    }
+    None
+}
+
+/// The token saved for `host`, looking in the keyring first
+pub fn lookup(host: &str) -> Option<(String, Store)> {
+    match keyring::get(host) {
+        Ok(Some(token)) => return Some((token, Store::Keyring)),
+        Ok(None) => {},
+        Err(e) => log::debug!("- Could not read the {}: {}", keyring::NAME, e),
+    }
+    match read_credentials() {
+        Ok(mut credentials) => credentials.tokens.remove(host).map(|token| (token, Store::File)),
+        Err(e) => {
+            log::debug!("- Could not read the credentials file: {}", e);
+            None
+        },
+    }
+}
+
+/// Save the token for `host` in the keyring, or in the credentials file with
+/// `insecure_storage`. A copy in the other place is removed so the two never
+/// disagree.
+pub fn save(host: &str, token: &str, insecure_storage: bool) -> Result<Store, EgitError> {
+    if insecure_storage {
+        let mut credentials = read_credentials().context("Failed to read the credentials file")?;
+        credentials.tokens.insert(host.to_string(), token.to_string());
+        write_credentials(&credentials).context("Failed to write the credentials file")?;
+        let _ = keyring::delete(host);
+        return Ok(Store::File);
+    }
+    keyring::set(host, token).map_err(|e| EgitError::Other(format!(
+        "Failed to save the token in the {}: {}. Pass --insecure-storage to keep it in a file instead.", keyring::NAME, e
+    )))?;
+    remove_from_file(host).context("Failed to update the credentials file")?;
+    Ok(Store::Keyring)
+}
+
+/// Forget the token saved for `host`, returning where one was removed from
+pub fn remove(host: &str) -> Result<Vec<Store>, EgitError> {
+    let mut removed = Vec::new();
+    match keyring::delete(host) {
+        Ok(true) => removed.push(Store::Keyring),
+        Ok(false) => {},
+        Err(e) => log::debug!("- Could not update the {}: {}", keyring::NAME, e),
+    }
+    if remove_from_file(host).context("Failed to update the credentials file")? {
+        removed.push(Store::File);
+    }
+    Ok(removed)
+}
+
+/// The login of the account `client`'s token belongs to, and the OAuth
+/// scopes it was granted (fine-grained tokens report none). Not cached, as
+/// the answer depends on the token rather than the URL.
+pub fn fetch_user(client: &Client) -> Result<(String, Option<String>), EgitError> {
+    #[derive(Deserialize)]
+    struct User {
+        login: String,
+    }
+
+    if client::is_offline() {
+        return Err(EgitError::Other("Cannot check the token with --offline".to_string()));
+    }
+    let url = format!("{}/user", assets::api_url());
+    let response = client.send(client.get(&url).header("User-Agent", "egit-cli"))?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Err(EgitError::Auth(format!("{} rejected the token, it is invalid or expired", github_host())));
+    }
+    let response = response.error_for_status()?;
+    let scopes = response.headers().get("x-oauth-scopes")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let user: User = response.json()?;
+    Ok((user.login, scopes))
+}
+
+/// Ask for a token on the terminal without echoing it
+pub fn prompt_token(host: &str) -> Result<String, EgitError> {
+    if !confirm::can_prompt() {
+        return Err(EgitError::Other("No terminal to ask for the token on, pass it on stdin with --with-token".to_string()));
+    }
+    status!("+ Create a token at https://{}/settings/tokens, with read access to the repositories egit should download from", host);
+    Password::new()
+        .with_prompt(format!("Paste a personal access token for {}", host))
+        .interact()
+        .map_err(|e| EgitError::Other(format!("Failed to read the token: {}", e)))
+}
+
+/// `ghp_abcd…` to `ghp_****`, keeping only the kind of token
+pub fn mask(token: &str) -> String {
+    let prefix = match token.split_once('_') {
+        _ if token.starts_with("github_pat_") => "github_pat_",
+        Some((kind, _)) if kind.len() <= 4 => &token[..=kind.len()],
+        _ => "",
+    };
+    format!("{}****", prefix)
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct Credentials {
+    #[serde(default)]
+    tokens: BTreeMap<String, String>,
+}
+
+/// `credentials.toml` next to the config file
+fn credentials_path() -> io::Result<PathBuf> {
+    Ok(config::config_path()?.with_file_name("credentials.toml"))
+}
+
+fn read_credentials() -> io::Result<Credentials> {
+    match fs::read_to_string(credentials_path()?) {
+        Ok(contents) => toml::from_str(&contents).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Credentials::default()),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_credentials(credentials: &Credentials) -> io::Result<()> {
+    let path = credentials_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let contents = format!(
+        "# Written by `egit auth login --insecure-storage`, remove with `egit auth logout`\n\n{}",
+        toml::to_string_pretty(credentials).map_err(io::Error::other)?
+    );
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies to new files
+        if path.exists() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(&path)?.write_all(contents.as_bytes())
+}
+
+/// Drop the token for `host` from the credentials file, deleting the file
+/// once it holds none
+fn remove_from_file(host: &str) -> io::Result<bool> {
+    let mut credentials = read_credentials()?;
+    if credentials.tokens.remove(host).is_none() {
+        return Ok(false);
+    }
+    if credentials.tokens.is_empty() {
+        fs::remove_file(credentials_path()?)?;
+    } else {
+        write_credentials(&credentials)?;
+    }
+    Ok(true)
+}
+
+/// Run a keyring command, feeding it `input` on stdin so the token never
+/// shows up in the process list
+#[cfg(unix)]
+fn run(command: &mut Command, input: Option<&str>) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(e.kind(), format!("`{}` is not installed", command.get_program().to_string_lossy())),
+            _ => e,
+        })?;
+    if let Some(input) = input {
+        child.stdin.take().expect("piped stdin").write_all(input.as_bytes())?;
+    }
+    child.wait_with_output()
+}
+
+/// The stderr of a failed keyring command as an error
+#[cfg(unix)]
+fn failure(output: &Output) -> io::Error {
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    io::Error::other(if message.is_empty() { format!("exited with {}", output.status) } else { message })
+}
+
+#[cfg(target_os = "macos")]
+mod keyring {
+    use std::io;
+    use std::process::Command;
+    use super::{SERVICE, failure, run};
+
+    pub const NAME: &str = "macOS Keychain";
+
+    /// `security` exits with 44 when there is no such item
+    const NOT_FOUND: i32 = 44;
+
+    pub fn get(host: &str) -> io::Result<Option<String>> {
+        let output = run(Command::new("security").args(["find-generic-password", "-s", SERVICE, "-a", host, "-w"]), None)?;
+        match output.status.code() {
+            Some(0) => Ok(Some(String::from_utf8_lossy(&output.stdout).trim().to_string())),
+            Some(NOT_FOUND) => Ok(None),
+            _ => Err(failure(&output)),
+        }
+    }
+
+    pub fn set(host: &str, token: &str) -> io::Result<()> {
+        // Interactive mode reads the command from stdin, keeping the token
+        // out of the arguments
+        let command = format!("add-generic-password -U -s {} -a {} -w {}\n", SERVICE, quote(host)?, quote(token)?);
+        let output = run(Command::new("security").arg("-i"), Some(&command))?;
+        if output.status.success() && output.stderr.is_empty() { Ok(()) } else { Err(failure(&output)) }
+    }
+
+    /// Quote a word of a `security -i` command line. Quotes, backslashes and
+    /// control characters are refused rather than escaped, so no value can
+    /// end the word early or start another command.
+    fn quote(value: &str) -> io::Result<String> {
+        if value.chars().any(|c| c == '"' || c == '\\' || c.is_control()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "quotes, backslashes and control characters can't be stored in the Keychain by egit"));
+        }
+        Ok(format!("\"{}\"", value))
+    }
+
+    pub fn delete(host: &str) -> io::Result<bool> {
+        let output = run(Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", host]), None)?;
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(NOT_FOUND) => Ok(false),
+            _ => Err(failure(&output)),
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod keyring {
+    use std::io;
+    use std::process::Command;
+    use super::{SERVICE, failure, run};
+
+    pub const NAME: &str = "Secret Service keyring";
+
+    pub fn get(host: &str) -> io::Result<Option<String>> {
+        let output = run(Command::new("secret-tool").args(["lookup", "service", SERVICE, "host", host]), None)?;
+        let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        match output.status.success() {
+            true if !token.is_empty() => Ok(Some(token)),
+            // Exits with 1 and prints nothing when there is no such item
+            _ if output.stderr.is_empty() => Ok(None),
+            _ => Err(failure(&output)),
+        }
+    }
+
+    pub fn set(host: &str, token: &str) -> io::Result<()> {
+        let label = format!("egit token for {}", host);
+        let output = run(Command::new("secret-tool").args(["store", "--label", &label, "service", SERVICE, "host", host]), Some(token))?;
+        if output.status.success() { Ok(()) } else { Err(failure(&output)) }
+    }
+
+    pub fn delete(host: &str) -> io::Result<bool> {
+        // `clear` succeeds whether or not there was an item to remove
+        if get(host)?.is_none() {
+            return Ok(false);
+        }
+        let output = run(Command::new("secret-tool").args(["clear", "service", SERVICE, "host", host]), None)?;
+        if output.status.success() { Ok(true) } else { Err(failure(&output)) }
+    }
+}
+
+/// Windows has no command that reads a credential back out of the Credential
+/// Manager (`cmdkey` only stores and lists them), so tokens can only be kept
+/// in the credentials file there for now
+#[cfg(not(unix))]
+mod keyring {
+    use std::io;
+
+    pub const NAME: &str = "OS keyring";
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "egit cannot use the Windows Credential Manager yet")
+    }
+
+    pub fn get(_host: &str) -> io::Result<Option<String>> {
+        Err(unsupported())
+    }
+
+    pub fn set(_host: &str, _token: &str) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn delete(_host: &str) -> io::Result<bool> {
+        Err(unsupported())
+    }
+}
@@ -0,0 +1,289 @@
+//! The download cache under `~/.cache/egit`: downloaded assets keyed by URL
+//! and SHA-256, and API responses kept for conditional requests and
+//! `--offline`
+
+use reqwest::blocking::Response;
+use reqwest::header::{ETAG, HeaderMap, HeaderValue, LAST_MODIFIED, LINK};
+use reqwest::{ResponseBuilderExt, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::checksum::{self, HashWriter, Hasher};
+
+// Release assets downloaded before, under `~/.cache/egit`. Each file is
+// stored once by content in `blobs/<sha256>`; `assets/` holds one small file
+// per asset URL naming the blob, so an asset published under several names
+// or tags takes the space only once:
+//
+//     assets/github.com/BurntSushi/ripgrep/releases/download/14.1.1/rg.tar.gz.sha256
+//     blobs/4cf9f2741e6c465ffdb7c26f38056a59e2a2544b51f7cc128ef28337eeae4d8e
+//
+// `api/` keeps the last response to each API request along with its ETag,
+// so that later requests can be conditional and `--offline` can resolve
+// releases without the network.
+
+/// `$XDG_CACHE_HOME/egit`, or `~/.cache/egit`
+pub fn cache_dir() -> io::Result<PathBuf> {
+    let dir = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => env::home_dir()
+            .map(|home| home.join(".cache"))
+            .ok_or_else(|| io::Error::other("cannot find the home directory"))?,
+    };
+    Ok(dir.join("egit"))
+}
+
+/// A cached asset
+#[derive(Serialize, Debug)]
+pub struct Entry {
+    /// Host and path of the asset URL, e.g. `github.com/owner/repo/releases/download/v1.0/tool.tar.gz`
+    pub key: String,
+    /// The SHA-256 the asset was stored under
+    pub sha256: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Where the cached file is
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// Keep URL path segments usable as file names everywhere
+fn escape(segment: &str) -> String {
+    segment.replace('%', "%25").replace(':', "%3A").replace('\\', "%5C")
+}
+
+/// The key of the asset at `url`: its host and path
+fn key(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let mut key = match url.port() {
+        Some(port) => escape(&format!("{}:{}", url.host_str()?, port)),
+        None => escape(url.host_str()?),
+    };
+    for segment in url.path_segments()? {
+        if segment.is_empty() || segment == "." || segment == ".." {
+            return None;
+        }
+        key.push('/');
+        key.push_str(&escape(segment));
+    }
+    Some(key)
+}
+
+/// The key of an API request: `key` with the query string, if any, folded
+/// into the last segment
+fn api_key(url: &str) -> Option<String> {
+    let mut key = key(url)?;
+    if let Some(query) = Url::parse(url).ok()?.query() {
+        key.push_str("%3F");
+        key.push_str(&escape(query).replace('/', "%2F"));
+    }
+    Some(key)
+}
+
+fn key_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join("assets").join(format!("{}.sha256", key))
+}
+
+fn blob_path(dir: &Path, sha256: &str) -> PathBuf {
+    dir.join("blobs").join(sha256)
+}
+
+/// A temporary name next to `path`, unique to this call, so that stores
+/// running at the same time (`sync -j`) never write to the same file
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}-{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    path.with_file_name(name)
+}
+
+/// The cached copy of the asset at `url`, if there is one
+pub fn lookup(url: &str) -> Option<Entry> {
+    let dir = cache_dir().ok()?;
+    let key = key(url)?;
+    let sha256 = fs::read_to_string(key_path(&dir, &key)).ok()?.trim().to_string();
+    let path = blob_path(&dir, &sha256);
+    let size = fs::metadata(&path).ok()?.len();
+    Some(Entry { key, sha256, size, path })
+}
+
+/// Add the asset at `url`, read from `reader`, to the cache. Files are written
+/// under a temporary name first so an interrupted copy is never picked up.
+pub fn store(url: &str, reader: &mut dyn Read) -> io::Result<()> {
+    let dir = cache_dir()?;
+    let key = key(url).ok_or_else(|| io::Error::other(format!("`{}` cannot be cached", url)))?;
+    let blobs = dir.join("blobs");
+    fs::create_dir_all(&blobs)?;
+
+    // The digest is only known once everything is copied
+    let temp = temp_path(&blobs.join("blob"));
+    let sha256 = match write_blob(reader, &temp) {
+        Ok(sha256) => sha256,
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        },
+    };
+    let blob = blob_path(&dir, &sha256);
+    if blob.exists() {
+        fs::remove_file(&temp)?;
+    } else {
+        fs::rename(&temp, &blob)?;
+    }
+
+    let path = key_path(&dir, &key);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = temp_path(&path);
+    fs::write(&temp, format!("{}\n", sha256))?;
+    fs::rename(&temp, &path)
+}
+
+/// Copy `reader` into `temp` and return its SHA-256. The file is hashed again
+/// once written, so a blob is never named after content it doesn't hold.
+fn write_blob(reader: &mut dyn Read, temp: &Path) -> io::Result<String> {
+    let mut writer = HashWriter::new(fs::File::create(temp)?, Hasher::new(&[]));
+    io::copy(reader, &mut writer)?;
+    let sha256 = writer.digests().sha256;
+    drop(writer);
+    let written = checksum::sha256_reader(fs::File::open(temp)?)?;
+    if written != sha256 {
+        return Err(io::Error::other("the cached copy changed while it was written"));
+    }
+    Ok(sha256)
+}
+
+/// The stored response to an API request
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ApiResponse {
+    /// The HTTP status code
+    pub status: u16,
+    // The `Link` header of paginated responses
+    link: Option<String>,
+    /// The `ETag` header, sent back in `If-None-Match`
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// The `Last-Modified` header, sent back in `If-Modified-Since`
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    body: String,
+}
+
+impl ApiResponse {
+    /// Rebuild the response to `url`
+    pub fn into_response(self, url: &str) -> Response {
+        let mut builder = http::Response::builder()
+            .status(StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK));
+        if let Ok(url) = Url::parse(url) {
+            builder = builder.url(url);
+        }
+        if let Some(link) = self.link {
+            builder = builder.header(LINK, link);
+        }
+        Response::from(builder.body(self.body).expect("valid response"))
+    }
+}
+
+fn api_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join("api").join(format!("{}.json", key))
+}
+
+/// The last response to the API request for `url`, if it was stored
+pub fn lookup_response(url: &str) -> Option<ApiResponse> {
+    let path = api_path(&cache_dir().ok()?, &api_key(url)?);
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Keep the response to the API request for `url`. Only successes and
+/// 404 Not Found are kept, as other errors say nothing about the resource.
+pub fn store_response(url: &str, status: StatusCode, headers: &HeaderMap, body: &str) -> io::Result<()> {
+    if status != StatusCode::OK && status != StatusCode::NOT_FOUND {
+        return Ok(());
+    }
+    let key = api_key(url).ok_or_else(|| io::Error::other(format!("`{}` cannot be cached", url)))?;
+    let header = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok()).map(str::to_string);
+    let stored = ApiResponse {
+        status: status.as_u16(),
+        link: header(LINK),
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+        body: body.to_string(),
+    };
+    let path = api_path(&cache_dir()?, &key);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = temp_path(&path);
+    fs::write(&temp, serde_json::to_string(&stored)?)?;
+    fs::rename(&temp, &path)
+}
+
+/// Every cached asset, sorted by key
+pub fn list() -> io::Result<Vec<Entry>> {
+    let dir = cache_dir()?;
+    let mut keys = Vec::new();
+    collect_keys(&dir.join("assets"), "", &mut keys)?;
+    keys.sort();
+    Ok(keys.into_iter()
+        .filter_map(|key| {
+            let sha256 = fs::read_to_string(key_path(&dir, &key)).ok()?.trim().to_string();
+            let path = blob_path(&dir, &sha256);
+            let size = fs::metadata(&path).ok()?.len();
+            Some(Entry { key, sha256, size, path })
+        })
+        .collect())
+}
+
+fn collect_keys(dir: &Path, prefix: &str, keys: &mut Vec<String>) -> io::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let key = if prefix.is_empty() { name.clone() } else { format!("{}/{}", prefix, name) };
+        if entry.file_type()?.is_dir() {
+            collect_keys(&entry.path(), &key, keys)?;
+        } else if let Some(key) = key.strip_suffix(".sha256") {
+            keys.push(key.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Number of stored files and their total size in bytes
+pub fn size() -> io::Result<(usize, u64)> {
+    let entries = match fs::read_dir(cache_dir()?.join("blobs")) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(e) => return Err(e),
+    };
+    let mut count = 0;
+    let mut bytes = 0;
+    for entry in entries {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            count += 1;
+            bytes += metadata.len();
+        }
+    }
+    Ok((count, bytes))
+}
+
+/// Delete the whole cache, returning what it held as `size` does
+pub fn clean() -> io::Result<(usize, u64)> {
+    let removed = size()?;
+    match fs::remove_dir_all(cache_dir()?) {
+        Ok(()) => Ok(removed),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(removed),
+        Err(e) => Err(e),
+    }
+}
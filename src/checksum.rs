@@ -0,0 +1,171 @@
+//! SHA-256, SHA-512 and BLAKE3 digests, computed while bytes stream past,
+//! and parsing of checksum files published alongside assets
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::io::{self, Read, Write};
+
+/// Compute the lowercase hex SHA-256 digest of everything read from `reader`
+pub fn sha256_reader<R: Read>(reader: R) -> io::Result<String> {
+    Ok(hash_reader(reader, &[])?.sha256)
+}
+
+/// Compute the digests of everything read from `reader`
+pub fn hash_reader<R: Read>(mut reader: R, extra: &[Algorithm]) -> io::Result<Digests> {
+    let mut hasher = Hasher::new(extra);
+    let mut buffer = [0; 8192];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finish())
+}
+
+/// Digests `--hash` can compute on top of the SHA-256 of every download
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Algorithm {
+    /// SHA-512
+    Sha512,
+    /// BLAKE3
+    Blake3,
+}
+
+/// Lowercase hex digests of a downloaded file
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Digests {
+    /// Always computed
+    pub sha256: String,
+    /// With `--hash sha512`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha512: Option<String>,
+    /// With `--hash blake3`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+}
+
+/// Computes the SHA-256 of a stream, along with the `extra` digests, in a
+/// single pass as the bytes go by
+#[derive(Clone)]
+pub struct Hasher {
+    sha256: Sha256,
+    sha512: Option<Sha512>,
+    blake3: Option<Box<blake3::Hasher>>,
+}
+
+impl Hasher {
+    /// A hasher for SHA-256 and the `extra` algorithms
+    pub fn new(extra: &[Algorithm]) -> Self {
+        Hasher {
+            sha256: Sha256::new(),
+            sha512: extra.contains(&Algorithm::Sha512).then(Sha512::new),
+            blake3: extra.contains(&Algorithm::Blake3).then(|| Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// Feed the next bytes of the stream
+    pub fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        if let Some(sha512) = &mut self.sha512 {
+            sha512.update(data);
+        }
+        if let Some(blake3) = &mut self.blake3 {
+            blake3.update(data);
+        }
+    }
+
+    /// The digests of everything fed so far
+    pub fn finish(self) -> Digests {
+        Digests {
+            sha256: format!("{:x}", self.sha256.finalize()),
+            sha512: self.sha512.map(|sha512| format!("{:x}", sha512.finalize())),
+            blake3: self.blake3.map(|blake3| blake3.finalize().to_hex().to_string()),
+        }
+    }
+}
+
+/// Writer that hashes everything passing through it on the way to `inner`
+pub struct HashWriter<W> {
+    inner: W,
+    hasher: Hasher,
+}
+
+impl<W: Write> HashWriter<W> {
+    /// Wrap `inner`, feeding what is written to `hasher`
+    pub fn new(inner: W, hasher: Hasher) -> Self {
+        HashWriter { inner, hasher }
+    }
+
+    /// The digests of everything written so far
+    pub fn digests(&self) -> Digests {
+        self.hasher.clone().finish()
+    }
+}
+
+/// Hashing without writing anywhere, e.g. to account for what an earlier,
+/// resumed download already wrote
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Validate a SHA-256 hex digest given on the command line
+pub fn parse_sha256_arg(value: &str) -> Result<String, String> {
+    if is_sha256_hex(value) {
+        Ok(value.to_lowercase())
+    } else {
+        Err(format!("`{}` is not a 64-character hex SHA-256 digest", value))
+    }
+}
+
+/// Extract the digest from a `<asset>.sha256` file, which holds either just
+/// the hex digest or `sha256sum` output (`<digest>  <filename>`)
+pub fn parse_sha256_file(contents: &str) -> Option<String> {
+    contents
+        .split_whitespace()
+        .next()
+        .filter(|digest| is_sha256_hex(digest))
+        .map(|digest| digest.to_lowercase())
+}
+
+/// Whether a release asset is a combined checksum list such as `SHA256SUMS`
+/// or `<project>_checksums.txt`
+pub fn is_sums_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.starts_with("sha256sums") || name.ends_with("_sha256sums") || name.ends_with("checksums.txt")
+}
+
+/// Find the digest of `asset_name` in `sha256sum` output listing several
+/// files, one `<digest>  <filename>` per line (`*<filename>` in binary mode)
+pub fn find_in_sums(contents: &str, asset_name: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let (digest, name) = line.trim().split_once(char::is_whitespace)?;
+        let name = name.trim_start().trim_start_matches('*').trim_start_matches("./");
+        (name == asset_name && is_sha256_hex(digest)).then(|| digest.to_lowercase())
+    })
+}
+
+fn is_sha256_hex(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
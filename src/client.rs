@@ -0,0 +1,277 @@
+//! The HTTP client behind every request: proxy, CA, mirror and `--resolve`
+//! settings, per-host tokens and the `HttpClient` trait for swapping out the
+//! transport
+
+use reqwest::Url;
+use reqwest::blocking::{self, RequestBuilder, Response};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Settings applied to every request made by the client
+#[derive(Clone, Default)]
+pub struct ClientOptions {
+    /// Hosts to connect to at a fixed address (`--resolve`)
+    pub resolve: Vec<(String, IpAddr)>,
+    /// Token sent to the API host
+    pub token: Option<String>,
+    /// Proxy for all requests, instead of the `HTTPS_PROXY` variables
+    pub proxy: Option<String>,
+    /// Extra root certificates in PEM format
+    pub cacert: Option<PathBuf>,
+    /// Accept any certificate (`--insecure`)
+    pub insecure: bool,
+}
+
+/// Sends the requests of a `Client`. Over the network this is the reqwest
+/// client itself; tests plug in one that answers from fixtures, so that
+/// release lookups and downloads run without a network. Parallel downloads
+/// (`--multithread`) use the async client directly and aren't covered.
+pub trait HttpClient: Send + Sync {
+    /// Send `request` and return its response
+    fn execute(&self, request: blocking::Request) -> reqwest::Result<Response>;
+}
+
+impl HttpClient for blocking::Client {
+    fn execute(&self, request: blocking::Request) -> reqwest::Result<Response> {
+        blocking::Client::execute(self, request)
+    }
+}
+
+/// The blocking client used for API calls and single-stream downloads, along
+/// with the settings it was built from so that the async download engine can
+/// build a matching client
+#[derive(Clone)]
+pub struct Client {
+    // Builds the requests, which `http` sends
+    client: blocking::Client,
+    http: Arc<dyn HttpClient>,
+    options: ClientOptions,
+}
+
+impl Client {
+    /// A client whose requests are answered by `http` instead of the network
+    pub fn with_http(http: impl HttpClient + 'static, options: ClientOptions) -> Client {
+        Client { client: blocking::Client::new(), http: Arc::new(http), options }
+    }
+
+    /// The settings the client was built with
+    pub fn options(&self) -> &ClientOptions {
+        &self.options
+    }
+
+    /// Send a request, logging it with -v and the response headers with -vv
+    pub fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let (_, request) = request.build_split();
+        let mut request = request?;
+        log::debug!("> {} {}", request.method(), request.url());
+        let response = match apply_mirror(request.url_mut()) {
+            Some(mirrors) => {
+                request.headers_mut().remove(header::AUTHORIZATION);
+                mirrors.client.execute(request)?
+            },
+            None => self.http.execute(request)?,
+        };
+        log_response(response.status(), response.url(), response.headers());
+        Ok(response)
+    }
+}
+
+impl Deref for Client {
+    type Target = blocking::Client;
+
+    fn deref(&self) -> &blocking::Client {
+        &self.client
+    }
+}
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Serve API responses and assets from the cache only (`--offline`)
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Whether `--offline` is set
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Parse a `HOST:IP` pair given to `--resolve`
+pub fn parse_resolve(value: &str) -> Result<(String, IpAddr), String> {
+    let (host, ip) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected HOST:IP, got `{}`", value))?;
+    if host.is_empty() {
+        return Err(format!("missing host in `{}`", value));
+    }
+    let ip = ip
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .map_err(|_| format!("invalid IP address in `{}`", value))?;
+    Ok((host.to_string(), ip))
+}
+
+/// URL prefixes and their replacements, longest prefix first
+struct Mirrors {
+    rewrites: Vec<(String, String)>,
+    // Sends requests that a rewrite moved to another host, so that tokens
+    // are never sent to a mirror
+    client: blocking::Client,
+    async_client: reqwest::Client,
+}
+
+static MIRRORS: OnceLock<Mirrors> = OnceLock::new();
+
+/// Rewrite URLs starting with a configured prefix before every request
+pub fn set_mirrors(mirrors: &BTreeMap<String, String>, options: &ClientOptions) -> Result<(), String> {
+    if mirrors.is_empty() {
+        return Ok(());
+    }
+    for (prefix, replacement) in mirrors {
+        for url in [prefix, replacement] {
+            Url::parse(url).map_err(|e| format!("`{}` is not a URL: {}", url, e))?;
+        }
+    }
+    let mut rewrites: Vec<_> = mirrors.iter().map(|(prefix, replacement)| (prefix.clone(), replacement.clone())).collect();
+    rewrites.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+    let options = ClientOptions { token: None, ..options.clone() };
+    let client = build_client(&options)?.client;
+    let async_client = build_async_client(&options)?;
+    let _ = MIRRORS.set(Mirrors { rewrites, client, async_client });
+    Ok(())
+}
+
+/// The mirror URL for `url`, if a configured prefix matches
+fn rewrite(url: &Url) -> Option<Url> {
+    let (prefix, replacement) = MIRRORS.get()?.rewrites.iter().find(|(prefix, _)| url.as_str().starts_with(prefix.as_str()))?;
+    Url::parse(&format!("{}{}", replacement, &url.as_str()[prefix.len()..])).ok()
+}
+
+/// `Client::send` for the async client of the download engine
+pub async fn send_async(request: reqwest::RequestBuilder) -> reqwest::Result<reqwest::Response> {
+    let (mut client, request) = request.build_split();
+    let mut request = request?;
+    if let Some(mirrors) = apply_mirror(request.url_mut()) {
+        client = mirrors.async_client.clone();
+        request.headers_mut().remove(header::AUTHORIZATION);
+    }
+    log::debug!("> {} {}", request.method(), request.url());
+    let response = client.execute(request).await?;
+    log_response(response.status(), response.url(), response.headers());
+    Ok(response)
+}
+
+/// Point a request at the mirror of its URL, if any. Returns the mirrors when
+/// the request moved to another host and must be sent without the token,
+/// whether it comes from the client or the request itself.
+fn apply_mirror(url: &mut Url) -> Option<&'static Mirrors> {
+    let mirror = rewrite(url)?;
+    log::debug!("> {} is mirrored at {}", url, mirror);
+    let moved = mirror.host_str() != url.host_str();
+    *url = mirror;
+    MIRRORS.get().filter(|_| moved)
+}
+
+fn log_response(status: reqwest::StatusCode, url: &Url, headers: &HeaderMap) {
+    log::debug!("< {} {}", status, url);
+    for (name, value) in headers {
+        log::trace!("< {}: {}", name, value.to_str().unwrap_or("<binary>"));
+    }
+}
+
+/// Check a proxy URL given to `--proxy`, e.g. `socks5h://127.0.0.1:1080`
+pub fn parse_proxy(value: &str) -> Result<String, String> {
+    reqwest::Proxy::all(value).map_err(|e| e.to_string())?;
+    Ok(value.to_string())
+}
+
+/// Apply the settings in `ClientOptions` to a client builder, returning early
+/// from the calling function on invalid settings. The blocking and async
+/// builders share these methods but no trait.
+macro_rules! configure {
+    ($builder:expr, $options:expr) => {{
+        let mut builder = $builder;
+        let options: &ClientOptions = $options;
+
+        // Authenticate every request; reqwest drops the header when a redirect
+        // leaves the original host, so CDN downloads never see the token
+        if let Some(token) = &options.token {
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|_| "the token contains invalid characters".to_string())?;
+            value.set_sensitive(true);
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, value);
+            builder = builder.default_headers(headers);
+        }
+
+        // Pin DNS resolution while keeping the URL host for SNI and certificate
+        // verification. Port 0 keeps the conventional port for the URL scheme.
+        for (host, ip) in &options.resolve {
+            builder = builder.resolve(host, SocketAddr::new(*ip, 0));
+        }
+
+        // reqwest reads the `*_PROXY` environment variables unless a proxy is
+        // given explicitly. Hosts in `NO_PROXY` bypass an explicit proxy too.
+        if let Some(proxy) = &options.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| format!("invalid proxy `{}`: {}", proxy, e))?
+                .no_proxy(reqwest::NoProxy::from_env());
+            builder = builder.proxy(proxy);
+        }
+
+        // Trusted on top of the system roots, as the proxies that need this
+        // usually only intercept some hosts
+        if let Some(path) = &options.cacert {
+            let pem = fs::read(path).map_err(|e| format!("failed to read `{}`: {}", path.display(), e))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("invalid certificate in `{}`: {}", path.display(), e))?;
+            if certificates.is_empty() {
+                return Err(format!("no certificates found in `{}`", path.display()));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if options.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        builder
+    }};
+}
+
+/// How long connecting, or waiting for the next bytes of a response, may take
+/// before a request fails with a timeout, which `retry` treats as transient.
+/// Shared by both clients, so a stalled server is given up on after the same
+/// time whether a download runs in one stream or in parallel chunks.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Build the HTTP client shared by all requests. Everything but parallel
+/// downloads is blocking; see `build_async_client`.
+pub fn build_client(options: &ClientOptions) -> Result<Client, String> {
+    // The blocking timeout applies to connecting and to each read
+    let builder = configure!(blocking::Client::builder().timeout(TIMEOUT), options);
+    let client = builder.build().map_err(|e| e.to_string())?;
+    Ok(Client { client: client.clone(), http: Arc::new(client), options: options.clone() })
+}
+
+/// Build the client of parallel downloads (`--multithread`), the only part of
+/// egit that runs on the async runtime, where many range requests share a few
+/// threads. As with the blocking client, the timeouts apply to connecting and
+/// to each read rather than to the whole transfer, so a large chunk may take
+/// as long as it needs while a stalled connection still fails.
+pub fn build_async_client(options: &ClientOptions) -> Result<reqwest::Client, String> {
+    let builder = reqwest::Client::builder()
+        .connect_timeout(TIMEOUT)
+        .read_timeout(TIMEOUT);
+    configure!(builder, options).build().map_err(|e| e.to_string())
+}
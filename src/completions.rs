@@ -0,0 +1,73 @@
+use clap_complete::Shell;
+use regex::Regex;
+
+// Subcommands whose package argument completes to installed packages and
+// aliases
+const PACKAGE_COMMANDS: &str = "download list info latest changelog install uninstall upgrade";
+
+// The completion script for `shell`. Bash, zsh and fish also complete
+// package names by asking `egit __complete-packages` for them; the other
+// shells only get the static script.
+pub fn script(shell: Shell, command: &mut clap::Command) -> String {
+    let mut buffer = Vec::new();
+    clap_complete::generate(shell, command, "egit", &mut buffer);
+    let script = String::from_utf8_lossy(&buffer).into_owned();
+    match shell {
+        Shell::Bash => bash(script),
+        Shell::Zsh => zsh(script),
+        Shell::Fish => fish(script),
+        _ => script,
+    }
+}
+
+// Wrap the generated function, adding package names once one of the
+// package subcommands appears on the command line
+fn bash(script: String) -> String {
+    let wrapper = format!(r#"
+_egit_with_packages() {{
+    _egit "$@"
+    local word
+    for word in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+        case " {} " in
+            *" ${{word}} "*)
+                if [[ "$2" != -* ]]; then
+                    COMPREPLY+=( $(compgen -W "$(egit __complete-packages 2>/dev/null)" -- "$2") )
+                fi
+                return 0
+                ;;
+        esac
+    done
+}}
+"#, PACKAGE_COMMANDS);
+    let script = script.replace("complete -F _egit ", "complete -F _egit_with_packages ");
+    match script.rfind("\nif [[ \"${BASH_VERSINFO[0]}\"") {
+        Some(at) => format!("{}{}{}", &script[..at], wrapper, &script[at..]),
+        None => script + &wrapper,
+    }
+}
+
+// Complete `package` arguments with a function listing the packages instead
+// of file names
+fn zsh(script: String) -> String {
+    let package = Regex::new(r"(':+package(?: -- [^:]*)?):_default'").unwrap();
+    let script = package.replace_all(&script, "$1:_egit_packages'").into_owned();
+    let function = r#"
+(( $+functions[_egit_packages] )) ||
+_egit_packages() {
+    local -a packages
+    packages=(${(f)"$(egit __complete-packages 2>/dev/null)"})
+    _describe -t packages 'package' packages
+}
+"#;
+    match script.find('\n') {
+        // After the `#compdef` line, which must stay first
+        Some(at) => format!("{}{}{}", &script[..=at], function, &script[at + 1..]),
+        None => script + function,
+    }
+}
+
+fn fish(script: String) -> String {
+    format!(
+        "{}complete -c egit -n \"__fish_seen_subcommand_from {}\" -a \"(egit __complete-packages 2>/dev/null)\"\n",
+        script, PACKAGE_COMMANDS)
+}
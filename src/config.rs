@@ -0,0 +1,226 @@
+//! The configuration file `~/.config/egit/config.toml`: defaults for the
+//! command line flags, package aliases and per-host settings
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use toml::{Table, Value};
+use crate::output;
+
+/// User defaults from `~/.config/egit/config.toml`. Command-line flags and
+/// environment variables take precedence over every setting.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// GitHub token, used when neither `--token` nor the environment sets one
+    /// and `tokens` has none for the host
+    pub token: Option<String>,
+    /// Directory downloads are saved under (`--dir`)
+    pub dir: Option<PathBuf>,
+    /// Number of threads for parallel downloads (`--threads`)
+    pub threads: Option<usize>,
+    /// GitHub API base URL (`--api-url`)
+    pub api_url: Option<String>,
+    /// Proxy for all requests (`--proxy`), e.g. `http://proxy.example.com:8080`
+    pub proxy: Option<String>,
+    /// Extra CA certificates to trust, in PEM format (`--cacert`)
+    pub cacert: Option<PathBuf>,
+    /// Base URL of the instance for each provider, e.g. `gitlab = "https://gitlab.example.com"`
+    #[serde(default)]
+    pub hosts: BTreeMap<String, String>,
+    /// Token for each host, e.g. `"github.example.com" = "ghp_xxx"` or
+    /// `"gitlab.com" = "glpat-xxx"`, picked by the host a package comes from
+    #[serde(default)]
+    pub tokens: BTreeMap<String, String>,
+    /// Asset pattern for each repository, e.g. `"BurntSushi/ripgrep" = "*musl*"`
+    #[serde(default)]
+    pub assets: BTreeMap<String, String>,
+    /// Short names for packages, e.g. `rg = "BurntSushi/ripgrep"`
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// URL prefixes rewritten before every request, e.g.
+    /// `"https://github.com/" = "https://mirror.example.com/github/"`
+    #[serde(default)]
+    pub mirrors: BTreeMap<String, String>,
+}
+
+/// Settings that hold a table of named values rather than a single value
+const TABLES: [&str; 5] = ["hosts", "tokens", "assets", "aliases", "mirrors"];
+
+static ALIASES: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Use the configured aliases when parsing package names
+pub fn set_aliases(aliases: BTreeMap<String, String>) {
+    let _ = ALIASES.set(aliases);
+}
+
+/// Replace an alias at the start of `name[@version]` with its package
+pub fn expand_alias(package: &str) -> String {
+    let (name, version) = match package.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (package, None),
+    };
+    match (ALIASES.get().and_then(|aliases| aliases.get(name)), version) {
+        (Some(target), Some(version)) => format!("{}@{}", target, version),
+        (Some(target), None) => target.clone(),
+        (None, _) => package.to_string(),
+    }
+}
+
+/// Check an alias name and the package it stands for
+pub fn parse_alias(name: &str, package: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', ':', '@']) {
+        return Err(format!("alias `{}` must not contain `/`, `:` or `@`", name));
+    }
+    if !package.contains('/') || package.contains('@') {
+        return Err(format!("expected a package like `owner/repo` without a version, got `{}`", package));
+    }
+    Ok(())
+}
+
+/// `$XDG_CONFIG_HOME/egit/config.toml`, or `~/.config/egit/config.toml`
+pub fn config_path() -> io::Result<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => env::home_dir()
+            .map(|home| home.join(".config"))
+            .ok_or_else(|| io::Error::other("cannot find the home directory"))?,
+    };
+    Ok(dir.join("egit").join("config.toml"))
+}
+
+impl Config {
+    /// Read the config, using the defaults when there is no config file
+    pub fn load() -> io::Result<Config> {
+        Config::from_table(read_table()?).map_err(io::Error::other)
+    }
+
+    fn from_table(table: Table) -> Result<Config, String> {
+        Value::Table(table).try_into().map_err(|e: toml::de::Error| e.message().to_string())
+    }
+}
+
+/// The config file as a raw TOML table, to edit it without losing settings
+pub fn read_table() -> io::Result<Table> {
+    match fs::read_to_string(config_path()?) {
+        Ok(contents) => contents.parse().map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Table::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replace the config file with `table`
+pub fn write_table(table: &Table) -> io::Result<()> {
+    let path = config_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, toml::to_string_pretty(table).map_err(io::Error::other)?)
+}
+
+/// Split `hosts.gitlab` into the table and the name inside it. Only the first
+/// dot separates them, as repository names may contain dots.
+fn split_key(key: &str) -> (&str, Option<&str>) {
+    match key.split_once('.') {
+        Some((table, name)) if TABLES.contains(&table) => (table, Some(name)),
+        _ => (key, None),
+    }
+}
+
+/// Look up a setting; `hosts.gitlab` and the like reach into a table
+pub fn get<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    match split_key(key) {
+        (table_key, Some(name)) => table.get(table_key)?.as_table()?.get(name),
+        (key, None) => table.get(key),
+    }
+}
+
+/// Set `key` to `value`, stored as a number or boolean when the setting
+/// expects one
+pub fn set(table: &mut Table, key: &str, value: &str) -> Result<(), String> {
+    let mut candidates = vec![Value::String(value.to_string())];
+    if let Ok(number) = value.parse() {
+        candidates.push(Value::Integer(number));
+    }
+    if let Ok(flag) = value.parse() {
+        candidates.push(Value::Boolean(flag));
+    }
+
+    let mut error = String::new();
+    for candidate in candidates {
+        let mut updated = table.clone();
+        match split_key(key) {
+            (table_key, Some(name)) => {
+                let Some(inner) = updated.entry(table_key).or_insert_with(|| Value::Table(Table::new())).as_table_mut() else {
+                    return Err(format!("`{}` is not a table", table_key));
+                };
+                inner.insert(name.to_string(), candidate);
+            },
+            (key, None) if TABLES.contains(&key) => {
+                return Err(format!("`{}` is a table, set `{}.<name>` instead", key, key));
+            },
+            (key, None) => {
+                updated.insert(key.to_string(), candidate);
+            },
+        }
+        match Config::from_table(updated.clone()) {
+            Ok(_) => {
+                *table = updated;
+                return Ok(());
+            },
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// Remove `key`, returning whether it was set
+pub fn unset(table: &mut Table, key: &str) -> bool {
+    match split_key(key) {
+        (table_key, Some(name)) => {
+            let Some(inner) = table.get_mut(table_key).and_then(Value::as_table_mut) else {
+                return false;
+            };
+            let removed = inner.remove(name).is_some();
+            if inner.is_empty() {
+                table.remove(table_key);
+            }
+            removed
+        },
+        (key, None) => table.remove(key).is_some(),
+    }
+}
+
+/// Every setting as `key = value`, with nested tables flattened to dotted
+/// keys. Tokens are masked.
+pub fn display_config(table: &Table) {
+    let mut settings = Vec::new();
+    for (key, value) in table {
+        match value {
+            Value::Table(inner) if key == "tokens" => {
+                settings.extend(inner.keys().map(|name| (format!("{}.{}", key, name), Value::String("********".to_string()))));
+            },
+            Value::Table(inner) => {
+                settings.extend(inner.iter().map(|(name, value)| (format!("{}.{}", key, name), value.clone())));
+            },
+            _ if key == "token" => settings.push((key.clone(), Value::String("********".to_string()))),
+            _ => settings.push((key.clone(), value.clone())),
+        }
+    }
+
+    if output::is_json() {
+        output::print_json(&settings.into_iter().collect::<BTreeMap<_, _>>());
+        return;
+    }
+    output!("=== Settings ===");
+    if settings.is_empty() {
+        output!("- No settings, the defaults are used");
+    }
+    for (key, value) in &settings {
+        output!("{} = {}", key, value);
+    }
+}
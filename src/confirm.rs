@@ -0,0 +1,28 @@
+//! Yes/no prompts before destructive or large operations
+
+use dialoguer::Confirm;
+use std::io::{self, IsTerminal};
+
+/// Ask the user to confirm a destructive or large operation. Answers yes
+/// without prompting when `assume_yes` is set, and no when there is no
+/// terminal to ask on, so nothing destructive happens unattended without
+/// --yes.
+pub fn confirm(prompt: &str, assume_yes: bool) -> bool {
+    if assume_yes {
+        return true;
+    }
+    if !can_prompt() {
+        return false;
+    }
+
+    Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .unwrap_or(false)
+}
+
+/// Whether there is a terminal to ask the user on
+pub fn can_prompt() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
@@ -0,0 +1,1336 @@
+//! The download engine: picking an asset, fetching it (resumable, in
+//! parallel, from the cache or to stdout), verifying its digest and
+//! signature, and unpacking it
+
+use indicatif::{HumanBytes, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use reqwest::StatusCode;
+use reqwest::header::ACCEPT;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use crate::assets::{self, Contents, GitHubAsset, GitHubRelease};
+use crate::cache;
+use crate::checksum::{self, Digests, HashWriter, Hasher};
+use crate::client::{self, Client};
+use crate::confirm;
+use crate::error::{Context, EgitError};
+use crate::extract;
+use crate::install;
+use crate::multitread;
+use crate::output;
+use crate::picker;
+use crate::progress;
+use crate::retry;
+use crate::select;
+use crate::sparkline;
+use crate::storage::Storage;
+use crate::throttle;
+use crate::verify;
+
+/// Custom reader that updates a progress bar as it reads data
+struct ProgressReader<R> {
+    reader: R,
+    progress_bar: ProgressBar,
+    bytes_read: u64,
+    sparkline: Option<sparkline::Sparkline>,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.reader.read(buf)?;
+        throttle::consume(bytes_read);
+        progress::inc(bytes_read as u64);
+        self.bytes_read += bytes_read as u64;
+        self.progress_bar.set_position(self.bytes_read);
+        if let Some(rendered) = self.sparkline.as_mut().and_then(|s| s.sample(self.bytes_read)) {
+            self.progress_bar.set_message(rendered);
+        }
+        Ok(bytes_read)
+    }
+}
+
+/// Reader that copies everything read through it into `writer`, so a single
+/// pass over a response can unpack, hash and keep it
+struct TeeReader<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.reader.read(buf)?;
+        self.writer.write_all(&buf[..bytes_read])?;
+        Ok(bytes_read)
+    }
+}
+
+/// Options shared by the asset and source download paths
+#[derive(Default)]
+pub struct DownloadOptions {
+    /// Download in parallel chunks (`--multithread`)
+    pub multithread: bool,
+    /// How many chunks to download at once
+    pub threads: usize,
+    /// Draw a chart of the recent speed next to the progress bar
+    pub sparkline: bool,
+    /// Record the tag in `<file>.version` next to each download
+    pub write_version_file: bool,
+    /// Record the tag in this file instead
+    pub version_file: Option<PathBuf>,
+    /// Answer yes to every prompt (`--yes`)
+    pub assume_yes: bool,
+    /// Glob, `/regex/` or substring the asset name must match
+    pub asset: Option<String>,
+    /// Allow pre-releases
+    pub pre: bool,
+    /// Pick the asset for this platform among those matching `asset`
+    pub auto: bool,
+    /// Never show the asset picker
+    pub no_interactive: bool,
+    /// Download every asset of the release
+    pub all_assets: bool,
+    /// SHA-256 the download must have, instead of the one published
+    /// with the release
+    pub sha256: Option<String>,
+    /// Require a valid signature for this public key
+    pub pubkey: Option<PathBuf>,
+    /// Require a keyless Sigstore bundle signed by this identity
+    pub sigstore: Option<verify::Identity>,
+    /// Start over instead of resuming a `.part` file
+    pub no_resume: bool,
+    /// Overwrite existing files without asking
+    pub force: bool,
+    /// Leave existing files alone and count them as downloaded
+    pub skip_existing: bool,
+    /// Neither read nor fill the download cache
+    pub no_cache: bool,
+    /// Save the download to this path (a directory with `all_assets`)
+    pub output: Option<PathBuf>,
+    /// Directory to save the download in, under its own name
+    pub dir: Option<PathBuf>,
+    /// Write the download to stdout instead of a file
+    pub stdout: bool,
+    /// A token is in use, so assets are fetched through the API, which
+    /// also serves those of private repositories
+    pub authenticated: bool,
+    /// How many times to retry a request that failed transiently
+    pub retries: u32,
+    /// Unpack the downloaded archive
+    pub extract: bool,
+    /// Unpack into this directory instead of one named after the archive
+    pub extract_to: Option<PathBuf>,
+    /// Leading path components to drop from archive entries
+    pub strip_components: Option<usize>,
+    /// Also save the archive when a source download is unpacked as it
+    /// streams
+    pub keep_archive: bool,
+    /// Print what would be downloaded instead of downloading it
+    pub dry_run: bool,
+    /// Refuse assets larger than this many bytes
+    pub max_size: Option<u64>,
+    /// Ask before downloading more assets than this with --all-assets
+    pub max_assets: Option<usize>,
+    /// Refuse assets whose content type differs, such as `application/gzip`
+    /// or `application/*`
+    pub content_type: Option<String>,
+    /// Digests to compute besides SHA-256 while downloading
+    pub hashes: Vec<checksum::Algorithm>,
+}
+
+impl DownloadOptions {
+    /// Where to save a file that would be called `name` in the current
+    /// directory: inside --output when it is a directory (always the case for
+    /// --all-assets), or --output itself, all under --dir when given
+    pub fn destination(&self, name: &str) -> String {
+        let dir = self.dir.as_deref().unwrap_or(Path::new(""));
+        match &self.output {
+            Some(output) if self.all_assets || dir.join(output).is_dir() || output.as_os_str().to_string_lossy().ends_with(std::path::is_separator) => {
+                dir.join(output).join(name).to_string_lossy().into_owned()
+            },
+            Some(output) => dir.join(output).to_string_lossy().into_owned(),
+            None => dir.join(name).to_string_lossy().into_owned(),
+        }
+    }
+    
+    /// Where to record the downloaded tag for `filename`, if requested
+    pub fn version_file_for(&self, filename: &str) -> Option<PathBuf> {
+        match &self.version_file {
+            Some(path) => Some(path.clone()),
+            None if self.write_version_file => Some(PathBuf::from(format!("{}.version", filename))),
+            None => None,
+        }
+    }
+}
+
+/// Download the asset of `release` that --asset and the platform select, or
+/// every asset with --all-assets
+pub fn download_asset(client: &Client, storage: &dyn Storage, release: &GitHubRelease, package: &str, options: &DownloadOptions) -> Result<(), EgitError> {
+    if options.all_assets {
+        if release.assets.is_empty() {
+            status!("- No assets found for this release");
+        }
+        let total: u64 = release.assets.iter().map(|asset| asset.size).sum();
+        status!("+ `{}@{}` has {} assets, {} in total", package, release.tag_name, release.assets.len(), HumanBytes(total));
+        if let Some(max) = options.max_assets
+            && release.assets.len() > max
+            && !options.dry_run
+            && !confirm::confirm(&format!("Download all {} assets, more than --max-assets {}?", release.assets.len(), max), options.assume_yes) {
+            return Err(EgitError::Other(format!(
+                "`{}@{}` has {} assets, more than --max-assets {}; pass --yes to download them all", package, release.tag_name, release.assets.len(), max)));
+        }
+        let _batch = progress::begin_batch(if options.dry_run { 0 } else { release.assets.len() }, Some(total));
+        for asset in &release.assets {
+            download_release_asset(client, storage, release, asset, package, options)?;
+        }
+        if !options.dry_run {
+            status!("+ Downloaded {} assets of `{}@{}`", release.assets.len(), package, release.tag_name);
+        }
+    } else if let Some(asset) = select_asset(release, options)? {
+        download_release_asset(client, storage, release, asset, package, options)?;
+    }
+    output::task_end();
+    Ok(())
+}
+
+/// The digest to check `asset` against: --sha256, then the digest the API
+/// reports for the asset, or else one published in the release
+pub fn expected_sha256(client: &Client, release: &GitHubRelease, asset: &GitHubAsset, options: &DownloadOptions) -> Result<Option<String>, EgitError> {
+    if let Some(sha256) = &options.sha256 {
+        return Ok(Some(sha256.clone()));
+    }
+    if let Some(sha256) = asset.sha256() {
+        status!("+ Verifying download against the digest of `{}` from the API...", asset.name);
+        return Ok(Some(sha256.to_string()));
+    }
+    fetch_release_sha256(client, release, &asset.name, options)
+}
+
+/// Download `asset` into its destination, or to stdout, then verify,
+/// cache and extract it as the options ask. Returns the digests computed
+/// while downloading, or `None` when nothing was downloaded (--dry-run, or
+/// a file --skip-existing kept).
+pub fn download_release_asset(client: &Client, storage: &dyn Storage, release: &GitHubRelease, asset: &GitHubAsset, package: &str, options: &DownloadOptions) -> Result<Option<Digests>, EgitError> {
+    preflight(asset, options)?;
+    if options.dry_run {
+        let filename = if options.stdout { "stdout".to_string() } else { options.destination(&asset.name) };
+        if !options.stdout && storage.exists(&filename) && !options.force && options.skip_existing {
+            output!("+ `{}` already exists, would skip it", filename);
+            return Ok(None);
+        }
+        plan_download("download", package, release, asset, &filename, options);
+        if options.extract && let Some(dest) = extract_destination(asset, &filename, options) {
+            output!("  and extract it into `{}`", dest.display());
+        }
+        return Ok(None);
+    }
+    
+    if options.stdout {
+        // Look up the digest first; streamed bytes can't be checked afterwards
+        let expected = expected_sha256(client, release, asset, options)?;
+        status!("+ Downloading `{}@{} -> stdout`...", package, release.tag_name);
+        summarize(asset, options);
+        return stream_to_stdout(client, asset.download_url(options.authenticated), asset.size, expected.as_deref(), options).map(Some);
+    }
+    
+    let filename = options.destination(&asset.name);
+    status!("+ Downloading `{}@{} -> {}`...", 
+             package, release.tag_name, filename);
+    summarize(asset, options);
+    
+    if !confirm_overwrite(storage, &filename, options)? {
+        record_download(&filename, asset.size, 0.0);
+        progress::file_done();
+        return Ok(None);
+    }
+    
+    let total_size = asset.size;
+    let start_time = std::time::Instant::now();
+    
+    // Known before downloading, so a mismatch is caught before an existing
+    // file is replaced
+    let expected = expected_sha256(client, release, asset, options)?;
+    let (cached, digests) = fetch_asset(client, storage, asset, &filename, expected.as_deref(), options)?;
+    
+    // Calculate accurate download time
+    let elapsed = start_time.elapsed().as_secs_f64();
+    
+    status!("+ Downloaded `{}@{}`{} , total size: {:.1}KB | spend {:.1}s.", 
+             package, release.tag_name, if cached { " (cached)" } else { "" }, total_size as f64 / 1024.0, elapsed);
+    record_download(&filename, total_size, elapsed);
+    output::record(|r| r.cached = Some(cached));
+    report_digests(&filename, &digests, expected.is_some());
+    
+    if options.pubkey.is_some() || options.sigstore.is_some() {
+        verify_signature(client, storage, release, asset, &filename, options)?;
+    }
+    
+    if !cached {
+        cache_asset(storage, asset, &filename, options);
+    }
+    
+    if let Some(path) = options.version_file_for(&filename) {
+        write_version_file(storage, &path, &release.tag_name)?;
+    }
+    
+    if options.extract {
+        extract_asset(storage, asset, &filename, options)?;
+    }
+    Ok(Some(digests))
+}
+
+/// Unpack a downloaded asset into --extract-to, or next to it
+pub fn extract_asset(storage: &dyn Storage, asset: &GitHubAsset, filename: &str, options: &DownloadOptions) -> Result<(), EgitError> {
+    match extract_destination(asset, filename, options) {
+        Some(dest) => extract_download(storage, filename, &dest, options)?,
+        None => status!("- `{}` is not an archive, skipping extraction", asset.name),
+    }
+    Ok(())
+}
+
+/// Check `asset` before fetching it: against --max-size and --content-type,
+/// and that it isn't a checksum or signature file picked in place of
+/// the download itself. Sidecar files can still be downloaded by giving their
+/// exact name to --asset, or with --all-assets.
+pub fn preflight(asset: &GitHubAsset, options: &DownloadOptions) -> Result<(), EgitError> {
+    if let Some(max_size) = options.max_size && asset.size > max_size {
+        return Err(EgitError::Verification(format!("`{}` is {:.1}KB, larger than --max-size {:.1}KB",
+            asset.name, asset.size as f64 / 1024.0, max_size as f64 / 1024.0)));
+    }
+    if let Some(expected) = &options.content_type {
+        let actual = asset.content_type.as_deref().unwrap_or("unknown");
+        if !content_type_matches(actual, expected) {
+            return Err(EgitError::Verification(format!("`{}` is {}, not the expected {}", asset.name, actual, expected)));
+        }
+    }
+    let requested = options.all_assets || options.asset.as_deref() == Some(asset.name.as_str());
+    if select::is_sidecar(&asset.name) && !requested {
+        return Err(EgitError::Other(format!(
+            "`{}` looks like a checksum, signature or metadata file, not the download itself. Pass `--asset '{}'` to download it anyway", asset.name, asset.name)));
+    }
+    Ok(())
+}
+
+/// Compare the essence of a content type (without parameters such as
+/// `charset`), allowing `type/*` wildcards
+fn content_type_matches(actual: &str, expected: &str) -> bool {
+    let essence = |value: &str| value.split(';').next().unwrap_or_default().trim().to_lowercase();
+    let (actual, expected) = (essence(actual), essence(expected));
+    match expected.strip_suffix("/*") {
+        Some(kind) => actual.split('/').next() == Some(kind),
+        None => actual == expected,
+    }
+}
+
+/// Print what is about to be fetched: the asset, its size and type, where it
+/// comes from and what it will be checked against
+fn summarize(asset: &GitHubAsset, options: &DownloadOptions) {
+    status!("  Asset:  {} ({:.1}KB, {})", asset.name, asset.size as f64 / 1024.0, asset.content_type.as_deref().unwrap_or("unknown type"));
+    status!("  From:   {}", asset.download_url(options.authenticated));
+    let check = if options.sha256.is_some() {
+        "SHA-256 given with --sha256"
+    } else if asset.sha256().is_some() {
+        "SHA-256 reported by the API"
+    } else {
+        "SHA-256 from the release checksums, if published"
+    };
+    match (&options.pubkey, &options.sigstore) {
+        (Some(key), _) => status!("  Check:  {} and a signature for `{}`", check, key.display()),
+        (None, Some(sigstore)) => status!("  Check:  {} and a Sigstore bundle signed by `{}`", check, sigstore.identity),
+        (None, None) => status!("  Check:  {}", check),
+    }
+}
+
+/// Where `asset`, downloaded to `filename`, is unpacked, if it is an archive
+fn extract_destination(asset: &GitHubAsset, filename: &str, options: &DownloadOptions) -> Option<PathBuf> {
+    extract::is_archive(&asset.name).then(|| options.extract_to.clone()
+        .unwrap_or_else(|| Path::new(filename).with_file_name(extract::archive_stem(&asset.name))))
+}
+
+/// With --dry-run: print the URL, size and destination `asset` would be
+/// fetched with, and record them in the JSON report. `action` says what
+/// would be done with it, such as `download` or `install`.
+pub fn plan_download(action: &str, package: &str, release: &GitHubRelease, asset: &GitHubAsset, destination: &str, options: &DownloadOptions) {
+    let url = asset.download_url(options.authenticated);
+    output!("+ Would {} `{}@{}` from {} ({:.1}KB) -> {}", 
+            action, package, release.tag_name, url, asset.size as f64 / 1024.0, destination);
+    record_plan(url, Some(asset.size), destination);
+}
+
+fn record_plan(url: &str, size: Option<u64>, destination: &str) {
+    output::record(|r| {
+        r.success = true;
+        r.planned.push(output::Planned {
+            url: url.to_string(),
+            size,
+            destination: destination.to_string(),
+        });
+    });
+}
+
+/// Pick the asset to download, narrowed by --asset and --auto. On a terminal
+/// a menu is shown with the best platform match preselected; otherwise the
+/// best match is taken and ambiguous picks are listed.
+pub fn select_asset<'a>(release: &'a GitHubRelease, options: &DownloadOptions) -> Result<Option<&'a GitHubAsset>, EgitError> {
+    if release.assets.is_empty() {
+        return Ok(None);
+    }
+    
+    let candidates = select::filter_assets(&release.assets, options.asset.as_deref(), options.auto);
+    match candidates.as_slice() {
+        [asset] => return Ok(Some(asset)),
+        [] => {
+            assets::display_assets(release);
+            return Err(EgitError::NotFound("No asset matches the selection".to_string()));
+        },
+        _ => {},
+    }
+    
+    let interactive = !options.no_interactive && !options.assume_yes && picker::is_interactive();
+    // An explicit pattern must pick exactly one asset so scripts stay deterministic
+    let explicit = options.asset.is_some() && !options.auto;
+    let best = if explicit {
+        Err(candidates.clone())
+    } else {
+        select::best_for_platform(&candidates)
+    };
+    
+    let pick = |choices: &[&'a GitHubAsset], default: usize| {
+        picker::pick_asset("Select an asset to download", choices, default)
+            .map(Some)
+            .ok_or_else(|| EgitError::Other("No asset selected".to_string()))
+    };
+    
+    if interactive && !explicit {
+        let default = best.as_ref().ok()
+            .and_then(|best| candidates.iter().position(|a| std::ptr::eq(*a, *best)))
+            .unwrap_or(0);
+        return pick(&candidates, default);
+    }
+    
+    let ambiguous = match best {
+        Ok(asset) => {
+            status!("+ Selected `{}` for {}/{}", asset.name, std::env::consts::OS, std::env::consts::ARCH);
+            return Ok(Some(asset));
+        },
+        Err(tied) => tied,
+    };
+    
+    if interactive {
+        return pick(&ambiguous, 0);
+    }
+    status!("- {} assets match the selection:", ambiguous.len());
+    for asset in &ambiguous {
+        status!("  {}", asset.name);
+    }
+    Err(EgitError::Other("Please narrow the selection down with --asset".to_string()))
+}
+
+fn record_download(filename: &str, total_size: u64, elapsed: f64) {
+    output::record(|r| {
+        r.success = true;
+        r.filename = Some(filename.to_string());
+        r.total_bytes = Some(total_size);
+        r.elapsed_secs = Some(elapsed);
+    });
+}
+
+/// Download a release asset into `filename`, or copy it from the cache when
+/// an earlier download left it there. The file is checked against the
+/// `expected` SHA-256 before it replaces `filename`. Returns whether it came
+/// from the cache, and the digests of the file.
+fn fetch_asset(client: &Client, storage: &dyn Storage, asset: &GitHubAsset, filename: &str, expected: Option<&str>, options: &DownloadOptions) -> Result<(bool, Digests), EgitError> {
+    progress::file_started(asset.size);
+    if !options.no_cache && let Some(entry) = cache::lookup(&asset.browser_download_url) {
+        // An asset replaced upstream keeps its URL, but not its size or digest
+        let stale = (asset.size > 0 && entry.size != asset.size)
+            || expected.is_some_and(|expected| !expected.eq_ignore_ascii_case(&entry.sha256));
+        if !stale {
+            if let Some(digests) = restore_cached(storage, &entry, filename, expected, options)? {
+                progress::inc(entry.size);
+                progress::file_done();
+                return Ok((true, digests));
+            }
+            status!("- The cached copy of `{}` is damaged, downloading it again", asset.name);
+        } else {
+            log::debug!("The cached copy of {} is out of date", entry.key);
+        }
+    }
+    let digests = download_file(client, storage, asset.download_url(options.authenticated), filename, asset.size, expected, options)?;
+    progress::file_done();
+    Ok((false, digests))
+}
+
+/// Copy a cached asset into `filename`, through a `.part` file like a
+/// download, hashing it on the way. Blobs are named after their SHA-256, so
+/// one that hashes differently is damaged: it is deleted, nothing is written
+/// and `None` returned.
+fn restore_cached(storage: &dyn Storage, entry: &cache::Entry, filename: &str, expected: Option<&str>, options: &DownloadOptions) -> Result<Option<Digests>, EgitError> {
+    let part_name = format!("{}.part", filename);
+    let file = storage.create_writer(&part_name, entry.size).context("Failed to create file")?;
+    let mut writer = HashWriter::new(file, Hasher::new(&options.hashes));
+    std::fs::File::open(&entry.path)
+        .and_then(|mut cached| io::copy(&mut cached, &mut writer))
+        .and_then(|_| writer.flush())
+        .context(format!("Failed to copy `{}` from the cache", entry.key))?;
+    let digests = writer.digests();
+    drop(writer);
+    if digests.sha256 != entry.sha256 {
+        let _ = storage.remove(&part_name);
+        let _ = std::fs::remove_file(&entry.path);
+        return Ok(None);
+    }
+    finish_part(storage, &part_name, filename, entry.size, expected, &digests.sha256)?;
+    Ok(Some(digests))
+}
+
+/// Keep a verified download for next time. The download itself succeeded, so
+/// failing to cache it is only reported.
+fn cache_asset(storage: &dyn Storage, asset: &GitHubAsset, filename: &str, options: &DownloadOptions) {
+    if options.no_cache {
+        return;
+    }
+    let stored = storage.open_reader(filename)
+        .and_then(|mut reader| cache::store(&asset.browser_download_url, &mut reader));
+    if let Err(e) = stored {
+        status!("- Failed to cache `{}`: {}", asset.name, e);
+    }
+}
+
+/// Download `url` into `filename`, in parallel when requested and supported
+/// by the server. The data goes to a temporary file that is only renamed to
+/// `filename` once complete, so an interrupted download never leaves a
+/// half-written file under the real name, nor one that doesn't match the
+/// `expected` SHA-256. The digests are computed as the data arrives, and
+/// returned.
+pub fn download_file(client: &Client, storage: &dyn Storage, url: &str, filename: &str, total_size: u64, expected: Option<&str>, options: &DownloadOptions) -> Result<Digests, EgitError> {
+    ensure_online(url)?;
+    // Parallel downloads start from scratch, so finish an earlier partial
+    // download in a single stream instead of throwing it away
+    let resumable = !options.no_resume && storage.exists(&format!("{}.part", filename));
+    if options.multithread && resumable {
+        status!("- Found a partial download, resuming it single-threaded");
+    } else if options.multithread {
+        status!("+ Using {} threads for parallel download...", options.threads);
+        
+        // Chunks land out of order, so unlike `.part` files this one can't
+        // be resumed and gets a name the resume check ignores
+        let temp_name = format!("{}.tmp", filename);
+        match multitread::download_parallel(client, storage, url, &temp_name, total_size, options) {
+            Ok(digests) => {
+                // Chunks that arrived too far ahead of the ones before them
+                // couldn't be hashed in order
+                let digests = match digests {
+                    Some(digests) => digests,
+                    None => hash_digests(storage, &temp_name, &options.hashes)?,
+                };
+                finish_part(storage, &temp_name, filename, total_size, expected, &digests.sha256)?;
+                return Ok(digests);
+            },
+            Err(e) => {
+                if storage.exists(&temp_name) && let Err(e) = storage.remove(&temp_name) {
+                    status!("- Failed to remove `{}`: {}", temp_name, e);
+                }
+                // No range support, or advertised support that doesn't hold
+                // up in practice
+                if e.kind() != io::ErrorKind::Unsupported {
+                    return Err(EgitError::from(e).context("Parallel download failed"));
+                }
+                status!("- {}, falling back to single-threaded download", e);
+            },
+        }
+    }
+    
+    download_single(client, storage, url, filename, total_size, expected, options)
+}
+
+/// Stream `url` into `<filename>.part`, resuming from an existing partial file
+/// when possible, and rename it to `filename` once complete and matching the
+/// `expected` SHA-256
+fn download_single(client: &Client, storage: &dyn Storage, url: &str, filename: &str, total_size: u64, expected: Option<&str>, options: &DownloadOptions) -> Result<Digests, EgitError> {
+    let part_name = format!("{}.part", filename);
+    
+    let mut resume_from = if !options.no_resume && storage.exists(&part_name) {
+        storage.size(&part_name).unwrap_or(0)
+    } else {
+        0
+    };
+    // A partial file larger than the asset can't be resumed
+    if total_size > 0 && resume_from > total_size {
+        resume_from = 0;
+    }
+    
+    let request = || {
+        let request = client.get(url)
+            .header("User-Agent", "egit-cli")
+            .header(ACCEPT, "application/octet-stream");
+        if resume_from > 0 {
+            request.header("Range", format!("bytes={}-", resume_from))
+        } else {
+            request
+        }
+    };
+    
+    let response = match retry::send_with_retry(client, options.retries, request).and_then(|resp| resp.error_for_status()) {
+        Ok(resp) => resp,
+        // Asking for bytes past the end means the partial file is already complete
+        Err(e) if resume_from > 0 && e.status() == Some(StatusCode::RANGE_NOT_SATISFIABLE) => {
+            let digests = hash_digests(storage, &part_name, &options.hashes)?;
+            finish_part(storage, &part_name, filename, total_size, expected, &digests.sha256)?;
+            return Ok(digests);
+        },
+        Err(e) => return Err(EgitError::from(e).context("Download failed")),
+    };
+    
+    let mut hasher = Hasher::new(&options.hashes);
+    let file = if resume_from > 0 && response.status() == StatusCode::PARTIAL_CONTENT {
+        status!("+ Resuming `{}` from {:.1}KB...", filename, resume_from as f64 / 1024.0);
+        // Only the part downloaded earlier is read back
+        storage.open_reader(&part_name)
+            .and_then(|part| io::copy(&mut part.take(resume_from), &mut hasher))
+            .context(format!("Failed to read `{}`", part_name))?;
+        storage.append_writer(&part_name)
+    } else {
+        if resume_from > 0 {
+            status!("- Server does not support resuming, restarting download");
+            resume_from = 0;
+        }
+        storage.create_writer(&part_name, total_size)
+    };
+    let mut file = HashWriter::new(file.context("Failed to create file")?, hasher);
+    
+    let pb = new_progress_bar(filename, total_size, options);
+    pb.set_position(resume_from);
+    progress::inc(resume_from);
+    
+    // Use custom ProgressReader to stream the response with progress updates
+    let mut reader = ProgressReader {
+        reader: response,
+        progress_bar: pb.clone(),
+        bytes_read: resume_from,
+        sparkline: options.sparkline.then(|| sparkline::Sparkline::new(resume_from)),
+    };
+    
+    // Copy the response to the file using the ProgressReader
+    if let Err(e) = io::copy(&mut reader, &mut file).and_then(|_| file.flush()) {
+        status!("- Partial download kept in `{}`, run the command again to resume", part_name);
+        return Err(EgitError::from(e).context("Download failed"));
+    }
+    let digests = file.digests();
+    drop(file);
+    
+    if total_size > 0 && reader.bytes_read != total_size {
+        status!("- Partial download kept in `{}`, run the command again to resume", part_name);
+        return Err(EgitError::Network(format!("Download incomplete: got {} of {} bytes", reader.bytes_read, total_size)));
+    }
+    
+    progress::finish(&pb, "Download completed");
+    finish_part(storage, &part_name, filename, total_size, expected, &digests.sha256)?;
+    Ok(digests)
+}
+
+/// Stream `url` to stdout for piping. The SHA-256 is checked on the fly;
+/// on mismatch the bytes are already written, so egit exits with an error to
+/// fail the pipeline.
+fn stream_to_stdout(client: &Client, url: &str, total_size: u64, expected: Option<&str>, options: &DownloadOptions) -> Result<Digests, EgitError> {
+    ensure_online(url)?;
+    let request = || client.get(url)
+        .header("User-Agent", "egit-cli")
+        .header(ACCEPT, "application/octet-stream");
+    let response = retry::send_with_retry(client, options.retries, request)
+        .and_then(|resp| resp.error_for_status())
+        .context("Download failed")?;
+    let total_size = if total_size > 0 { total_size } else { response.content_length().unwrap_or(0) };
+    
+    let start_time = std::time::Instant::now();
+    let pb = new_progress_bar("-", total_size, options);
+    let mut reader = ProgressReader {
+        reader: response,
+        progress_bar: pb.clone(),
+        bytes_read: 0,
+        sparkline: options.sparkline.then(|| sparkline::Sparkline::new(0)),
+    };
+    let mut writer = HashWriter::new(io::stdout().lock(), Hasher::new(&options.hashes));
+    io::copy(&mut reader, &mut writer).and_then(|_| writer.flush()).context("Download failed")?;
+    progress::clear(&pb);
+    
+    if total_size > 0 && reader.bytes_read != total_size {
+        return Err(EgitError::Network(format!("Download incomplete: got {} of {} bytes", reader.bytes_read, total_size)));
+    }
+    let elapsed = start_time.elapsed().as_secs_f64();
+    status!("+ Streamed {:.1}KB to stdout | spend {:.1}s.", reader.bytes_read as f64 / 1024.0, elapsed);
+    record_download("-", reader.bytes_read, elapsed);
+    
+    let digests = writer.digests();
+    if let Some(expected) = expected {
+        if !digests.sha256.eq_ignore_ascii_case(expected) {
+            status!("-   expected: {}", expected.to_lowercase());
+            status!("-   actual:   {}", digests.sha256);
+            return Err(EgitError::Verification("SHA-256 mismatch for the streamed download".to_string()));
+        }
+        status!("+ Verified SHA-256 of the streamed download: {}", digests.sha256);
+    }
+    report_digests("the streamed download", &digests, expected.is_some());
+    Ok(digests)
+}
+
+/// Move a completed download into place once it has the size the server
+/// announced and its SHA-256 (`sha256`) matches the `expected` one, so
+/// neither a truncated nor a tampered response replaces the file already at
+/// `filename`. The size isn't checked when unknown (0).
+fn finish_part(storage: &dyn Storage, part_name: &str, filename: &str, total_size: u64, expected: Option<&str>, sha256: &str) -> Result<(), EgitError> {
+    let size = storage.size(part_name).context(format!("Failed to read `{}`", part_name))?;
+    if total_size > 0 && size != total_size {
+        if let Err(e) = storage.remove(part_name) {
+            status!("- Failed to remove `{}`: {}", part_name, e);
+        }
+        return Err(EgitError::Verification(format!(
+            "Download of `{}` is {} bytes but {} were expected, the file has been deleted", filename, size, total_size)));
+    }
+    if let Some(expected) = expected {
+        check_sha256(storage, part_name, filename, expected, sha256)?;
+    }
+    storage.rename(part_name, filename).context(format!("Failed to move `{}` to `{}`", part_name, filename))
+}
+
+/// Look for the asset's digest in a `<asset>.sha256` file published
+/// alongside it, or else in a combined list such as `SHA256SUMS`
+fn fetch_release_sha256(client: &Client, release: &GitHubRelease, asset_name: &str, options: &DownloadOptions) -> Result<Option<String>, EgitError> {
+    let sibling_name = format!("{}.sha256", asset_name);
+    if let Some(sibling) = release.assets.iter().find(|a| a.name == sibling_name) {
+        status!("+ Found `{}`, verifying download against it...", sibling.name);
+        let digest = checksum::parse_sha256_file(&fetch_asset_text(client, sibling, options)?);
+        if digest.is_none() {
+            status!("- `{}` does not contain a SHA-256 digest, skipping verification", sibling.name);
+        }
+        return Ok(digest);
+    }
+    
+    let Some(sums) = release.assets.iter().find(|a| a.name != asset_name && checksum::is_sums_file(&a.name)) else {
+        return Ok(None);
+    };
+    let digest = checksum::find_in_sums(&fetch_asset_text(client, sums, options)?, asset_name);
+    match &digest {
+        Some(_) => status!("+ Found `{}` in `{}`, verifying download against it...", asset_name, sums.name),
+        None => status!("- `{}` is not listed in `{}`, skipping verification", asset_name, sums.name),
+    }
+    Ok(digest)
+}
+
+/// Download a small text asset such as a checksum file into memory
+fn fetch_asset_text(client: &Client, asset: &GitHubAsset, options: &DownloadOptions) -> Result<String, EgitError> {
+    Ok(String::from_utf8_lossy(&fetch_asset_bytes(client, asset, options)?).into_owned())
+}
+
+/// Download a small asset such as a checksum or signature file into memory.
+/// It is cached too, so that `--offline` runs can still verify downloads.
+fn fetch_asset_bytes(client: &Client, asset: &GitHubAsset, options: &DownloadOptions) -> Result<Vec<u8>, EgitError> {
+    if client::is_offline() && let Some(entry) = cache::lookup(&asset.browser_download_url) {
+        return std::fs::read(&entry.path).context(format!("Failed to read `{}` from the cache", entry.key));
+    }
+    ensure_online(&asset.browser_download_url)?;
+    let contents = client.send(client.get(asset.download_url(options.authenticated))
+        .header("User-Agent", "egit-cli")
+        .header(ACCEPT, "application/octet-stream"))
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.bytes())
+        .context(format!("Failed to fetch `{}`", asset.name))?;
+    if !options.no_cache && let Err(e) = cache::store(&asset.browser_download_url, &mut &contents[..]) {
+        status!("- Failed to cache `{}`: {}", asset.name, e);
+    }
+    Ok(contents.to_vec())
+}
+
+/// Fail before sending a request with `--offline`, naming what isn't cached
+fn ensure_online(url: &str) -> Result<(), EgitError> {
+    if client::is_offline() {
+        return Err(EgitError::Network(format!(
+            "`{}` is not cached and --offline prevents downloading it. Run the command once without --offline to cache it", url)));
+    }
+    Ok(())
+}
+
+/// Check a finished download against the detached signature published next
+/// to it, made with the --pubkey key or, keyless, by the --verify-sigstore
+/// identity. The file is deleted when the signature is missing or invalid.
+pub fn verify_signature(client: &Client, storage: &dyn Storage, release: &GitHubRelease, asset: &GitHubAsset, filename: &str, options: &DownloadOptions) -> Result<(), EgitError> {
+    let signer = match (&options.pubkey, &options.sigstore) {
+        (Some(key_path), _) => {
+            let key = std::fs::read_to_string(key_path).context(format!("Failed to read `{}`", key_path.display()))?;
+            let scheme = verify::Scheme::detect(&key).ok_or_else(|| {
+                EgitError::Other(format!("`{}` is not a minisign, PGP or cosign public key", key_path.display()))
+            })?;
+            verify::Signer::Key(scheme, key_path)
+        },
+        (None, Some(identity)) => verify::Signer::Keyless(identity),
+        (None, None) => return Ok(()),
+    };
+    
+    let names = signer.signature_names(&asset.name);
+    let Some(signature) = release.assets.iter().find(|a| names.contains(&a.name)) else {
+        status!("- No {} signature found for `{}` (looked for {})", signer, asset.name, names.join(", "));
+        if let Err(e) = storage.remove(filename) {
+            status!("- Failed to remove `{}`: {}", filename, e);
+        }
+        return Err(EgitError::Verification(format!("Refusing to accept an unsigned download, `{}` has been deleted", filename)));
+    };
+    
+    status!("+ Verifying `{}` against `{}`...", filename, signature.name);
+    let bytes = fetch_asset_bytes(client, signature, options)?;
+    let verified = storage.open_reader(filename)
+        .map_err(|e| format!("failed to read `{}`: {}", filename, e))
+        .and_then(|mut file| verify::verify(signer, &mut file, &signature.name, &bytes));
+    if let Err(e) = verified {
+        if let Err(e) = storage.remove(filename) {
+            status!("- Failed to remove `{}`: {}", filename, e);
+        }
+        return Err(EgitError::Verification(format!("Signature check failed for `{}`, the file has been deleted: {}", filename, e)));
+    }
+    status!("+ Verified {} signature of `{}`", signer, filename);
+    Ok(())
+}
+
+/// Compare the SHA-256 computed while downloading `filename` into
+/// `part_name` with `expected`, deleting only the partial file on mismatch
+fn check_sha256(storage: &dyn Storage, part_name: &str, filename: &str, expected: &str, actual: &str) -> Result<(), EgitError> {
+    progress::phase("verify", filename);
+    if !actual.eq_ignore_ascii_case(expected) {
+        if let Err(e) = storage.remove(part_name) {
+            status!("- Failed to remove `{}`: {}", part_name, e);
+        }
+        status!("-   expected: {}", expected.to_lowercase());
+        status!("-   actual:   {}", actual);
+        return Err(EgitError::Verification(format!("SHA-256 mismatch for `{}`, the download has been deleted", filename)));
+    }
+    
+    status!("+ Verified SHA-256 of `{}`: {}", filename, actual);
+    Ok(())
+}
+
+/// Print the digests of a download, the SHA-256 only when no verification
+/// already did, and add them to the JSON report
+fn report_digests(filename: &str, digests: &Digests, verified: bool) {
+    if !verified {
+        status!("+ SHA-256 of `{}`: {}", filename, digests.sha256);
+    }
+    if let Some(sha512) = &digests.sha512 {
+        status!("+ SHA-512 of `{}`: {}", filename, sha512);
+    }
+    if let Some(blake3) = &digests.blake3 {
+        status!("+ BLAKE3 of `{}`: {}", filename, blake3);
+    }
+    output::record(|r| r.digests = Some(digests.clone()));
+}
+
+/// Decide what happens to a file that is already there: --force and --yes
+/// overwrite it, --skip-existing keeps it, otherwise the user is asked, and
+/// without a terminal to ask on the download fails. Returns whether to go
+/// ahead with the download.
+fn confirm_overwrite(storage: &dyn Storage, filename: &str, options: &DownloadOptions) -> Result<bool, EgitError> {
+    if !storage.exists(filename) || options.force {
+        return Ok(true);
+    }
+    if options.skip_existing {
+        status!("+ `{}` already exists, skipping", filename);
+        return Ok(false);
+    }
+    if !options.assume_yes && !confirm::can_prompt() {
+        return Err(EgitError::Other(format!(
+            "`{}` already exists, pass --force to overwrite it or --skip-existing to keep it", filename)));
+    }
+    if !confirm::confirm(&format!("`{}` already exists. Overwrite?", filename), options.assume_yes) {
+        return Err(EgitError::Other(format!("Download cancelled, `{}` was left untouched", filename)));
+    }
+    Ok(true)
+}
+
+fn write_version_file(storage: &dyn Storage, path: &Path, tag: &str) -> Result<(), EgitError> {
+    storage.create_writer(&path.to_string_lossy(), tag.len() as u64 + 1)
+        .and_then(|mut file| writeln!(file, "{}", tag))
+        .context("Failed to write version file")?;
+    status!("+ Recorded version `{}` in `{}`", tag, path.display());
+    Ok(())
+}
+
+/// Compare the asset --asset and the platform select with a local file,
+/// returning whether they are identical
+pub fn compare_local_file(client: &Client, storage: &dyn Storage, release: &GitHubRelease, package: &str, path: &Path, options: &DownloadOptions) -> Result<bool, EgitError> {
+    let Some(asset) = select_asset(release, options)? else {
+        return Err(EgitError::NotFound("No assets found for this release".to_string()));
+    };
+    
+    status!("+ Comparing `{}` with `{}@{} -> {}`...", 
+             path.display(), package, release.tag_name, asset.name);
+    
+    let name = path.to_string_lossy();
+    let local_size = match storage.size(&name) {
+        Ok(size) => size,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            status!("+ `{}` does not exist, differs from `{}`", path.display(), asset.name);
+            output::record(|r| { r.success = true; r.identical = Some(false); });
+            return Ok(false);
+        },
+        Err(e) => return Err(EgitError::from(e).context("Failed to read local file")),
+    };
+    
+    if local_size != asset.size {
+        status!("+ `{}` differs from `{}` (local {:.1}KB, remote {:.1}KB)", 
+                 path.display(), asset.name, local_size as f64 / 1024.0, asset.size as f64 / 1024.0);
+        output::record(|r| { r.success = true; r.identical = Some(false); });
+        return Ok(false);
+    }
+    
+    // Sizes match, so hash both sides to tell them apart
+    let local_hash = hash_file(storage, &name)?;
+    
+    // The digest from the API saves downloading the asset
+    let remote_hash = match asset.sha256() {
+        Some(sha256) => sha256.to_lowercase(),
+        None => hash_remote_asset(client, asset, options)?,
+    };
+    
+    if local_hash == remote_hash {
+        status!("+ `{}` is identical to `{}` (sha256: {})", path.display(), asset.name, local_hash);
+        output::record(|r| { r.success = true; r.identical = Some(true); });
+        return Ok(true);
+    }
+    
+    status!("+ `{}` differs from `{}` (local sha256: {}, remote sha256: {})", 
+             path.display(), asset.name, local_hash, remote_hash);
+    output::record(|r| { r.success = true; r.identical = Some(false); });
+    Ok(false)
+}
+
+/// Download an asset without storing it, returning its SHA-256
+fn hash_remote_asset(client: &Client, asset: &GitHubAsset, options: &DownloadOptions) -> Result<String, EgitError> {
+    if client::is_offline() && let Some(entry) = cache::lookup(&asset.browser_download_url) {
+        return Ok(entry.sha256);
+    }
+    ensure_online(&asset.browser_download_url)?;
+    let response = client.send(client.get(asset.download_url(options.authenticated))
+        .header("User-Agent", "egit-cli")
+        .header(ACCEPT, "application/octet-stream"))
+        .and_then(|resp| resp.error_for_status())
+        .context("Download failed")?;
+    
+    let pb = new_progress_bar(&asset.name, asset.size, options);
+    let reader = ProgressReader {
+        reader: response,
+        progress_bar: pb.clone(),
+        bytes_read: 0,
+        sparkline: None,
+    };
+    let remote_hash = checksum::sha256_reader(reader).context("Download failed")?;
+    progress::clear(&pb);
+    Ok(remote_hash)
+}
+
+fn new_progress_bar(name: &str, total_size: u64, options: &DownloadOptions) -> ProgressBar {
+    if progress::mode() == progress::Mode::Json {
+        return progress::track(name, ProgressBar::with_draw_target(Some(total_size), ProgressDrawTarget::hidden()));
+    }
+    if progress::is_hidden() {
+        return ProgressBar::hidden();
+    }
+    let mut template = "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})".to_string();
+    if options.sparkline {
+        template.push_str(" {msg:.cyan}");
+    }
+    
+    let pb = ProgressBar::new(total_size);
+    pb.set_style(ProgressStyle::with_template(&template)
+        .unwrap()
+        .progress_chars("█▉▊▋▌▍▎▏ "));
+    progress::add(pb)
+}
+
+/// Download `asset` and copy the executables it contains into `bin_dir`,
+/// named after `name` when the asset is a bare binary. Returns the installed
+/// files and the digests of the asset.
+pub fn install_release_asset(client: &Client, storage: &dyn Storage, release: &GitHubRelease, asset: &GitHubAsset, name: &str, bin_dir: &Path, options: &DownloadOptions) -> Result<(Vec<PathBuf>, Digests), EgitError> {
+    preflight(asset, options)?;
+    summarize(asset, options);
+    // Download next to the binaries under a name of its own, as `sync`
+    // installs several at once; only the executables are kept
+    let filename = bin_dir.join(format!(".{}.{}.download", sanitize_filename(&asset.name), std::process::id())).to_string_lossy().into_owned();
+    let installed = expected_sha256(client, release, asset, options)
+        .and_then(|expected| {
+            let (cached, digests) = fetch_asset(client, storage, asset, &filename, expected.as_deref(), options)?;
+            report_digests(&asset.name, &digests, expected.is_some());
+            if !cached {
+                cache_asset(storage, asset, &filename, options);
+            }
+            let files = install::install_asset(storage, &filename, &asset.name, name, bin_dir)
+                .context("Installation failed")?;
+            Ok((files, digests))
+        });
+    // A `.part` left by a failed download can't be resumed under this name
+    for leftover in [format!("{}.part", filename), filename] {
+        if storage.exists(&leftover) && let Err(e) = storage.remove(&leftover) {
+            status!("- Failed to remove `{}`: {}", leftover, e);
+        }
+    }
+    let (mut files, digests) = installed?;
+    for file in &files {
+        status!("+ Installed `{}`", file.display());
+    }
+    // Listed with the binaries, so uninstalling removes them too
+    let version_files: Vec<PathBuf> = match &options.version_file {
+        Some(path) => vec![path.clone()],
+        None if options.write_version_file => files.iter().filter_map(|file| options.version_file_for(&file.to_string_lossy())).collect(),
+        None => Vec::new(),
+    };
+    for path in version_files {
+        write_version_file(storage, &path, &release.tag_name)?;
+        files.push(path);
+    }
+    Ok((files, digests))
+}
+
+/// The SHA-256 of a stored file
+pub fn hash_file(storage: &dyn Storage, filename: &str) -> Result<String, EgitError> {
+    hash_digests(storage, filename, &[]).map(|digests| digests.sha256)
+}
+
+/// The SHA-256 of a stored file, along with the `extra` digests
+pub fn hash_digests(storage: &dyn Storage, filename: &str, extra: &[checksum::Algorithm]) -> Result<Digests, EgitError> {
+    storage.open_reader(filename)
+        .and_then(|reader| checksum::hash_reader(reader, extra))
+        .context(format!("Failed to hash `{}`", filename))
+}
+
+/// Download the file or directory at `path` through the contents API. Whole
+/// directories are listed with one recursive trees API call instead of one
+/// contents request per subdirectory.
+pub fn get_path(client: &Client, storage: &dyn Storage, owner: &str, repo: &str, path: &str, git_ref: Option<&str>, options: &DownloadOptions) -> Result<(), EgitError> {
+    let contents = assets::fetch_contents(client, owner, repo, path, git_ref)
+        .context(format!("Failed to fetch `{}`", path))?
+        .ok_or_else(|| EgitError::NotFound(format!("`{}` not found in `{}/{}`", path, owner, repo)))?;
+    
+    let start_time = std::time::Instant::now();
+    let file = match contents {
+        Contents::File(file) => file,
+        Contents::Dir(entries) if entries.is_empty() => {
+            status!("- `{}` is empty", path);
+            return Ok(());
+        },
+        Contents::Dir(_) => {
+            return get_directory(client, storage, owner, repo, path, git_ref, options);
+        },
+    };
+    let Some(url) = &file.download_url else {
+        return Err(EgitError::Other(format!("`{}` is a {} and cannot be downloaded", path, file.kind)));
+    };
+    
+    let filename = options.destination(&file.name);
+    status!("+ Downloading `{} -> {}`...", file.path, filename);
+    if !confirm_overwrite(storage, &filename, options)? {
+        record_download(&filename, file.size, 0.0);
+        return Ok(());
+    }
+    download_single(client, storage, url, &filename, file.size, None, options)?;
+    
+    let elapsed = start_time.elapsed().as_secs_f64();
+    status!("+ Download completed {:.1}KB | spend {:.1}s.", file.size as f64 / 1024.0, elapsed);
+    record_download(&filename, file.size, elapsed);
+    Ok(())
+}
+
+fn get_directory(client: &Client, storage: &dyn Storage, owner: &str, repo: &str, path: &str, git_ref: Option<&str>, options: &DownloadOptions) -> Result<(), EgitError> {
+    // The trees API needs an explicit ref
+    let git_ref = match git_ref {
+        Some(git_ref) => git_ref.to_string(),
+        None => assets::fetch_repo(client, owner, repo).context("Failed to fetch repository info")?.default_branch,
+    };
+    let tree = assets::fetch_tree(client, owner, repo, &git_ref).context(format!("Failed to list `{}`", path))?;
+    if tree.truncated {
+        status!("- The repository is too large to list in full, some files may be missing");
+    }
+    
+    let prefix = if path.is_empty() { String::new() } else { format!("{}/", path) };
+    let files: Vec<_> = tree.tree.iter()
+        .filter(|entry| entry.kind == "blob" && entry.path.starts_with(&prefix))
+        .collect();
+    
+    // Named after the directory, or the repository when fetching its root
+    let root = options.destination(path.rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or(repo));
+    let start_time = std::time::Instant::now();
+    let mut total_size = 0;
+    for entry in &files {
+        let filename = Path::new(&root).join(&entry.path[prefix.len()..]).to_string_lossy().into_owned();
+        let size = entry.size.unwrap_or(0);
+        status!("+ Downloading `{} -> {}`...", entry.path, filename);
+        if !confirm_overwrite(storage, &filename, options)? {
+            continue;
+        }
+        download_single(client, storage, &assets::raw_url(owner, repo, &git_ref, &entry.path), &filename, size, None, options)?;
+        total_size += size;
+    }
+    
+    let elapsed = start_time.elapsed().as_secs_f64();
+    status!("+ Downloaded {} files ({:.1}KB) into `{}` | spend {:.1}s.", files.len(), total_size as f64 / 1024.0, root, elapsed);
+    record_download(&root, total_size, elapsed);
+    Ok(())
+}
+
+fn sanitize_filename(name: &str) -> String {
+    name.replace(['@', '/', ':', '*', '?', '"', '<', '>', '|'], "-")
+}
+
+/// Download the source archive of `git_ref` (a release tag, branch or commit)
+pub fn download_source(client: &Client, storage: &dyn Storage, git_ref: &str, tarball_url: &str, zipball_url: &str, package: &str, options: &DownloadOptions) -> Result<(), EgitError> {
+    use std::env::consts::OS;
+    
+    let (source_url, extension) = match OS {
+        "windows" => (zipball_url, "zip"),
+        _ => (tarball_url, "tar.gz"),
+    };
+    
+    if options.dry_run {
+        let filename = if options.stdout { "stdout".to_string() } else { options.destination(&format!("{}-source.{}", sanitize_filename(package), extension)) };
+        // The size of a source archive is only known once it is generated
+        output!("+ Would download the source of `{}@{}` from {} -> {}", package, git_ref, source_url, filename);
+        record_plan(source_url, None, &filename);
+        output::task_end();
+        return Ok(());
+    }
+    
+    if options.stdout {
+        status!("+ Downloading `{}@{} -> stdout`...", package, git_ref);
+        stream_to_stdout(client, source_url, 0, options.sha256.as_deref(), options)?;
+        output::task_end();
+        return Ok(());
+    }
+    
+    let sanitized_package = sanitize_filename(package);
+    let filename = options.destination(&format!("{}-source.{}", sanitized_package, extension));
+    let dest = options.extract_to.clone()
+        .unwrap_or_else(|| Path::new(&filename).with_file_name(&sanitized_package));
+    
+    // A tarball can be unpacked as it arrives; a zip needs the whole file,
+    // and parallel chunks arrive out of order
+    let streaming = options.extract && extension != "zip" && !options.multithread;
+    if streaming && !options.keep_archive {
+        status!("+ Downloading and extracting `{}@{} -> {}`...", package, git_ref, dest.display());
+    } else {
+        status!("+ Downloading `{}@{} -> {}`...", 
+                 package, git_ref, filename);
+    }
+    
+    if (!streaming || options.keep_archive) && !confirm_overwrite(storage, &filename, options)? {
+        record_download(&filename, storage.size(&filename).unwrap_or(0), 0.0);
+        output::task_end();
+        return Ok(());
+    }
+    
+    let start_time = std::time::Instant::now();
+    
+    if streaming {
+        let (digests, total_size) = stream_extract(client, storage, source_url, &filename, &dest, options)?;
+        let elapsed = start_time.elapsed().as_secs_f64();
+        status!("+ Downloaded and extracted `{}@{}` , total size: {:.1}KB | spend {:.1}s.", 
+                 package, git_ref, total_size as f64 / 1024.0, elapsed);
+        record_download(&filename, total_size, elapsed);
+        report_digests(&filename, &digests, options.sha256.is_some());
+        if let Some(path) = options.version_file_for(&filename) {
+            write_version_file(storage, &path, git_ref)?;
+        }
+        warn_about_submodules(storage, &dest);
+        output::task_end();
+        return Ok(());
+    }
+    
+    // Get total size for progress tracking
+    ensure_online(source_url)?;
+    let request = || client.head(source_url)
+        .header("User-Agent", "egit-cli")
+        .header(ACCEPT, "application/octet-stream");
+    let total_size = retry::send_with_retry(client, options.retries, request)
+        .context("Failed to get file size")?
+        .content_length()
+        .unwrap_or(0);
+    
+    let digests = download_file(client, storage, source_url, &filename, total_size, options.sha256.as_deref(), options)?;
+    
+    // Calculate accurate download time
+    let elapsed = start_time.elapsed().as_secs_f64();
+    
+    status!("+ Downloaded `{}@{}` , total size: {:.1}KB | spend {:.1}s.", 
+             package, git_ref, total_size as f64 / 1024.0, elapsed);
+    record_download(&filename, total_size, elapsed);
+    report_digests(&filename, &digests, options.sha256.is_some());
+    
+    if let Some(path) = options.version_file_for(&filename) {
+        write_version_file(storage, &path, git_ref)?;
+    }
+    
+    if options.extract {
+        extract_download(storage, &filename, &dest, options)?;
+        warn_about_submodules(storage, &dest);
+    }
+    
+    output::task_end();
+    Ok(())
+}
+
+/// Source archives leave out git submodules, whose directories are then
+/// empty. Warn when the extracted source at `dest` declares any, as builds
+/// that need them fail in confusing ways.
+fn warn_about_submodules(storage: &dyn Storage, dest: &Path) {
+    let gitmodules = dest.join(".gitmodules").to_string_lossy().into_owned();
+    let mut contents = String::new();
+    if !storage.exists(&gitmodules)
+        || storage.open_reader(&gitmodules).and_then(|mut file| file.read_to_string(&mut contents)).is_err() {
+        return;
+    }
+    let paths = submodule_paths(&contents);
+    log::warn!("- WARNING: `{}` uses git submodules, which source archives don't include. Their directories are empty:", dest.display());
+    for path in &paths {
+        log::warn!("-   {}", path);
+    }
+    log::warn!("- Clone the repository with `git clone --recurse-submodules` to get them");
+}
+
+/// The `path` of every submodule declared in a `.gitmodules` file
+fn submodule_paths(contents: &str) -> Vec<String> {
+    contents.lines()
+        .filter_map(|line| line.split_once('='))
+        .filter(|(key, _)| key.trim() == "path")
+        .map(|(_, path)| path.trim().to_string())
+        .collect()
+}
+
+/// Download the tarball at `url` and unpack it into `dest` as it streams in,
+/// without writing the archive to disk; with --keep-archive a copy is saved
+/// to `filename` on the way. Files are unpacked into a scratch directory and
+/// only moved into `dest` once the download is complete and its digest
+/// checked. Returns the digests and size of the archive.
+fn stream_extract(client: &Client, storage: &dyn Storage, url: &str, filename: &str, dest: &Path, options: &DownloadOptions) -> Result<(Digests, u64), EgitError> {
+    ensure_online(url)?;
+    let request = || client.get(url)
+        .header("User-Agent", "egit-cli")
+        .header(ACCEPT, "application/octet-stream");
+    let response = retry::send_with_retry(client, options.retries, request)
+        .and_then(|resp| resp.error_for_status())
+        .context("Download failed")?;
+    let total_size = response.content_length().unwrap_or(0);
+    
+    let part_name = format!("{}.part", filename);
+    let copy: Box<dyn Write> = if options.keep_archive {
+        storage.create_writer(&part_name, total_size).context("Failed to create file")?
+    } else {
+        Box::new(io::sink())
+    };
+    let pb = new_progress_bar(filename, total_size, options);
+    let mut reader = TeeReader {
+        reader: ProgressReader {
+            reader: response,
+            progress_bar: pb.clone(),
+            bytes_read: 0,
+            sparkline: options.sparkline.then(|| sparkline::Sparkline::new(0)),
+        },
+        writer: HashWriter::new(copy, Hasher::new(&options.hashes)),
+    };
+    
+    let scratch = storage.scratch_dir(&dest.to_string_lossy()).context("Failed to create a scratch directory")?;
+    let streamed = extract::unpack(&mut reader, filename, &scratch)
+        // Read what the unpacker left, such as the compression trailer, so
+        // the digest covers the whole archive
+        .and_then(|()| io::copy(&mut reader, &mut io::sink()))
+        .and_then(|_| reader.writer.flush())
+        .context("Extraction failed")
+        .and_then(|()| {
+            let bytes_read = reader.reader.bytes_read;
+            if total_size > 0 && bytes_read != total_size {
+                return Err(EgitError::Network(format!("Download incomplete: got {} of {} bytes", bytes_read, total_size)));
+            }
+            progress::finish(&pb, "Download completed");
+            let digests = reader.writer.digests();
+            if options.keep_archive {
+                finish_part(storage, &part_name, filename, bytes_read, options.sha256.as_deref(), &digests.sha256)?;
+            } else if let Some(expected) = &options.sha256 {
+                if !digests.sha256.eq_ignore_ascii_case(expected) {
+                    status!("-   expected: {}", expected.to_lowercase());
+                    status!("-   actual:   {}", digests.sha256);
+                    return Err(EgitError::Verification(format!("SHA-256 mismatch for `{}`, nothing was extracted", filename)));
+                }
+                status!("+ Verified SHA-256 of `{}`: {}", filename, digests.sha256);
+            }
+            progress::phase("extract", filename);
+            place_entries(storage, &scratch, dest, options)?;
+            Ok((digests, bytes_read))
+        });
+    let _ = std::fs::remove_dir_all(&scratch);
+    drop(reader);
+    if streamed.is_err() && storage.exists(&part_name) && let Err(e) = storage.remove(&part_name) {
+        status!("- Failed to remove `{}`: {}", part_name, e);
+    }
+    let streamed = streamed?;
+    status!("+ Extracted to `{}`", dest.display());
+    Ok(streamed)
+}
+
+/// Unpack a downloaded archive into `dest`, removing the archive afterwards
+/// unless --keep-archive was given. The archive is unpacked into a scratch
+/// directory and its files moved into `dest`, which may be an existing
+/// directory: nothing in it is deleted, and files it already has are only
+/// replaced with --force or once confirmed.
+fn extract_download(storage: &dyn Storage, filename: &str, dest: &Path, options: &DownloadOptions) -> Result<(), EgitError> {
+    progress::phase("extract", filename);
+    status!("+ Extracting `{}` -> `{}`...", filename, dest.display());
+    let scratch = storage.scratch_dir(&dest.to_string_lossy()).context("Failed to create a scratch directory")?;
+    let extracted = storage.open_reader(filename)
+        .and_then(|reader| extract::unpack(reader, filename, &scratch))
+        .context("Extraction failed")
+        .and_then(|()| place_entries(storage, &scratch, dest, options));
+    let _ = std::fs::remove_dir_all(&scratch);
+    extracted?;
+    
+    if !options.keep_archive
+        && let Err(e) = storage.remove(filename) {
+        status!("- Failed to remove `{}`: {}", filename, e);
+    }
+    status!("+ Extracted to `{}`", dest.display());
+    Ok(())
+}
+
+/// Move what was unpacked into `scratch` into `dest`, after checking which
+/// files it would replace
+fn place_entries(storage: &dyn Storage, scratch: &Path, dest: &Path, options: &DownloadOptions) -> Result<(), EgitError> {
+    let entries = extract::entries(scratch, options.strip_components).context("Extraction failed")?;
+    let target = |entry: &extract::Entry| dest.join(&entry.relative).to_string_lossy().into_owned();
+    let existing: Vec<String> = entries.iter()
+        .filter(|entry| !entry.is_dir)
+        .map(target)
+        .filter(|name| storage.exists(name))
+        .collect();
+    confirm_replace(&existing, dest, options)?;
+    
+    let dest_name = dest.to_string_lossy();
+    storage.create_dir(&dest_name).context(format!("Failed to create `{}`", dest_name))?;
+    for entry in &entries {
+        let name = target(entry);
+        let placed = if entry.is_dir { storage.create_dir(&name) } else { storage.import(&entry.path, &name) };
+        placed.context(format!("Failed to write `{}`", name))?;
+    }
+    Ok(())
+}
+
+/// Check that extracting may replace the `existing` files: always with
+/// --force, otherwise once confirmed on a terminal
+fn confirm_replace(existing: &[String], dest: &Path, options: &DownloadOptions) -> Result<(), EgitError> {
+    if existing.is_empty() || options.force {
+        return Ok(());
+    }
+    let mut listed = existing.iter().take(5).map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ");
+    if existing.len() > 5 {
+        listed.push_str(&format!(" and {} more", existing.len() - 5));
+    }
+    status!("- Extracting into `{}` would replace {} existing files: {}", dest.display(), existing.len(), listed);
+    if !options.assume_yes && !confirm::can_prompt() {
+        return Err(EgitError::Other(format!(
+            "`{}` already has files from the archive, pass --force to overwrite them or extract somewhere else with --extract-to", dest.display())));
+    }
+    if !confirm::confirm("Replace them?", options.assume_yes) {
+        return Err(EgitError::Other(format!("Extraction cancelled, `{}` was left untouched", dest.display())));
+    }
+    Ok(())
+}
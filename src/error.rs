@@ -0,0 +1,138 @@
+//! The error type of the library, and the exit code each kind maps to
+
+use reqwest::StatusCode;
+use std::error;
+use std::fmt;
+use std::io;
+use crate::assets::ApiError;
+
+/// Why a command failed. Each kind exits with its own code so that scripts can
+/// tell "no such release" from "network down".
+#[derive(Debug)]
+pub enum EgitError {
+    /// Invalid arguments or settings, cancelled prompts and anything else
+    Other(String),
+    /// The server could not be reached or returned an error
+    Network(String),
+    /// The package, release, asset or file does not exist
+    NotFound(String),
+    /// The token is missing, invalid or lacks access
+    Auth(String),
+    /// A checksum, signature or size check failed
+    Verification(String),
+    /// Reading or writing local files failed
+    Io(String),
+}
+
+impl EgitError {
+    /// The process exit code for this kind of error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            EgitError::Other(_) => 1,
+            EgitError::Network(_) => 2,
+            EgitError::NotFound(_) => 3,
+            EgitError::Auth(_) => 4,
+            EgitError::Verification(_) => 5,
+            EgitError::Io(_) => 6,
+        }
+    }
+
+    /// Prefix the message with what was being done, keeping the kind
+    pub fn context(self, context: impl fmt::Display) -> Self {
+        let wrap = |message: String| format!("{}: {}", context, message);
+        match self {
+            EgitError::Other(message) => EgitError::Other(wrap(message)),
+            EgitError::Network(message) => EgitError::Network(wrap(message)),
+            EgitError::NotFound(message) => EgitError::NotFound(wrap(message)),
+            EgitError::Auth(message) => EgitError::Auth(wrap(message)),
+            EgitError::Verification(message) => EgitError::Verification(wrap(message)),
+            EgitError::Io(message) => EgitError::Io(wrap(message)),
+        }
+    }
+}
+
+impl fmt::Display for EgitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EgitError::Other(message)
+            | EgitError::Network(message)
+            | EgitError::NotFound(message)
+            | EgitError::Auth(message)
+            | EgitError::Verification(message)
+            | EgitError::Io(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<reqwest::Error> for EgitError {
+    fn from(e: reqwest::Error) -> Self {
+        // Requests may go to GitHub or another provider
+        let host = e.url().and_then(|url| url.host_str()).unwrap_or("GitHub");
+        if e.is_timeout() {
+            EgitError::Network("Connection timed out. Please check your network connection or try again later.".to_string())
+        } else if is_certificate_error(&e) {
+            EgitError::Network(format!("The TLS certificate of {} is not trusted. If a proxy intercepts TLS, pass its CA certificate with --cacert.", host))
+        } else if e.is_connect() {
+            EgitError::Network(format!("Failed to connect to {}. Please check your network connection.", host))
+        } else if let Some(status) = e.status() {
+            let message = format!("{} returned an error: {}", host, status);
+            match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => EgitError::Auth(message),
+                StatusCode::NOT_FOUND | StatusCode::GONE => EgitError::NotFound(message),
+                _ => EgitError::Network(message),
+            }
+        } else {
+            EgitError::Network(format!("An error occurred: {}", e))
+        }
+    }
+}
+
+/// Whether the request failed because the server's certificate was rejected
+fn is_certificate_error(e: &reqwest::Error) -> bool {
+    let mut source = error::Error::source(e);
+    while let Some(inner) = source {
+        if inner.to_string().to_lowercase().contains("certificate") {
+            return true;
+        }
+        source = inner.source();
+    }
+    false
+}
+
+impl From<ApiError> for EgitError {
+    fn from(e: ApiError) -> Self {
+        match e {
+            ApiError::Http(e) => e.into(),
+            // The message explains how to get a higher limit
+            rate_limited @ ApiError::RateLimited { .. } => EgitError::Auth(rate_limited.to_string()),
+            offline @ ApiError::Offline { .. } => EgitError::Network(offline.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for EgitError {
+    fn from(e: io::Error) -> Self {
+        // Parallel downloads wrap HTTP errors in I/O errors, and report
+        // dropped connections with the network kinds
+        match e.downcast::<reqwest::Error>() {
+            Ok(e) => e.into(),
+            Err(e) if matches!(e.kind(),
+                io::ErrorKind::TimedOut | io::ErrorKind::ConnectionAborted | io::ErrorKind::UnexpectedEof) => {
+                EgitError::Network(e.to_string())
+            },
+            Err(e) => EgitError::Io(e.to_string()),
+        }
+    }
+}
+
+/// Attach context to any error that converts into an `EgitError`
+pub trait Context<T> {
+    /// Prefix the error, if any, with `context`
+    fn context(self, context: impl fmt::Display) -> Result<T, EgitError>;
+}
+
+impl<T, E: Into<EgitError>> Context<T> for Result<T, E> {
+    fn context(self, context: impl fmt::Display) -> Result<T, EgitError> {
+        self.map_err(|e| e.into().context(context))
+    }
+}
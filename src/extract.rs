@@ -0,0 +1,128 @@
+//! Unpacking zip and tar archives (gzip, xz, bzip2 and zstd compressed)
+
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use xz2::read::XzDecoder;
+use zip::ZipArchive;
+
+/// Archive formats that can be unpacked, with the extensions they go by
+const FORMATS: &[(&str, Format)] = &[
+    (".zip", Format::Zip),
+    (".tar", Format::Tar),
+    (".tar.gz", Format::TarGz),
+    (".tgz", Format::TarGz),
+    (".tar.xz", Format::TarXz),
+    (".txz", Format::TarXz),
+    (".tar.bz2", Format::TarBz2),
+    (".tbz2", Format::TarBz2),
+    (".tar.zst", Format::TarZst),
+    (".tzst", Format::TarZst),
+];
+
+#[derive(Clone, Copy)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarBz2,
+    TarZst,
+}
+
+fn format_of(name: &str) -> Option<(usize, Format)> {
+    let lower = name.to_lowercase();
+    FORMATS
+        .iter()
+        .find(|(ext, _)| lower.ends_with(ext))
+        .map(|(ext, format)| (name.len() - ext.len(), *format))
+}
+
+/// Whether `name` has an extension egit knows how to unpack
+pub fn is_archive(name: &str) -> bool {
+    format_of(name).is_some()
+}
+
+/// The archive name without its extension (`tool-1.0.tar.gz` -> `tool-1.0`)
+pub fn archive_stem(name: &str) -> &str {
+    format_of(name).map_or(name, |(stem, _)| &name[..stem])
+}
+
+/// Unpack the archive called `name`, read from `reader`, into `dir`. Tar
+/// archives are unpacked as they are read; a zip keeps its index at the end,
+/// so it is spooled to a file next to `dir` first.
+pub fn unpack<'a>(mut reader: impl Read + 'a, name: &str, dir: &Path) -> io::Result<()> {
+    let Some((_, format)) = format_of(name) else {
+        return Err(io::Error::other(format!("unsupported archive format: {}", name)));
+    };
+
+    let reader: Box<dyn Read + 'a> = match format {
+        Format::Zip => {
+            let mut spool_name = dir.file_name().unwrap_or_default().to_os_string();
+            spool_name.push(".zip");
+            let spool = dir.with_file_name(spool_name);
+            let result = File::create(&spool)
+                .and_then(|mut file| io::copy(&mut reader, &mut file))
+                .and_then(|_| File::open(&spool))
+                .and_then(|file| ZipArchive::new(file)
+                    .and_then(|mut zip| zip.extract(dir))
+                    .map_err(io::Error::other));
+            let _ = fs::remove_file(&spool);
+            return result;
+        },
+        Format::Tar => Box::new(reader),
+        Format::TarGz => Box::new(GzDecoder::new(reader)),
+        Format::TarXz => Box::new(XzDecoder::new(reader)),
+        Format::TarBz2 => Box::new(BzDecoder::new(reader)),
+        Format::TarZst => Box::new(zstd::Decoder::new(reader)?),
+    };
+    tar::Archive::new(reader).unpack(dir)
+}
+
+/// A file or directory unpacked into a scratch directory, and where it goes
+/// relative to the destination
+pub struct Entry {
+    /// Where it was unpacked
+    pub path: PathBuf,
+    /// Where it goes, relative to the destination
+    pub relative: PathBuf,
+    /// Whether it is a directory
+    pub is_dir: bool,
+}
+
+/// What was unpacked into `dir`, directories before their contents. With
+/// `strip_components` that many leading path components are removed from
+/// every entry, as `tar --strip-components` does; otherwise a single
+/// top-level folder in the archive (as in GitHub source archives) is.
+pub fn entries(dir: &Path, strip_components: Option<usize>) -> io::Result<Vec<Entry>> {
+    let mut all = Vec::new();
+    collect(dir, Path::new(""), &mut all)?;
+    let strip = strip_components.unwrap_or_else(|| {
+        let top: Vec<_> = all.iter().filter(|entry| entry.relative.components().count() == 1).collect();
+        usize::from(matches!(top.as_slice(), [entry] if entry.is_dir))
+    });
+    // Entries above the stripped depth are dropped, as tar does
+    Ok(all.into_iter()
+        .filter(|entry| entry.relative.components().count() > strip)
+        .map(|entry| Entry { relative: entry.relative.components().skip(strip).collect(), ..entry })
+        .collect())
+}
+
+fn collect(dir: &Path, relative: &Path, entries: &mut Vec<Entry>) -> io::Result<()> {
+    let mut children: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+    children.sort_by_key(|child| child.file_name());
+    for child in children {
+        let is_dir = child.file_type()?.is_dir();
+        let entry = Entry { path: child.path(), relative: relative.join(child.file_name()), is_dir };
+        if is_dir {
+            let (path, relative) = (entry.path.clone(), entry.relative.clone());
+            entries.push(entry);
+            collect(&path, &relative, entries)?;
+        } else {
+            entries.push(entry);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! Releases from Gitea and Forgejo instances
+
+use reqwest::Url;
+use serde::Deserialize;
+use crate::assets::{self, ApiError, GitHubAsset, GitHubRelease, GitHubRepo, GitHubTag, GitHubUser};
+use crate::client::Client;
+use crate::provider::ReleaseProvider;
+
+/// Releases of a Gitea or Forgejo instance such as Codeberg, through the v1
+/// API. It mirrors the GitHub API closely, only assets lack an API URL.
+pub struct Gitea {
+    api: Url,
+    client: Client,
+    prefix: &'static str,
+}
+
+#[derive(Deserialize, Clone)]
+struct GiteaRelease {
+    tag_name: String,
+    name: Option<String>,
+    body: Option<String>,
+    created_at: Option<String>,
+    published_at: Option<String>,
+    html_url: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    author: Option<GitHubUser>,
+    #[serde(default)]
+    assets: Vec<GiteaAsset>,
+    zipball_url: String,
+    tarball_url: String,
+}
+
+#[derive(Deserialize, Clone)]
+struct GiteaAsset {
+    name: String,
+    size: u64,
+    #[serde(default)]
+    download_count: u64,
+    browser_download_url: String,
+}
+
+impl From<GiteaRelease> for GitHubRelease {
+    fn from(release: GiteaRelease) -> Self {
+        GitHubRelease {
+            tag_name: release.tag_name,
+            name: release.name,
+            published_at: release.published_at,
+            created_at: release.created_at,
+            html_url: release.html_url,
+            author: release.author,
+            draft: release.draft,
+            prerelease: release.prerelease,
+            body: release.body,
+            assets: release.assets.into_iter().map(|asset| GitHubAsset {
+                name: asset.name,
+                url: asset.browser_download_url.clone(),
+                browser_download_url: asset.browser_download_url,
+                size: asset.size,
+                content_type: None,
+                download_count: asset.download_count,
+                digest: None,
+            }).collect(),
+            zipball_url: release.zipball_url,
+            tarball_url: release.tarball_url,
+        }
+    }
+}
+
+impl Gitea {
+    /// A provider for the instance at `host`, e.g. `https://codeberg.org`.
+    /// `prefix` is the one the package was given with: `gitea:`, `forgejo:` or
+    /// `codeberg:`.
+    pub fn new(host: &str, client: Client, prefix: &'static str) -> Result<Self, String> {
+        // The trailing slash keeps instances served under a path when joining
+        let api = Url::parse(&format!("{}/", host.trim_end_matches('/')))
+            .and_then(|host| host.join("api/v1/repos"))
+            .map_err(|e| format!("invalid Gitea host `{}`: {}", host, e))?;
+        Ok(Gitea { api, client, prefix })
+    }
+
+    /// API URL of the repository followed by `path`
+    fn url(&self, owner: &str, repo: &str, path: &[&str]) -> Url {
+        let mut url = self.api.clone();
+        url.path_segments_mut()
+            .expect("API URL has a path")
+            .extend([owner, repo])
+            .extend(path);
+        url
+    }
+}
+
+impl ReleaseProvider for Gitea {
+    fn client(&self) -> &Client {
+        &self.client
+    }
+
+    fn prefix(&self) -> &'static str {
+        self.prefix
+    }
+
+    fn fetch_repo(&self, owner: &str, repo: &str) -> Result<GitHubRepo, ApiError> {
+        Ok(assets::get(&self.client, self.url(owner, repo, &[]).as_str())?.json()?)
+    }
+
+    fn fetch_latest_release(&self, owner: &str, repo: &str) -> Result<Option<GitHubRelease>, ApiError> {
+        let url = self.url(owner, repo, &["releases", "latest"]);
+        Ok(assets::fetch_optional::<GiteaRelease>(&self.client, url.as_str())?.map(GitHubRelease::from))
+    }
+
+    fn fetch_release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Option<GitHubRelease>, ApiError> {
+        let url = self.url(owner, repo, &["releases", "tags", tag]);
+        Ok(assets::fetch_optional::<GiteaRelease>(&self.client, url.as_str())?.map(GitHubRelease::from))
+    }
+
+    fn fetch_releases_until(&self, owner: &str, repo: &str, stop: &mut dyn FnMut(&[GitHubRelease]) -> bool) -> Result<Vec<GitHubRelease>, ApiError> {
+        let url = self.url(owner, repo, &["releases"]);
+        let releases: Vec<GiteaRelease> = assets::fetch_pages(&self.client, url.as_str(), |page: &[GiteaRelease]| {
+            stop(&page.iter().cloned().map(GitHubRelease::from).collect::<Vec<_>>())
+        })?;
+        Ok(releases.into_iter().map(GitHubRelease::from).collect())
+    }
+
+    fn fetch_tags(&self, owner: &str, repo: &str, limit: Option<usize>) -> Result<Vec<GitHubTag>, ApiError> {
+        let url = self.url(owner, repo, &["tags"]);
+        let mut tags: Vec<GitHubTag> = assets::fetch_pages(&self.client, url.as_str(), assets::stop_after(limit))?;
+        tags.truncate(limit.unwrap_or(usize::MAX));
+        Ok(tags)
+    }
+
+    fn archive_url(&self, owner: &str, repo: &str, kind: &str, git_ref: &str) -> String {
+        let extension = if kind == "zipball" { "zip" } else { "tar.gz" };
+        let archive = format!("{}.{}", git_ref, extension);
+        self.url(owner, repo, &["archive", &archive]).into()
+    }
+}
@@ -0,0 +1,177 @@
+//! Releases from GitLab, on gitlab.com or a self-hosted instance
+
+use reqwest::Url;
+use serde::Deserialize;
+use crate::assets::{self, ApiError, GitHubAsset, GitHubRelease, GitHubRepo, GitHubTag, GitHubUser};
+use crate::client::Client;
+use crate::provider::ReleaseProvider;
+
+/// Releases of gitlab.com or a self-hosted GitLab instance, through the v4 API
+pub struct GitLab {
+    api: Url,
+    client: Client,
+}
+
+#[derive(Deserialize)]
+struct GitLabProject {
+    path_with_namespace: String,
+    // Missing for projects without a repository
+    default_branch: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+struct GitLabRelease {
+    tag_name: String,
+    name: Option<String>,
+    description: Option<String>,
+    created_at: Option<String>,
+    released_at: Option<String>,
+    // Set for releases scheduled in the future
+    #[serde(default)]
+    upcoming_release: bool,
+    author: Option<GitLabUser>,
+    #[serde(rename = "_links")]
+    links: Option<GitLabReleaseLinks>,
+    assets: GitLabAssets,
+}
+
+#[derive(Deserialize, Clone)]
+struct GitLabUser {
+    username: String,
+}
+
+#[derive(Deserialize, Clone)]
+struct GitLabReleaseLinks {
+    #[serde(rename = "self")]
+    html: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+struct GitLabAssets {
+    #[serde(default)]
+    sources: Vec<GitLabSource>,
+    #[serde(default)]
+    links: Vec<GitLabLink>,
+}
+
+#[derive(Deserialize, Clone)]
+struct GitLabSource {
+    format: String,
+    url: String,
+}
+
+/// A release asset; GitLab only stores a link to the file, not its size
+#[derive(Deserialize, Clone)]
+struct GitLabLink {
+    name: String,
+    url: String,
+    direct_asset_url: Option<String>,
+}
+
+impl From<GitLabRelease> for GitHubRelease {
+    fn from(release: GitLabRelease) -> Self {
+        let source = |format: &str| release.assets.sources.iter()
+            .find(|s| s.format == format)
+            .map(|s| s.url.clone())
+            .unwrap_or_default();
+        GitHubRelease {
+            zipball_url: source("zip"),
+            tarball_url: source("tar.gz"),
+            tag_name: release.tag_name,
+            name: release.name,
+            published_at: release.released_at,
+            created_at: release.created_at,
+            html_url: release.links.and_then(|links| links.html),
+            author: release.author.map(|author| GitHubUser { login: author.username }),
+            draft: false,
+            // GitLab has no prereleases; upcoming releases are the closest match
+            prerelease: release.upcoming_release,
+            body: release.description,
+            assets: release.assets.links.into_iter().map(|link| {
+                let url = link.direct_asset_url.unwrap_or(link.url);
+                GitHubAsset {
+                    name: link.name,
+                    url: url.clone(),
+                    browser_download_url: url,
+                    size: 0,
+                    content_type: None,
+                    download_count: 0,
+                    digest: None,
+                }
+            }).collect(),
+        }
+    }
+}
+
+impl GitLab {
+    /// A provider for the instance at `host`, e.g. `https://gitlab.com`
+    pub fn new(host: &str, client: Client) -> Result<Self, String> {
+        // The trailing slash keeps instances served under a path, e.g.
+        // `https://example.com/gitlab`, when joining
+        let api = Url::parse(&format!("{}/", host.trim_end_matches('/')))
+            .and_then(|host| host.join("api/v4/projects"))
+            .map_err(|e| format!("invalid GitLab host `{}`: {}", host, e))?;
+        Ok(GitLab { api, client })
+    }
+
+    /// API URL of the project followed by `path`. The project is addressed by
+    /// its URL-encoded full path, which may include nested groups.
+    fn url(&self, owner: &str, repo: &str, path: &[&str]) -> Url {
+        let mut url = self.api.clone();
+        url.path_segments_mut()
+            .expect("API URL has a path")
+            .push(&format!("{}/{}", owner, repo))
+            .extend(path);
+        url
+    }
+}
+
+impl ReleaseProvider for GitLab {
+    fn client(&self) -> &Client {
+        &self.client
+    }
+
+    fn prefix(&self) -> &'static str {
+        "gitlab:"
+    }
+
+    fn fetch_repo(&self, owner: &str, repo: &str) -> Result<GitHubRepo, ApiError> {
+        let project: GitLabProject = assets::get(&self.client, self.url(owner, repo, &[]).as_str())?.json()?;
+        Ok(GitHubRepo {
+            full_name: project.path_with_namespace,
+            default_branch: project.default_branch.unwrap_or_default(),
+        })
+    }
+
+    fn fetch_latest_release(&self, owner: &str, repo: &str) -> Result<Option<GitHubRelease>, ApiError> {
+        let url = self.url(owner, repo, &["releases", "permalink", "latest"]);
+        Ok(assets::fetch_optional::<GitLabRelease>(&self.client, url.as_str())?.map(GitHubRelease::from))
+    }
+
+    fn fetch_release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Option<GitHubRelease>, ApiError> {
+        let url = self.url(owner, repo, &["releases", tag]);
+        Ok(assets::fetch_optional::<GitLabRelease>(&self.client, url.as_str())?.map(GitHubRelease::from))
+    }
+
+    fn fetch_releases_until(&self, owner: &str, repo: &str, stop: &mut dyn FnMut(&[GitHubRelease]) -> bool) -> Result<Vec<GitHubRelease>, ApiError> {
+        let url = self.url(owner, repo, &["releases"]);
+        let releases: Vec<GitLabRelease> = assets::fetch_pages(&self.client, url.as_str(), |page: &[GitLabRelease]| {
+            stop(&page.iter().cloned().map(GitHubRelease::from).collect::<Vec<_>>())
+        })?;
+        Ok(releases.into_iter().map(GitHubRelease::from).collect())
+    }
+
+    fn fetch_tags(&self, owner: &str, repo: &str, limit: Option<usize>) -> Result<Vec<GitHubTag>, ApiError> {
+        let url = self.url(owner, repo, &["repository", "tags"]);
+        let mut tags: Vec<GitHubTag> = assets::fetch_pages(&self.client, url.as_str(), assets::stop_after(limit))?;
+        tags.truncate(limit.unwrap_or(usize::MAX));
+        Ok(tags)
+    }
+
+    fn archive_url(&self, owner: &str, repo: &str, kind: &str, git_ref: &str) -> String {
+        let format = if kind == "zipball" { "archive.zip" } else { "archive.tar.gz" };
+        let mut url = self.url(owner, repo, &["repository", format]);
+        url.query_pairs_mut().append_pair("sha", git_ref);
+        url.into()
+    }
+}
@@ -0,0 +1,113 @@
+//! Printing releases, changelogs and tables for humans
+
+use crate::assets::GitHubRelease;
+use crate::markdown;
+use crate::output;
+use indicatif::HumanBytes;
+
+/// Print everything known about a release: metadata, notes and assets
+pub fn display_release(release: &GitHubRelease) {
+    if output::is_json() {
+        output::print_json(release);
+        return;
+    }
+
+    output!("=== Release '{}' ===", release.tag_name);
+    output!("Name:       {}", release.name.as_deref().unwrap_or("Unnamed release"));
+    output!("Tag:        {}", release.tag_name);
+    output!("Published:  {}", release.published_at.as_deref().unwrap_or("Unknown date"));
+    output!("Author:     {}", release.author.as_ref().map_or("Unknown", |a| a.login.as_str()));
+    output!("Flags:      {}", flags(release));
+    if let Some(url) = &release.html_url {
+        output!("URL:        {}", url);
+    }
+
+    output!("");
+    output!("=== Release Notes ===");
+    match release.body.as_deref().map(str::trim) {
+        Some(body) if !body.is_empty() => output!("{}", body),
+        _ => output!("- No release notes"),
+    }
+
+    output!("");
+    output!("=== Assets ({}) ===", release.assets.len());
+    if release.assets.is_empty() {
+        output!("- No assets found for this release");
+        return;
+    }
+    let rows: Vec<[String; 4]> = release
+        .assets
+        .iter()
+        .map(|a| [
+            a.name.clone(),
+            HumanBytes(a.size).to_string(),
+            a.content_type.clone().unwrap_or_else(|| "-".to_string()),
+            a.download_count.to_string(),
+        ])
+        .collect();
+    print_table(["NAME", "SIZE", "TYPE", "DOWNLOADS"], &rows);
+}
+
+/// Print the notes of several releases, newest first
+pub fn display_changelog(releases: &[GitHubRelease]) {
+    if output::is_json() {
+        output::print_json(releases);
+        return;
+    }
+
+    let styled = markdown::use_styles();
+    for (i, release) in releases.iter().enumerate() {
+        if i > 0 {
+            output!("");
+        }
+        let date = release.published_at.as_deref().and_then(|date| date.get(..10)).unwrap_or("unknown date");
+        match release.name.as_deref().filter(|name| !name.is_empty() && *name != release.tag_name) {
+            Some(name) => output!("=== {}: {} ({}) ===", release.tag_name, name, date),
+            None => output!("=== {} ({}) ===", release.tag_name, date),
+        }
+        match release.body.as_deref().map(str::trim) {
+            Some(body) if !body.is_empty() && styled => output!("{}", markdown::render(body)),
+            Some(body) if !body.is_empty() => output!("{}", body),
+            _ => output!("- No release notes"),
+        }
+    }
+}
+
+/// `draft`/`prerelease` markers, or `-` for a regular release
+fn flags(release: &GitHubRelease) -> String {
+    let mut flags = Vec::new();
+    if release.draft {
+        flags.push("draft");
+    }
+    if release.prerelease {
+        flags.push("prerelease");
+    }
+    if flags.is_empty() {
+        "-".to_string()
+    } else {
+        flags.join(", ")
+    }
+}
+
+/// Print rows under a header with every column padded to its widest cell
+pub fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: [&str; N]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        output!("{}", padded.join("  ").trim_end());
+    };
+    line(header);
+    for row in rows {
+        line(row.each_ref().map(String::as_str));
+    }
+}
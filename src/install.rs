@@ -0,0 +1,111 @@
+//! Installing binaries from a downloaded asset into a bin directory
+
+use crate::extract;
+use crate::storage::Storage;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Where binaries go unless `--bin-dir` says otherwise: `~/.local/bin`, or
+/// `%USERPROFILE%\.egit\bin` on Windows
+pub fn default_bin_dir() -> io::Result<PathBuf> {
+    let home = env::home_dir().ok_or_else(|| io::Error::other("cannot find the home directory"))?;
+    if cfg!(windows) {
+        Ok(home.join(".egit").join("bin"))
+    } else {
+        Ok(home.join(".local").join("bin"))
+    }
+}
+
+/// Copy the executables in a downloaded asset into `bin_dir` and return the
+/// installed paths. Archives are unpacked into a scratch directory first; a
+/// bare binary is installed under the package name (`tool-linux-amd64` ->
+/// `tool`).
+pub fn install_asset(storage: &dyn Storage, download: &str, asset_name: &str, name: &str, bin_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let scratch = storage.scratch_dir(download)?;
+    let installed = install_from(storage, &scratch, download, asset_name, name, bin_dir);
+    let _ = fs::remove_dir_all(&scratch);
+    installed
+}
+
+fn install_from(storage: &dyn Storage, scratch: &Path, download: &str, asset_name: &str, name: &str, bin_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let executables = if extract::is_archive(asset_name) {
+        extract::unpack(storage.open_reader(download)?, asset_name, scratch)?;
+        find_executables(scratch, name)?
+    } else {
+        let file_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+        let local = scratch.join(&file_name);
+        io::copy(&mut storage.open_reader(download)?, &mut File::create(&local)?)?;
+        vec![(local, file_name)]
+    };
+    if executables.is_empty() {
+        return Err(io::Error::other(format!("no executables found in `{}`", asset_name)));
+    }
+
+    let mut installed = Vec::new();
+    for (source, file_name) in executables {
+        let dest = bin_dir.join(file_name);
+        // Copy next to the destination and rename over it, so an interrupted
+        // install never leaves a truncated binary. A running binary being
+        // replaced keeps working, as its file is only unlinked.
+        let temp = dest.with_file_name(format!(".{}.tmp", dest.file_name().unwrap_or_default().to_string_lossy())).to_string_lossy().into_owned();
+        let mut writer = storage.create_writer(&temp, fs::metadata(&source)?.len())?;
+        io::copy(&mut File::open(&source)?, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        storage.set_executable(&temp)?;
+        storage.rename(&temp, &dest.to_string_lossy())?;
+        installed.push(dest);
+    }
+    Ok(installed)
+}
+
+/// Executables anywhere in `dir`, with the file name to install them under.
+/// Archives that lost their permission bits fall back to a file named after
+/// the package.
+fn find_executables(dir: &Path, name: &str) -> io::Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+
+    let file_name = |path: &PathBuf| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let executables: Vec<_> = files
+        .iter()
+        .filter(|path| is_executable(path))
+        .map(|path| (path.clone(), file_name(path)))
+        .collect();
+    if !executables.is_empty() {
+        return Ok(executables);
+    }
+
+    let wanted = format!("{}{}", name, env::consts::EXE_SUFFIX);
+    Ok(files
+        .into_iter()
+        .filter(|path| file_name(path) == wanted)
+        .map(|path| (path, wanted.clone()))
+        .collect())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+}
@@ -0,0 +1,45 @@
+//! Bare package names such as `ripgrep` for well-known tools
+
+/// Repositories of well-known tools, so that `egit download ripgrep` works
+/// without the owner. Aliases in the config take precedence and can add more.
+const PACKAGES: &[(&str, &str)] = &[
+    ("age", "FiloSottile/age"),
+    ("bat", "sharkdp/bat"),
+    ("bottom", "ClementTsang/bottom"),
+    ("btm", "ClementTsang/bottom"),
+    ("delta", "dandavison/delta"),
+    ("direnv", "direnv/direnv"),
+    ("dust", "bootandy/dust"),
+    ("eza", "eza-community/eza"),
+    ("fd", "sharkdp/fd"),
+    ("fzf", "junegunn/fzf"),
+    ("gh", "cli/cli"),
+    ("gitui", "gitui-org/gitui"),
+    ("hexyl", "sharkdp/hexyl"),
+    ("hyperfine", "sharkdp/hyperfine"),
+    ("jq", "jqlang/jq"),
+    ("just", "casey/just"),
+    ("k9s", "derailed/k9s"),
+    ("lazygit", "jesseduffield/lazygit"),
+    ("mkcert", "FiloSottile/mkcert"),
+    ("neovim", "neovim/neovim"),
+    ("nvim", "neovim/neovim"),
+    ("rg", "BurntSushi/ripgrep"),
+    ("ripgrep", "BurntSushi/ripgrep"),
+    ("sd", "chmln/sd"),
+    ("shellcheck", "koalaman/shellcheck"),
+    ("shfmt", "mvdan/sh"),
+    ("starship", "starship/starship"),
+    ("tokei", "XAMPPRocky/tokei"),
+    ("xh", "ducaale/xh"),
+    ("yq", "mikefarah/yq"),
+    ("zellij", "zellij-org/zellij"),
+    ("zoxide", "ajeetdsouza/zoxide"),
+];
+
+/// The `owner/repo` of a well-known tool, matched case-insensitively
+pub fn lookup(name: &str) -> Option<&'static str> {
+    PACKAGES.iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, repository)| *repository)
+}
@@ -0,0 +1,80 @@
+//! Find, download and verify release assets from GitHub, GitLab, Gitea and
+//! Forgejo. This is the library behind the `egit` command line tool; the
+//! tool only parses arguments and calls into it.
+//!
+//! The main entry points:
+//!
+//! - [`release`]: parse `owner/repo@version` packages and resolve them to a
+//!   release through a [`provider::Providers`] set
+//! - [`download`]: pick the asset for the current platform, download it
+//!   (resumable, optionally in parallel and cached) and check its SHA-256
+//!   and signature
+//! - [`verify`], [`checksum`] and [`extract`]: the verification and
+//!   unpacking steps on their own
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//! use egit::client::{self, ClientOptions};
+//! use egit::download::{self, DownloadOptions};
+//! use egit::error::EgitError;
+//! use egit::provider::Providers;
+//! use egit::release;
+//! use egit::storage::FsStorage;
+//!
+//! # fn main() -> Result<(), EgitError> {
+//! let client_options = ClientOptions::default();
+//! let client = client::build_client(&client_options).map_err(EgitError::Other)?;
+//! let providers = Providers::new(client, client_options, None, Default::default(), Default::default());
+//! let (provider, owner, repo, version) = release::open_package(&providers, "BurntSushi/ripgrep@^14")?;
+//! let release = release::resolve_release(provider.as_ref(), &owner, &repo, None, version.as_deref(), false)?;
+//! let options = DownloadOptions {
+//!     dir: Some(PathBuf::from("downloads")),
+//!     no_interactive: true,
+//!     ..Default::default()
+//! };
+//! if let Some(asset) = download::select_asset(&release, &options)? {
+//!     download::download_release_asset(provider.client(), &FsStorage, &release, asset, "BurntSushi/ripgrep", &options)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Status lines are printed through the `log` crate and progress bars drawn
+//! on stderr; see [`output`](mod@output) and [`progress`] to configure them.
+
+#![warn(missing_docs)]
+
+#[macro_use]
+pub mod output;
+pub mod assets;
+pub mod auth;
+pub mod cache;
+pub mod checksum;
+pub mod client;
+pub mod config;
+pub mod confirm;
+pub mod download;
+pub mod error;
+pub mod extract;
+pub mod gitea;
+pub mod gitlab;
+pub mod info;
+pub mod install;
+pub mod known;
+pub mod lockfile;
+pub mod manifest;
+pub mod markdown;
+pub mod multitread;
+pub mod picker;
+pub mod progress;
+pub mod provider;
+pub mod registry;
+pub mod release;
+pub mod retry;
+pub mod search;
+pub mod select;
+pub mod sparkline;
+pub mod storage;
+pub mod throttle;
+pub mod verify;
+pub mod version;
@@ -0,0 +1,63 @@
+//! `egit.lock`: the exact release, asset and digest each package of a
+//! manifest resolved to, so that `sync` can reproduce them
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Exact artifacts picked by the last `egit sync`, stored next to the
+/// manifest so that `egit sync --locked` can reproduce them
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Lockfile {
+    /// One entry per package of the manifest
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// What a package of the manifest resolved to
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LockedPackage {
+    /// The package as written in the manifest
+    pub name: String,
+    /// The release tag
+    pub tag: String,
+    /// The asset name
+    pub asset: String,
+    /// Where the asset was downloaded from
+    pub url: String,
+    /// Size in bytes
+    pub size: u64,
+    /// SHA-256 of the asset
+    pub sha256: String,
+    /// Extra digests requested with `egit sync --hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha512: Option<String>,
+    /// BLAKE3 of the asset, if requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+}
+
+/// `egit.lock` for `egit.toml`
+pub fn lock_path(manifest: &Path) -> PathBuf {
+    manifest.with_extension("lock")
+}
+
+impl Lockfile {
+    /// Read a lock file
+    pub fn load(path: &Path) -> io::Result<Lockfile> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(io::Error::other)
+    }
+
+    /// Write the lock file, replacing `path`
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, format!("# Written by `egit sync`, do not edit by hand\n\n{}", contents))
+    }
+
+    /// The locked entry of the package `name`
+    pub fn find(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+}
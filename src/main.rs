@@ -1,411 +1,1466 @@
-use clap::Parser;
-use reqwest::blocking::Client;
-use serde::Deserialize;
-use std::fs::File;
-use std::io::{self, Read};
-use std::process::exit;
-use indicatif::{ProgressBar, ProgressStyle};
-use regex::Regex;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand};
+use serde::Serialize;
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Read, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use egit::{assets, auth, cache, checksum, client, config, confirm, info, install, lockfile, output, progress, registry, search, select, status, throttle, verify, version};
+use egit::error::{Context, EgitError};
+use egit::output::{finish, Format};
+use indicatif::HumanBytes;
+use egit::assets::{GitHubAsset, GitHubRelease};
+use egit::client::{Client, ClientOptions};
+use egit::config::Config;
+use egit::checksum::Digests;
+use egit::download::{DownloadOptions, compare_local_file, download_asset, download_release_asset, download_source, expected_sha256, extract_asset, get_path, hash_digests, install_release_asset, plan_download, preflight, select_asset};
+use egit::lockfile::{LockedPackage, Lockfile};
+use egit::manifest::{Manifest, PackageOptions};
+use egit::provider::{Providers, ReleaseProvider};
+use egit::release::{changelog_releases, did_you_mean, find_package_release, latest_stable_release, open_package, package_key, resolve_release, similar_packages};
+use egit::registry::{InstalledPackage, Registry};
+use egit::storage::FsStorage;
+use egit::version::OrderBy;
 
-mod assets;
-mod multitread;
-
-// Custom reader that updates a progress bar as it reads data
-struct ProgressReader<R> {
-    reader: R,
-    progress_bar: ProgressBar,
-    bytes_read: u64,
-}
-
-impl<R: Read> Read for ProgressReader<R> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let bytes_read = self.reader.read(buf)?;
-        self.bytes_read += bytes_read as u64;
-        self.progress_bar.set_position(self.bytes_read);
-        Ok(bytes_read)
-    }
-}
+mod completions;
+mod manpage;
+mod selfupdate;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     #[command(subcommand)]
     command: Command,
+    #[arg(long, global = true, value_name = "HOST:IP", value_parser = client::parse_resolve, help = "Resolve HOST to IP instead of using DNS (repeatable)")]
+    resolve: Vec<(String, IpAddr)>,
+    #[arg(long, global = true, help = "GitHub token for private repositories and higher rate limits [default: $GITHUB_TOKEN, $GH_TOKEN, the token saved by `egit auth login`, `gh auth token` or ~/.netrc]")]
+    token: Option<String>,
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text, help = "Print human-readable text, or a single JSON result on stdout with status lines on stderr")]
+    format: Format,
+    #[arg(long, global = true, help = "Shorthand for --format json")]
+    json: bool,
+    #[arg(short, long, global = true, conflicts_with = "verbose", help = "Hide progress bars and status lines, only print results, warnings and errors")]
+    quiet: bool,
+    #[arg(long, global = true, value_enum, default_value_t = progress::Mode::PerThread, help = "How to show the progress of downloads: one bar per thread of a --multithread download, a single combined bar, none for CI logs, or JSON lines on stderr")]
+    progress: progress::Mode,
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, help = "Log requests (-v) and response headers (-vv) to stderr")]
+    verbose: u8,
+    #[arg(short = 'y', long = "yes", visible_alias = "assume-yes", global = true, help = "Answer yes to all confirmation prompts")]
+    assume_yes: bool,
+    #[arg(long, global = true, help = "When the GitHub API rate limit is exhausted, wait for it to reset and retry")]
+    wait_for_rate_limit: bool,
+    #[arg(long, global = true, value_name = "URL", value_parser = assets::parse_api_url, help = "GitHub API base URL, e.g. https://github.example.com/api/v3 for GitHub Enterprise Server [default: $EGIT_GITHUB_API or https://api.github.com]")]
+    api_url: Option<String>,
+    #[arg(long, global = true, value_name = "URL", help = "Base URL of a self-hosted instance for `gitlab:` and `gitea:` packages [default: https://gitlab.com or https://codeberg.org]")]
+    host: Option<String>,
+    #[arg(long, global = true, value_name = "URL", value_parser = client::parse_proxy, help = "Send all requests through this HTTP, HTTPS or SOCKS5 proxy [default: $HTTPS_PROXY, $HTTP_PROXY or $ALL_PROXY]")]
+    proxy: Option<String>,
+    #[arg(long, global = true, value_name = "PEM", help = "Also trust the CA certificates in this PEM file, e.g. for a proxy that intercepts TLS")]
+    cacert: Option<PathBuf>,
+    #[arg(long, global = true, help = "Skip TLS certificate verification. Insecure: anyone on the network can tamper with downloads")]
+    insecure: bool,
+    #[arg(long, global = true, value_name = "RATE", value_parser = throttle::parse_rate, help = "Limit the download speed to RATE bytes per second, e.g. 500k or 2M")]
+    limit_rate: Option<u64>,
+    #[arg(long, global = true, help = "Never use the network: resolve releases and assets from the cache, and fail when something isn't cached")]
+    offline: bool,
 }
 
+// Parsed once per run, so the size of the `Download` variant doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Parser, Debug)]
 enum Command {
     #[command(about = "Download a package from GitHub releases")]
+    #[command(group(ArgGroup::new("extracting").args(["extract", "extract_to"]).multiple(true)))]
     Download {
         package: String,
         #[arg(short, long, help = "Download source code instead of binary")]
         source: bool,
+        #[arg(long = "ref", value_name = "REF", requires = "source", conflicts_with_all = ["tags", "releases", "assets"], help = "Download the source of a branch, tag or commit instead of a release")]
+        git_ref: Option<String>,
         #[arg(long, help = "Enable multithreaded parallel downloads")]
         multithread: bool,
-        #[arg(long, default_value_t = 4, help = "Number of threads to use for parallel downloads")]
-        threads: usize,
+        #[arg(long, help = "Number of threads to use for parallel downloads [default: 4]")]
+        threads: Option<usize>,
         #[arg(long, help = "List all tags for the repository")]
         tags: bool,
         #[arg(long, help = "List all releases for the repository")]
         releases: bool,
         #[arg(long, help = "List all assets for the selected release")]
         assets: bool,
+        #[arg(long, value_name = "PATH", help = "Compare the release asset with a local file instead of downloading it")]
+        compare_local: Option<PathBuf>,
+        #[arg(long, requires = "compare_local", help = "With --compare-local, download the asset over PATH when it differs, and skip the download when it is identical")]
+        skip_identical: bool,
+        #[arg(long, help = "Show a sparkline of recent download speed next to the progress bar")]
+        sparkline: bool,
+        #[arg(long, value_enum, help = "Release field used to find the latest release and sort listings [default: API order]")]
+        order_by: Option<OrderBy>,
+        #[arg(long, value_name = "TAG", value_parser = version::parse_version_arg, help = "Only list releases and tags newer than this semver version")]
+        since_version: Option<semver::Version>,
+        #[arg(long, value_name = "N", help = "List at most the N newest releases or tags, fetching no more pages than needed")]
+        limit: Option<usize>,
+        #[arg(long, help = "Allow prereleases when picking the latest release or matching a version requirement")]
+        pre: bool,
+        #[arg(long, help = "Record the downloaded tag in `<file>.version` next to the download")]
+        write_version_file: bool,
+        #[arg(long, value_name = "PATH", help = "Record the downloaded tag in this file (implies --write-version-file)")]
+        version_file: Option<PathBuf>,
+        #[arg(long, value_name = "PATTERN", value_parser = select::parse_asset_pattern, help = "Download the asset whose name matches this glob, `/regex/` or substring")]
+        asset: Option<String>,
+        #[arg(long, help = "Download the asset built for the current OS and architecture")]
+        auto: bool,
+        #[arg(long, help = "Never show the asset selection menu; pick the best platform match or fail")]
+        no_interactive: bool,
+        #[arg(long, conflicts_with_all = ["source", "asset", "auto", "sha256", "compare_local"], help = "Download every asset of the release (into --output when it is given)")]
+        all_assets: bool,
+        #[arg(long, value_name = "N", requires = "all_assets", help = "With --all-assets, ask before downloading more than N assets; without a terminal, refuse unless --yes is given")]
+        max_assets: Option<usize>,
+        #[arg(long, value_name = "SIZE", value_parser = throttle::parse_size, conflicts_with = "source", help = "Refuse to download an asset larger than SIZE bytes, e.g. 50M")]
+        max_size: Option<u64>,
+        #[arg(long, value_name = "TYPE", conflicts_with_all = ["source", "all_assets"], help = "Refuse to download an asset of another content type, e.g. application/gzip or application/*")]
+        content_type: Option<String>,
+        #[arg(long, visible_alias = "checksum", value_name = "HEX", value_parser = checksum::parse_sha256_arg, help = "Verify the download against this SHA-256 digest [default: from a `<asset>.sha256` or `SHA256SUMS` release asset, if any]")]
+        sha256: Option<String>,
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["source", "all_assets"], help = "Require a valid detached signature (minisign, GPG or cosign) for the asset, checked with this public key")]
+        pubkey: Option<PathBuf>,
+        #[arg(long, conflicts_with_all = ["source", "all_assets", "pubkey"], requires_all = ["certificate_identity", "certificate_oidc_issuer"], help = "Require a keyless Sigstore bundle for the asset, checked with cosign against --certificate-identity and --certificate-oidc-issuer")]
+        verify_sigstore: bool,
+        #[arg(long, value_name = "IDENTITY", requires = "verify_sigstore", help = "With --verify-sigstore, the identity the signing certificate must name, e.g. an email or a workflow URL")]
+        certificate_identity: Option<String>,
+        #[arg(long, value_name = "URL", requires = "verify_sigstore", help = "With --verify-sigstore, the OIDC issuer that must have vouched for the identity, e.g. https://token.actions.githubusercontent.com")]
+        certificate_oidc_issuer: Option<String>,
+        #[arg(long, help = "Start over instead of resuming from an existing `.part` file")]
+        no_resume: bool,
+        #[arg(long, conflicts_with = "skip_existing", help = "Overwrite files that already exist without asking")]
+        force: bool,
+        #[arg(long, help = "Leave files that already exist untouched and count them as downloaded")]
+        skip_existing: bool,
+        #[arg(long, help = "Always download the asset, neither reading nor filling the download cache")]
+        no_cache: bool,
+        #[arg(short, long, value_name = "PATH", help = "Save to this file, or into this directory under the original name")]
+        output: Option<PathBuf>,
+        #[arg(short, long, value_name = "DIR", help = "Save into this directory, creating it if missing")]
+        dir: Option<PathBuf>,
+        #[arg(long, conflicts_with_all = ["multithread", "output", "dir", "all_assets", "extracting", "pubkey", "verify_sigstore", "compare_local", "write_version_file", "version_file"], help = "Write the downloaded bytes to stdout for piping; status and progress go to stderr")]
+        stdout: bool,
+        #[arg(long, default_value_t = 3, help = "Number of times to retry a request after a timeout, connection error or 5xx response")]
+        retries: u32,
+        #[arg(long, help = "Extract the downloaded archive (.zip, .tar, .tar.gz, .tar.xz, .tar.bz2 or .tar.zst) next to it")]
+        extract: bool,
+        #[arg(long, value_name = "DIR", conflicts_with = "all_assets", help = "Extract the downloaded archive into DIR (implies --extract)")]
+        extract_to: Option<PathBuf>,
+        #[arg(long, value_name = "N", requires = "extracting", help = "Remove N leading path components from every archive entry [default: strip a lone top-level folder]")]
+        strip_components: Option<usize>,
+        #[arg(long, requires = "extracting", help = "Keep the archive after extracting it")]
+        keep_archive: bool,
+        #[arg(long, conflicts_with_all = ["tags", "releases", "assets", "compare_local"], help = "Print the URL, size and destination of what would be downloaded without writing anything")]
+        dry_run: bool,
+        #[arg(long = "hash", value_name = "ALGORITHM", value_enum, value_delimiter = ',', help = "Also compute these digests while downloading: sha512, blake3 (repeatable or comma-separated)")]
+        hashes: Vec<checksum::Algorithm>,
+    },
+    #[command(about = "List the releases, tags or release assets of a repository")]
+    List {
+        #[arg(required_unless_present = "installed")]
+        package: Option<String>,
+        #[arg(long, conflicts_with_all = ["package", "tags", "releases", "assets", "order_by", "since_version", "limit"], help = "List the packages installed with `egit install`")]
+        installed: bool,
+        #[arg(long, conflicts_with_all = ["releases", "assets"], help = "List all tags for the repository")]
+        tags: bool,
+        #[arg(long, conflicts_with = "assets", help = "List all releases for the repository (default)")]
+        releases: bool,
+        #[arg(long, value_name = "TAG", help = "List all assets of a release (`latest` or a tag)")]
+        assets: Option<String>,
+        #[arg(long, value_enum, help = "Release field used to find the latest release and sort listings [default: API order]")]
+        order_by: Option<OrderBy>,
+        #[arg(long, value_name = "TAG", value_parser = version::parse_version_arg, help = "Only list releases and tags newer than this semver version")]
+        since_version: Option<semver::Version>,
+        #[arg(long, value_name = "N", help = "List at most the N newest releases or tags, fetching no more pages than needed")]
+        limit: Option<usize>,
+        #[arg(long, help = "Allow prereleases when picking the latest release or matching a version requirement")]
+        pre: bool,
+    },
+    #[command(about = "Show the details, release notes and assets of a release")]
+    Info {
+        package: String,
+        #[arg(long, value_enum, help = "Release field used to find the latest release [default: API order]")]
+        order_by: Option<OrderBy>,
+        #[arg(long, help = "Allow prereleases when picking the latest release or matching a version requirement")]
+        pre: bool,
+    },
+    #[command(about = "Print the tag of the latest release, for use in scripts")]
+    Latest {
+        package: String,
+        #[arg(long, value_enum, help = "Release field used to find the latest release [default: API order]")]
+        order_by: Option<OrderBy>,
+        #[arg(long, help = "Allow prereleases when picking the latest release or matching a version requirement")]
+        pre: bool,
+    },
+    #[command(about = "Show the release notes of every release between two versions")]
+    Changelog {
+        package: String,
+        #[arg(long, value_name = "TAG", help = "Show the releases after this one [default: the installed version, or the oldest release]")]
+        from: Option<String>,
+        #[arg(long, value_name = "TAG", value_parser = version::parse_version_arg, conflicts_with = "from", help = "Show only the releases whose tag is a semver version newer than this one")]
+        since_version: Option<semver::Version>,
+        #[arg(long, value_name = "TAG", help = "Show the releases up to and including this one [default: the latest release]")]
+        to: Option<String>,
+        #[arg(long, help = "Include prereleases")]
+        pre: bool,
+    },
+    #[command(about = "Search GitHub repositories by name, description or topic")]
+    Search {
+        #[arg(help = "Search terms, with GitHub search qualifiers such as `language:rust` or `topic:cli`")]
+        query: String,
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=100), help = "Show at most N repositories")]
+        limit: u8,
+        #[arg(long, value_enum, help = "Sort by stars or last update instead of best match")]
+        sort: Option<search::SearchSort>,
+    },
+    #[command(about = "Download a single file or directory from a repository")]
+    Get {
+        package: String,
+        #[arg(value_name = "PATH[@REF]", help = "File or directory in the repository, optionally at a branch, tag or commit [default: default branch]")]
+        path: String,
+        #[arg(short, long, help = "File or directory to save to [default: the name of PATH]")]
+        output: Option<PathBuf>,
+        #[arg(long, conflicts_with = "skip_existing", help = "Overwrite files that already exist without asking")]
+        force: bool,
+        #[arg(long, help = "Leave files that already exist untouched and count them as downloaded")]
+        skip_existing: bool,
     },
+    #[command(about = "Install the executables of a release into a directory on PATH")]
+    Install {
+        package: String,
+        #[arg(long, value_name = "PATTERN", value_parser = select::parse_asset_pattern, help = "Install from the asset whose name matches this glob, `/regex/` or substring [default: best match for this platform]")]
+        asset: Option<String>,
+        #[arg(long, help = "Allow prereleases when picking the latest release or matching a version requirement")]
+        pre: bool,
+        #[arg(long, value_name = "DIR", help = "Directory to install executables into [default: ~/.local/bin, or %USERPROFILE%\\.egit\\bin on Windows]")]
+        bin_dir: Option<PathBuf>,
+        #[arg(long, value_name = "SIZE", value_parser = throttle::parse_size, help = "Refuse to install from an asset larger than SIZE bytes, e.g. 50M")]
+        max_size: Option<u64>,
+        #[arg(long, help = "Print the asset that would be installed and where, without installing it")]
+        dry_run: bool,
+        #[arg(long = "hash", value_name = "ALGORITHM", value_enum, value_delimiter = ',', help = "Also compute these digests while downloading and record them in the install registry: sha512, blake3 (repeatable or comma-separated)")]
+        hashes: Vec<checksum::Algorithm>,
+        #[arg(long, help = "Record the installed tag in a `<binary>.version` file next to each installed binary")]
+        write_version_file: bool,
+        #[arg(long, value_name = "PATH", help = "Record the installed tag in this file instead (implies --write-version-file)")]
+        version_file: Option<PathBuf>,
+    },
+    #[command(about = "Remove the executables installed for a package")]
+    Uninstall {
+        package: String,
+    },
+    #[command(about = "Upgrade installed packages to their latest release")]
+    Upgrade {
+        #[arg(help = "Package to upgrade [default: every installed package]")]
+        package: Option<String>,
+        #[arg(long, help = "Only report which packages have a newer release and the assets that would be installed")]
+        dry_run: bool,
+    },
+    #[command(about = "Compare installed packages with their latest release")]
+    Outdated {
+        #[arg(short, long, default_value_t = 8, help = "Number of repositories to query at once")]
+        jobs: usize,
+    },
+    #[command(about = "Update egit itself to its latest release")]
+    SelfUpdate {
+        #[arg(long, help = "Only report whether a newer release is available")]
+        check: bool,
+        #[arg(long, help = "Allow prereleases")]
+        pre: bool,
+    },
+    #[command(about = "Download or install every package listed in an egit.toml manifest")]
+    Sync {
+        #[arg(long, default_value = "egit.toml", help = "Manifest listing the packages")]
+        manifest: PathBuf,
+        #[arg(short, long, default_value_t = 4, help = "Number of packages to download at once")]
+        jobs: usize,
+        #[arg(long, help = "Download exactly the artifacts in the lockfile and fail if any changed upstream")]
+        locked: bool,
+        #[arg(long, help = "Print what would be downloaded or installed without writing anything, not even the lockfile")]
+        dry_run: bool,
+        #[arg(long = "hash", value_name = "ALGORITHM", value_enum, value_delimiter = ',', help = "Also compute these digests while downloading and record them in the lockfile: sha512, blake3 (repeatable or comma-separated)")]
+        hashes: Vec<checksum::Algorithm>,
+    },
+    #[command(about = "Save, check or remove the GitHub token egit uses")]
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+    #[command(about = "Manage short names for packages")]
+    Alias {
+        #[command(subcommand)]
+        action: AliasAction,
+    },
+    #[command(about = "Show or change the settings in ~/.config/egit/config.toml")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    #[command(about = "Show or clear the cache of downloaded release assets in ~/.cache/egit")]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    #[command(about = "Print a shell completion script to stdout")]
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    #[command(about = "Write man pages for egit and each of its commands")]
+    GenMan {
+        #[arg(long, default_value = ".", help = "Directory to write the pages into")]
+        dir: PathBuf,
+    },
+    // Called by the completion scripts
+    #[command(name = "__complete-packages", hide = true)]
+    CompletePackages,
 }
 
-#[derive(Deserialize, Debug)]
-struct GitHubRelease {
-    tag_name: String,
-    assets: Vec<GitHubAsset>,
-    zipball_url: String,
-    tarball_url: String,
+#[derive(Subcommand, Debug)]
+enum AuthAction {
+    #[command(about = "Check a personal access token and save it in the OS keyring")]
+    Login {
+        #[arg(long, help = "Read the token from stdin instead of prompting for it")]
+        with_token: bool,
+        #[arg(long, help = "Save the token in a file readable only by you instead of the keyring; required on Windows, where the Credential Manager is not supported yet")]
+        insecure_storage: bool,
+    },
+    #[command(about = "Show which token is used, where it comes from and whom it belongs to")]
+    Status,
+    #[command(about = "Remove the saved token")]
+    Logout,
 }
 
-#[derive(Deserialize, Debug)]
-struct GitHubAsset {
-    name: String,
-    browser_download_url: String,
-    size: u64,
+#[derive(Subcommand, Debug)]
+enum AliasAction {
+    #[command(about = "Add or replace an alias, e.g. `egit alias add rg BurntSushi/ripgrep`")]
+    Add {
+        name: String,
+        #[arg(help = "Package the alias stands for, e.g. `owner/repo` or `gitlab:group/project`")]
+        package: String,
+    },
+    #[command(about = "Remove an alias")]
+    Remove {
+        name: String,
+    },
+    #[command(about = "List all aliases")]
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    #[command(about = "List the cached assets")]
+    List,
+    #[command(about = "Print how much space the cache takes")]
+    Size,
+    #[command(about = "Delete everything in the cache")]
+    Clean,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    #[command(about = "Print the value of a setting")]
+    Get {
+        #[arg(help = "Setting such as `dir`, `threads` or `hosts.gitlab`")]
+        key: String,
+    },
+    #[command(about = "Change a setting")]
+    Set {
+        key: String,
+        value: String,
+    },
+    #[command(about = "Remove a setting, restoring its default")]
+    Unset {
+        key: String,
+    },
+    #[command(about = "List all settings")]
+    List,
 }
 
 fn main() {
-    let args = Args::parse();
+    let args = Args::try_parse().unwrap_or_else(|e| {
+        // clap exits with 2 on invalid arguments, which is the network code here
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() { 1 } else { 0 });
+    });
+    if let Err(e) = run(args) {
+        output::fail(&e);
+    }
+}
+
+// The token, HTTP client and providers, set up on first use so commands that
+// never go online don't read the keyring or run `gh`
+struct Network {
+    // `--token`
+    flag: Option<String>,
+    // The token from the config file for `host`
+    configured: Option<String>,
+    host: String,
+    // Everything but the token
+    options: ClientOptions,
+    mirrors: BTreeMap<String, String>,
+    // `--host`
+    provider_host: Option<String>,
+    hosts: BTreeMap<String, String>,
+    tokens: BTreeMap<String, String>,
+    resolved: OnceCell<Option<(String, auth::Source)>>,
+    client: OnceCell<Client>,
+    providers: OnceCell<Providers>,
+}
+
+impl Network {
+    // The GitHub token in effect and where it came from, looked up once
+    fn resolved(&self) -> Option<&(String, auth::Source)> {
+        self.resolved.get_or_init(|| auth::resolve(self.flag.clone(), self.configured.as_deref(), &self.host)).as_ref()
+    }
+
+    fn authenticated(&self) -> bool {
+        self.resolved().is_some()
+    }
+
+    // The client options with the GitHub token
+    fn options(&self) -> ClientOptions {
+        ClientOptions { token: self.resolved().map(|(token, _)| token.clone()), ..self.options.clone() }
+    }
+
+    fn client(&self) -> Result<&Client, EgitError> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let options = self.options();
+        let client = client::build_client(&options)
+            .map_err(|e| EgitError::Other(format!("Failed to build HTTP client: {}", e)))?;
+        client::set_mirrors(&self.mirrors, &options)
+            .map_err(|e| EgitError::Other(format!("Invalid mirror in the config: {}", e)))?;
+        Ok(self.client.get_or_init(|| client))
+    }
+
+    fn providers(&self) -> Result<&Providers, EgitError> {
+        if let Some(providers) = self.providers.get() {
+            return Ok(providers);
+        }
+        let providers = Providers::new(self.client()?.clone(), self.options(), self.provider_host.clone(), self.hosts.clone(), self.tokens.clone());
+        Ok(self.providers.get_or_init(|| providers))
+    }
+}
+
+fn run(args: Args) -> Result<(), EgitError> {
+    output::set_verbosity(args.quiet, args.verbose);
+    progress::set_mode(args.progress);
+    output::set_format(if args.json { Format::Json } else { args.format });
+    assets::set_wait_for_rate_limit(args.wait_for_rate_limit);
+    client::set_offline(args.offline);
+    if let Some(rate) = args.limit_rate {
+        throttle::set_limit(rate);
+    }
+    
+    let config = Config::load().map_err(|e| {
+        let path = config::config_path().map_or_else(|_| "the config file".to_string(), |p| format!("`{}`", p.display()));
+        EgitError::Other(format!("Failed to read {}: {}", path, e))
+    })?;
+    config::set_aliases(config.aliases.clone());
+    let api_url = match (std::env::var("EGIT_GITHUB_API"), &config.api_url) {
+        _ if args.api_url.is_some() => args.api_url,
+        (Ok(value), _) => Some(assets::parse_api_url(&value)
+            .map_err(|e| EgitError::Other(format!("Invalid EGIT_GITHUB_API: {}", e)))?),
+        (Err(_), Some(value)) => Some(assets::parse_api_url(value)
+            .map_err(|e| EgitError::Other(format!("Invalid api_url in the config: {}", e)))?),
+        (Err(_), None) => None,
+    };
+    if let Some(url) = api_url {
+        assets::set_api_url(url);
+    }
+    
+    let host = auth::github_host();
+    let configured = config.tokens.get(&host).or(config.token.as_ref()).cloned();
+    let client_options = ClientOptions {
+        resolve: args.resolve,
+        token: None,
+        proxy: args.proxy.or(config.proxy.clone()),
+        cacert: args.cacert.or(config.cacert.clone()),
+        insecure: args.insecure,
+    };
+    for (host, ip) in &client_options.resolve {
+        status!("+ Resolving `{}` to `{}`", host, ip);
+    }
+    if client_options.insecure {
+        log::warn!("- WARNING: TLS certificate verification is disabled (--insecure). Downloads and tokens can be intercepted or tampered with.");
+    }
+    let network = Network {
+        flag: args.token,
+        configured,
+        host: host.clone(),
+        options: client_options,
+        mirrors: config.mirrors.clone(),
+        provider_host: args.host,
+        hosts: config.hosts.clone(),
+        tokens: config.tokens.clone(),
+        resolved: OnceCell::new(),
+        client: OnceCell::new(),
+        providers: OnceCell::new(),
+    };
 
     match args.command {
-        Command::Download { package, source, multithread, threads, tags, releases, assets } => {
-            println!("+ Searching for `{}`...", package);
+        Command::Download { package, source, git_ref, multithread, threads, tags, releases, assets, compare_local, skip_identical, sparkline, order_by, since_version, limit, pre, write_version_file, version_file, asset, auto, no_interactive, all_assets, max_assets, max_size, content_type, sha256, pubkey, verify_sigstore, certificate_identity, certificate_oidc_issuer, no_resume, force, skip_existing, no_cache, output, dir, stdout, retries, extract, extract_to, strip_components, keep_archive, dry_run, hashes } => {
+            if stdout {
+                if output::is_json() {
+                    return Err(EgitError::Other("--stdout cannot be combined with --format json".to_string()));
+                }
+                output::reserve_stdout();
+            }
             
-            let (owner, repo, version) = parse_package(&package);
-            let client = Client::builder()
-                .timeout(std::time::Duration::from_secs(30))
-                .build()
-                .unwrap();
+            status!("+ Searching for `{}`...", package);
+            
+            let (provider, owner, repo, version) = open_package(network.providers()?, &package)?;
+            let provider = provider.as_ref();
+            let client = provider.client();
+            let asset = match asset {
+                Some(asset) => Some(asset),
+                None => config_asset_pattern(&config, &format!("{}{}/{}", provider.prefix(), owner, repo))?,
+            };
             
             // Handle --tags flag
             if tags {
-                match assets::fetch_tags(&client, &owner, &repo) {
-                    Ok(tags) => {
-                        assets::display_tags(&tags);
-                    },
-                    Err(e) => {
-                        println!("- Failed to fetch tags: {}", get_error_message(&e));
-                    }
-                }
-                println!("=== Task End ===");
-                return;
+                list_tags(provider, &owner, &repo, since_version.as_ref(), limit)?;
+                output::task_end();
+                return Ok(());
             }
             
             // Handle --releases flag
             if releases {
-                match assets::fetch_releases(&client, &owner, &repo) {
-                    Ok(releases) => {
-                        assets::display_releases(&releases);
-                    },
-                    Err(e) => {
-                        println!("- Failed to fetch releases: {}", get_error_message(&e));
-                    }
-                }
-                println!("=== Task End ===");
-                return;
+                list_releases(provider, &owner, &repo, since_version.as_ref(), order_by, limit)?;
+                output::task_end();
+                return Ok(());
             }
             
-            let releases = match get_releases(&client, &owner, &repo) {
-                Ok(releases) => releases,
-                Err(e) => {
-                    println!("- Failed to fetch releases: {}", get_error_message(&e));
-                    println!("=== Task End ===");
-                    exit(1);
-                }
-            };
+            // Everything below reports a single download result in JSON mode
+            if !assets {
+                output::begin_report(&package);
+            }
             
-            let target_release = match &version {
-                Some(v) if v == "latest" => {
-                    releases.first().unwrap_or_else(|| {
-                        println!("- No releases found for this package");
-                        println!("=== Task End ===");
-                        exit(1);
-                    })
-                },
-                Some(v) => {
-                    releases.iter().find(|r| r.tag_name == *v).unwrap_or_else(|| {
-                        println!("- Version {} not found", v);
-                        println!("=== Task End ===");
-                        exit(1);
-                    })
-                },
-                None => {
-                    releases.first().unwrap_or_else(|| {
-                        println!("- No releases found for this package");
-                        println!("=== Task End ===");
-                        exit(1);
-                    })
-                },
+            let options = DownloadOptions {
+                multithread,
+                threads: threads.or(config.threads).unwrap_or(4),
+                // The sparkline is redrawn in place, so only show it on a terminal
+                sparkline: sparkline && io::stderr().is_terminal(),
+                write_version_file,
+                version_file,
+                assume_yes: args.assume_yes,
+                asset,
+                pre,
+                auto,
+                no_interactive,
+                all_assets,
+                sha256,
+                pubkey,
+                // clap requires both with --verify-sigstore
+                sigstore: certificate_identity.zip(certificate_oidc_issuer)
+                    .filter(|_| verify_sigstore)
+                    .map(|(identity, oidc_issuer)| verify::Identity { identity, oidc_issuer }),
+                max_size,
+                max_assets,
+                content_type,
+                no_resume,
+                force,
+                skip_existing,
+                no_cache,
+                output,
+                dir: dir.or(config.dir.clone()),
+                stdout,
+                authenticated: network.authenticated(),
+                retries,
+                extract: extract || extract_to.is_some(),
+                extract_to,
+                strip_components,
+                keep_archive,
+                dry_run,
+                hashes,
             };
             
+            // A branch, tag or commit bypasses release resolution entirely
+            if let Some(git_ref) = &git_ref {
+                if version.is_some() {
+                    return Err(EgitError::Other("--ref cannot be combined with a version in the package name".to_string()));
+                }
+                output::record(|r| r.tag = Some(git_ref.clone()));
+                let tarball_url = provider.archive_url(&owner, &repo, "tarball", git_ref);
+                let zipball_url = provider.archive_url(&owner, &repo, "zipball", git_ref);
+                return download_source(client, &FsStorage, git_ref, &tarball_url, &zipball_url, &package, &options);
+            }
+            
+            let (similar, target_release) = &resolve_download_release(provider, &owner, &repo, order_by, version.as_deref(), &options)?;
+            let package = similar.clone().unwrap_or(package);
+            output::record(|r| r.tag = Some(target_release.tag_name.clone()));
+            
             if let Some(v) = &version {
-                println!("+ Found `{}@{}` redirecting to `{}@{}`", 
+                status!("+ Found `{}@{}` redirecting to `{}@{}`", 
                          package, v, package, target_release.tag_name);
             }
             
             // Handle --assets flag
             if assets {
-                // Fetch the release with full asset details
-                let releases = match assets::fetch_releases(&client, &owner, &repo) {
-                    Ok(releases) => releases,
-                    Err(e) => {
-                        println!("- Failed to fetch releases: {}", get_error_message(&e));
-                        println!("=== Task End ===");
-                        exit(1);
-                    }
+                assets::display_assets(target_release);
+                output::task_end();
+                return Ok(());
+            }
+            
+            // Handle --compare-local flag
+            if let Some(path) = &compare_local {
+                if source {
+                    return Err(EgitError::Other("--compare-local is only supported for release assets".to_string()));
+                }
+                let identical = compare_local_file(client, &FsStorage, target_release, &package, path, &options)?;
+                if !skip_identical {
+                    finish(if identical { 0 } else { output::EXIT_DIFFERS });
+                }
+                if identical {
+                    status!("+ Skipping the download, `{}` is up to date", path.display());
+                    output::task_end();
+                    return Ok(());
+                }
+                // Update the compared file unless --output names another
+                let options = match options.output {
+                    Some(_) => options,
+                    None => DownloadOptions { output: Some(path.clone()), dir: None, ..options },
                 };
-                
-                // Find the matching release
-                let release_with_assets = releases.iter().find(|r| r.tag_name == target_release.tag_name)
-                    .unwrap_or_else(|| {
-                        println!("- Release not found");
-                        println!("=== Task End ===");
-                        exit(1);
-                    });
-                
-                assets::display_assets(release_with_assets);
-                println!("=== Task End ===");
-                return;
+                return download_asset(client, &FsStorage, target_release, &package, &options);
             }
             
             if source {
-                download_source(&client, target_release, &package, multithread, threads);
+                download_source(client, &FsStorage, &target_release.tag_name, &target_release.tarball_url, &target_release.zipball_url, &package, &options)?;
             } else {
-                download_asset(&client, target_release, &package, multithread, threads);
+                download_asset(client, &FsStorage, target_release, &package, &options)?;
             }
-        }
-    }
-}
-
-fn parse_package(package: &str) -> (String, String, Option<String>) {
-    let re = Regex::new(r"^([^/@]+)/([^@]+)(?:@(.+))?$").unwrap();
-    
-    if let Some(captures) = re.captures(package) {
-        let owner = captures.get(1).unwrap().as_str().to_string();
-        let repo = captures.get(2).unwrap().as_str().to_string();
-        let version = captures.get(3).map(|v| v.as_str().to_string());
-        (owner, repo, version)
-    } else {
-        let parts: Vec<&str> = package.split('@').collect();
-        if parts.len() == 2 {
-            ("github".to_string(), parts[0].to_string(), Some(parts[1].to_string()))
-        } else {
-            ("github".to_string(), parts[0].to_string(), None)
-        }
-    }
-}
-
-fn get_releases(client: &Client, owner: &str, repo: &str) -> Result<Vec<GitHubRelease>, reqwest::Error> {
-    let url = format!("https://api.github.com/repos/{}/{}/releases", owner, repo);
-    let response = client.get(&url)
-        .header("User-Agent", "egit-cli")
-        .send()?;
-    
-    response.json()
-}
-
-fn download_asset(client: &Client, release: &GitHubRelease, package: &str, multithread: bool, threads: usize) {
-    if let Some(asset) = release.assets.first() {
-        println!("+ Downloading `{}@{} -> {}`...", 
-                 package, release.tag_name, asset.name);
-        
-        let total_size = asset.size;
-        let start_time = std::time::Instant::now();
-        
-        if multithread {
-            println!("+ Using {} threads for parallel download...", threads);
+        },
+        Command::List { package, installed, tags, releases: _, assets, order_by, since_version, limit, pre } => {
+            let Some(package) = package.filter(|_| !installed) else {
+                registry::display_installed(&load_registry()?);
+                output::task_end();
+                return Ok(());
+            };
+            status!("+ Searching for `{}`...", package);
+            
+            let (provider, owner, repo, _) = open_package(network.providers()?, &package)?;
+            let provider = provider.as_ref();
+            
+            if tags {
+                list_tags(provider, &owner, &repo, since_version.as_ref(), limit)?;
+            } else if let Some(tag) = &assets {
+                let release = resolve_release(provider, &owner, &repo, order_by, Some(tag), pre)?;
+                assets::display_assets(&release);
+            } else {
+                // Releases are listed by default
+                list_releases(provider, &owner, &repo, since_version.as_ref(), order_by, limit)?;
+            }
+            output::task_end();
+        },
+        Command::Info { package, order_by, pre } => {
+            status!("+ Searching for `{}`...", package);
+            
+            let (provider, owner, repo, version) = open_package(network.providers()?, &package)?;
+            let release = resolve_release(provider.as_ref(), &owner, &repo, order_by, version.as_deref(), pre)?;
+            info::display_release(&release);
+            output::task_end();
+        },
+        Command::Latest { package, order_by, pre } => {
+            // Only the tag goes to stdout, so that `$(egit latest owner/repo)` captures it
+            output::reserve_stdout();
+            status!("+ Searching for `{}`...", package);
             
-            match multitread::download_parallel(client, &asset.browser_download_url, &asset.name, total_size, threads) {
-                Ok(_) => {
-                    // Calculate accurate download time
-                    let elapsed = start_time.elapsed().as_secs_f64();
-                    
-                    println!("+ Downloaded `{}@{}` , total size: {:.1}KB | spend {:.1}s.", 
-                             package, release.tag_name, total_size as f64 / 1024.0, elapsed);
+            let (provider, owner, repo, version) = open_package(network.providers()?, &package)?;
+            let release = resolve_release(provider.as_ref(), &owner, &repo, order_by, version.as_deref(), pre)?;
+            if output::is_json() {
+                output::print_json(&serde_json::json!({
+                    "package": format!("{}{}/{}", provider.prefix(), owner, repo),
+                    "tag": release.tag_name,
+                    "name": release.name,
+                    "published_at": release.published_at,
+                    "prerelease": release.prerelease,
+                    "html_url": release.html_url,
+                    "assets": release.assets.iter().map(|asset| &asset.name).collect::<Vec<_>>(),
+                }));
+            } else {
+                println!("{}", release.tag_name);
+            }
+            output::task_end();
+        },
+        Command::Changelog { package, from, since_version, to, pre } => {
+            status!("+ Searching for `{}`...", package);
+            
+            let (provider, owner, repo, _) = open_package(network.providers()?, &package)?;
+            // Default to what changed since the installed version
+            let from = match from {
+                Some(from) => Some(from),
+                None if since_version.is_some() => None,
+                None => {
+                    let key = format!("{}{}/{}", provider.prefix(), owner, repo);
+                    let installed = load_registry()?.packages.remove(&key).map(|installed| installed.version);
+                    if let Some(version) = &installed {
+                        status!("+ Showing the changes since the installed version {}", version);
+                    }
+                    installed
                 },
-                Err(e) => {
-                    println!("- Parallel download failed: {}", e);
-                    println!("=== Task End ===");
-                    exit(1);
-                }
+            };
+            let releases = changelog_releases(provider.as_ref(), &owner, &repo, from.as_deref(), since_version.as_ref(), to.as_deref(), pre)?;
+            if releases.is_empty() {
+                let since = since_version.as_ref().map(|since| since.to_string());
+                status!("- No releases after {}", from.as_deref().or(since.as_deref()).unwrap_or("the start"));
             }
-        } else {
-            let response = match client.get(&asset.browser_download_url)
-                .header("User-Agent", "egit-cli")
-                .send() {
-                Ok(resp) => resp,
-                Err(e) => {
-                    println!("- Download failed: {}", get_error_message(&e));
-                    println!("=== Task End ===");
-                    exit(1);
-                }
+            info::display_changelog(&releases);
+            output::task_end();
+        },
+        Command::Search { query, limit, sort } => {
+            status!("+ Searching GitHub for `{}`...", query);
+            
+            let mut repositories = search::search_repositories(network.client()?, &query, sort, limit.into())
+                .context("Failed to search repositories")?;
+            search::fill_latest_releases(network.client()?, &mut repositories);
+            search::display_repositories(&repositories);
+            output::task_end();
+        },
+        Command::Get { package, path, output, force, skip_existing } => {
+            let (provider, owner, repo, version) = open_package(network.providers()?, &package)?;
+            if !provider.prefix().is_empty() {
+                return Err(EgitError::Other("`get` only supports GitHub repositories".to_string()));
+            }
+            // `path@ref`, falling back to `owner/repo@ref`
+            let (path, git_ref) = match path.rsplit_once('@') {
+                Some((path, git_ref)) => (path.to_string(), Some(git_ref.to_string())),
+                None => (path, version),
             };
+            let path = path.trim_matches('/');
+            status!("+ Searching for `{}` in `{}/{}`...", path, owner, repo);
             
-            let pb = ProgressBar::new(total_size);
-            pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-                .unwrap()
-                .progress_chars("█▉▊▋▌▍▎▏ "));
+            let options = DownloadOptions {
+                assume_yes: args.assume_yes,
+                force,
+                skip_existing,
+                output,
+                authenticated: network.authenticated(),
+                retries: 3,
+                ..Default::default()
+            };
             
-            let mut file = match File::create(&asset.name) {
-                Ok(file) => file,
-                Err(e) => {
-                    println!("- Failed to create file: {}", e);
-                    println!("=== Task End ===");
-                    exit(1);
-                }
+            output::begin_report(&package);
+            get_path(provider.client(), &FsStorage, &owner, &repo, path, git_ref.as_deref(), &options)?;
+            output::task_end();
+        },
+        Command::Install { package, asset, pre, bin_dir, max_size, dry_run, hashes, write_version_file, version_file } => {
+            // Absolute, so the registry stays valid from any working directory
+            let bin_dir = bin_dir.map_or_else(install::default_bin_dir, std::path::absolute)
+                .context("Failed to find the install directory")?;
+            let asset = match asset {
+                Some(asset) => Some(asset),
+                None => config_asset_pattern(&config, &package_key(network.providers()?, &package)?)?,
+            };
+            let options = DownloadOptions {
+                assume_yes: args.assume_yes,
+                asset,
+                pre,
+                authenticated: network.authenticated(),
+                retries: 3,
+                dry_run,
+                max_size,
+                hashes,
+                write_version_file,
+                // Absolute too, as it is recorded in the registry
+                version_file: version_file.map(std::path::absolute).transpose()
+                    .context("Failed to find the version file")?,
+                ..Default::default()
             };
             
-            // Use custom ProgressReader to stream the response with progress updates
-            let mut reader = ProgressReader {
-                reader: response,
-                progress_bar: pb.clone(),
-                bytes_read: 0,
+            output::begin_report(&package);
+            install_package(network.providers()?, &package, &bin_dir, &options)?;
+            output::task_end();
+        },
+        Command::Uninstall { package } => {
+            let key = package_key(network.providers()?, &package)?;
+            
+            let mut registry = load_registry()?;
+            let Some(installed) = registry.packages.remove(&key) else {
+                return Err(EgitError::NotFound(format!("`{}` is not installed", key)));
+            };
+            for file in &installed.files {
+                match std::fs::remove_file(file) {
+                    Ok(()) => status!("+ Removed `{}`", file.display()),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => status!("- `{}` was already gone", file.display()),
+                    Err(e) => return Err(EgitError::from(e).context(format!("Failed to remove `{}`", file.display()))),
+                }
+            }
+            save_registry(&registry)?;
+            status!("+ Uninstalled `{}@{}`", key, installed.version);
+            output::print_result(serde_json::json!({
+                "success": true,
+                "package": key,
+                "tag": installed.version,
+                "files": installed.files,
+            }));
+            output::task_end();
+        },
+        Command::Upgrade { package, dry_run } => {
+            let registry = load_registry()?;
+            let packages: Vec<String> = match &package {
+                Some(package) => {
+                    let key = package_key(network.providers()?, package)?;
+                    if !registry.packages.contains_key(&key) {
+                        return Err(EgitError::NotFound(format!("`{}` is not installed", key)));
+                    }
+                    vec![key]
+                },
+                None => registry.packages.keys().cloned().collect(),
             };
+            if packages.is_empty() {
+                status!("- No packages installed");
+            }
+            
+            let upgrades = find_upgrades(network.providers()?, &registry, &packages)?;
+            if upgrades.is_empty() {
+                status!("+ {} of {} packages can be upgraded", upgrades.len(), packages.len());
+                if output::is_json() {
+                    output::print_json(&upgrades);
+                }
+                output::task_end();
+                return Ok(());
+            }
+            
+            let batch = progress::begin_batch(if dry_run { 0 } else { upgrades.len() }, None);
+            for upgrade in &upgrades {
+                let options = DownloadOptions {
+                    assume_yes: args.assume_yes,
+                    asset: config_asset_pattern(&config, &upgrade.package)?,
+                    // Upgrades run unattended, so take the best platform match
+                    no_interactive: true,
+                    authenticated: network.authenticated(),
+                    retries: 3,
+                    dry_run,
+                    ..Default::default()
+                };
+                // Reinstall next to the previous files
+                let bin_dir = match registry.packages[&upgrade.package].files.first().and_then(|file| file.parent()) {
+                    Some(dir) => dir.to_path_buf(),
+                    None => install::default_bin_dir().context("Failed to find the install directory")?,
+                };
+                install_package(network.providers()?, &format!("{}@{}", upgrade.package, upgrade.to), &bin_dir, &options)?;
+            }
+            drop(batch);
+            if dry_run {
+                status!("+ {} of {} packages can be upgraded", upgrades.len(), packages.len());
+            } else {
+                status!("+ Upgraded {} of {} packages", upgrades.len(), packages.len());
+            }
+            if output::is_json() {
+                output::print_json(&upgrades);
+            }
+            output::task_end();
+        },
+        Command::Outdated { jobs } => {
+            let registry = load_registry()?;
+            status!("+ Checking {} installed packages...", registry.packages.len());
+            
+            // Each worker takes the next package until none are left
+            let packages: Vec<(&String, &InstalledPackage)> = registry.packages.iter().collect();
+            let next = AtomicUsize::new(0);
+            let checked = Mutex::new(Vec::new());
+            let providers = network.providers()?;
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..jobs.clamp(1, packages.len().max(1))).map(|_| scope.spawn(|| {
+                    while let Some((package, installed)) = packages.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let latest = match latest_stable_release(providers, package) {
+                            Ok(latest) => latest.map(|release| release.tag_name),
+                            Err(e) => {
+                                // Let the other workers finish their current package and stop
+                                next.store(packages.len(), Ordering::Relaxed);
+                                return Err(e);
+                            },
+                        };
+                        checked.lock().unwrap().push(registry::Outdated {
+                            package: package.to_string(),
+                            installed: installed.version.clone(),
+                            update_available: latest.as_ref().is_some_and(|latest| version::is_newer(latest, &installed.version)),
+                            latest,
+                        });
+                    }
+                    Ok(())
+                })).collect();
+                workers.into_iter().try_for_each(|worker| worker.join().unwrap())
+            })?;
+            
+            let mut checked = checked.into_inner().unwrap();
+            checked.sort_by(|a, b| a.package.cmp(&b.package));
+            let outdated = checked.iter().filter(|package| package.update_available).count();
+            registry::display_outdated(&checked);
+            status!("+ {} of {} packages can be upgraded", outdated, checked.len());
+            output::task_end();
+        },
+        Command::SelfUpdate { check, pre } => {
+            status!("+ Checking for a newer egit...");
+            let current = env!("CARGO_PKG_VERSION");
+            let (provider, owner, repo, _) = open_package(network.providers()?, selfupdate::REPOSITORY)?;
+            let release = resolve_release(provider.as_ref(), &owner, &repo, None, None, pre)?;
+            let available = version::is_newer(&release.tag_name, current);
+            output::print_result(serde_json::json!({
+                "current": current,
+                "latest": release.tag_name,
+                "update_available": available,
+            }));
+            if !available {
+                status!("+ egit {} is up to date", current);
+                output::task_end();
+                return Ok(());
+            }
+            if check {
+                status!("+ egit {} is available (current: {}), run `egit self-update` to install it", release.tag_name, current);
+                output::task_end();
+                return Ok(());
+            }
             
-            // Copy the response to the file using the ProgressReader
-            if let Err(e) = io::copy(&mut reader, &mut file) {
-                println!("- Download failed: {}", e);
-                println!("=== Task End ===");
-                exit(1);
+            if !args.assume_yes && !confirm::can_prompt() {
+                return Err(EgitError::Other(format!("egit {} is available, pass --yes to update without a terminal", release.tag_name)));
+            }
+            if !confirm::confirm(&format!("Update egit from {} to {}?", current, release.tag_name), args.assume_yes) {
+                status!("- Update cancelled, egit {} was left in place", current);
+                output::task_end();
+                return Ok(());
             }
             
-            pb.finish_with_message("Download completed");
+            let exe = selfupdate::current_exe().context("Failed to find the egit executable")?;
+            selfupdate::remove_old_exe(&exe);
+            let mut options = DownloadOptions {
+                no_interactive: true,
+                authenticated: network.authenticated(),
+                retries: 3,
+                ..Default::default()
+            };
+            let Some(asset) = select_asset(&release, &options)? else {
+                return Err(EgitError::NotFound(format!("egit {} has no assets", release.tag_name)));
+            };
+            status!("+ Downloading egit {} ({})...", release.tag_name, asset.name);
+            // Never run an unverified binary
+            let Some(expected) = expected_sha256(provider.client(), &release, asset, &options)? else {
+                return Err(EgitError::Verification(format!("egit {} publishes no checksum for `{}`, refusing to update", release.tag_name, asset.name)));
+            };
+            options.sha256 = Some(expected);
             
-            // Calculate accurate download time
-            let elapsed = start_time.elapsed().as_secs_f64();
+            let staging = std::env::temp_dir().join(format!("egit-self-update-{}", std::process::id()));
+            let updated = install_release_asset(provider.client(), &FsStorage, &release, asset, "egit", &staging, &options)
+                .and_then(|(files, _)| {
+                    let binary = format!("egit{}", std::env::consts::EXE_SUFFIX);
+                    let new = files.iter().find(|file| file.file_name().is_some_and(|name| name == binary.as_str()))
+                        .ok_or_else(|| EgitError::NotFound(format!("`{}` does not contain `{}`", asset.name, binary)))?;
+                    selfupdate::replace_exe(&exe, new).context(format!("Failed to replace `{}`", exe.display()))
+                });
+            let _ = std::fs::remove_dir_all(&staging);
+            updated?;
+            status!("+ Updated egit from {} to {}", current, release.tag_name);
+            output::task_end();
+        },
+        Command::Sync { manifest: manifest_path, jobs, locked, dry_run, hashes } => {
+            let manifest = Manifest::load(&manifest_path)
+                .context(format!("Failed to read `{}`", manifest_path.display()))?;
+            let lock_path = lockfile::lock_path(&manifest_path);
+            let lockfile = if locked {
+                let lockfile = Lockfile::load(&lock_path)
+                    .context(format!("Failed to read `{}`", lock_path.display()))?;
+                check_lockfile(&manifest, &lockfile)?;
+                Some(lockfile)
+            } else {
+                None
+            };
+            status!("+ Syncing {} packages into `{}`...", manifest.packages.len(), manifest.dir.display());
             
-            println!("+ Downloaded `{}@{}` , total size: {:.1}KB | spend {:.1}s.", 
-                     package, release.tag_name, total_size as f64 / 1024.0, elapsed);
-        }
+            // Each worker takes the next package until none are left
+            let packages: Vec<(&String, PackageOptions)> = manifest.packages.iter()
+                .map(|(package, spec)| (package, spec.options()))
+                .collect();
+            let next = AtomicUsize::new(0);
+            let synced = Mutex::new(Vec::new());
+            let batch = progress::begin_batch(if dry_run { 0 } else { packages.len() }, None);
+            // What every package shares; the rest comes from its entry
+            let base = DownloadOptions {
+                authenticated: network.authenticated(),
+                dry_run,
+                hashes,
+                ..Default::default()
+            };
+            let providers = network.providers()?;
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..jobs.clamp(1, packages.len().max(1))).map(|_| scope.spawn(|| {
+                    while let Some((package, spec)) = packages.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let locked = lockfile.as_ref().and_then(|lockfile| lockfile.find(package));
+                        match sync_package(providers, package, spec, &manifest.dir, locked, &base) {
+                            Ok(result) => synced.lock().unwrap().push(result),
+                            Err(e) => {
+                                // Let the other workers finish their current package and stop
+                                next.store(packages.len(), Ordering::Relaxed);
+                                return Err(e.context(format!("`{}`", package)));
+                            },
+                        }
+                    }
+                    Ok(())
+                })).collect();
+                workers.into_iter().try_for_each(|worker| worker.join().unwrap())
+            })?;
+            
+            drop(batch);
+            let mut synced = synced.into_inner().unwrap();
+            synced.sort_by(|a, b| a.name.cmp(&b.name));
+            if dry_run {
+                status!("+ {} packages would be synced", synced.len());
+            } else {
+                status!("+ Synced {} packages", synced.len());
+            }
+            if !locked && !dry_run {
+                let lockfile = Lockfile { packages: synced };
+                lockfile.save(&lock_path).context(format!("Failed to write `{}`", lock_path.display()))?;
+                status!("+ Wrote `{}`", lock_path.display());
+                synced = lockfile.packages;
+            }
+            if output::is_json() {
+                output::print_json(&synced);
+            }
+            output::task_end();
+        },
+        Command::Auth { action } => {
+            match action {
+                AuthAction::Login { with_token, insecure_storage } => {
+                    let token = if with_token {
+                        let mut token = String::new();
+                        io::stdin().read_to_string(&mut token).context("Failed to read the token from stdin")?;
+                        token
+                    } else {
+                        auth::prompt_token(&host)?
+                    };
+                    let token = token.trim();
+                    if token.is_empty() {
+                        return Err(EgitError::Other("The token is empty".to_string()));
+                    }
+                    let options = ClientOptions { token: Some(token.to_string()), ..network.options.clone() };
+                    let client = client::build_client(&options)
+                        .map_err(|e| EgitError::Other(format!("Invalid token: {}", e)))?;
+                    let (login, _) = auth::fetch_user(&client)?;
+                    let store = auth::save(&host, token, insecure_storage)?;
+                    status!("+ Logged in to {} as {}", host, login);
+                    status!("+ Saved the token in {}", auth::Source::Stored(store));
+                    if let Some((_, source)) = network.resolved() && source.is_explicit() {
+                        status!("- {} is set and takes precedence over the saved token", source);
+                    }
+                    output::print_result(serde_json::json!({ "success": true, "host": host, "login": login }));
+                },
+                AuthAction::Status => {
+                    let Some((token, source)) = network.resolved() else {
+                        return Err(EgitError::Auth(format!("Not logged in to {}. Run `egit auth login` or set GITHUB_TOKEN.", host)));
+                    };
+                    let (login, scopes) = auth::fetch_user(network.client()?).map_err(|e| match e {
+                        EgitError::Auth(_) => e.context(format!("The token from {} does not work", source)),
+                        e => e,
+                    })?;
+                    status!("+ Logged in to {} as {}", host, login);
+                    status!("  Token:  {} (from {})", auth::mask(token), source);
+                    if let Some(scopes) = &scopes {
+                        status!("  Scopes: {}", if scopes.is_empty() { "none" } else { scopes });
+                    }
+                    output::print_result(serde_json::json!({ "host": host, "login": login, "source": source.to_string(), "scopes": scopes }));
+                },
+                AuthAction::Logout => {
+                    let removed = auth::remove(&host)?;
+                    if removed.is_empty() {
+                        status!("- No token was saved for {}", host);
+                    }
+                    for store in &removed {
+                        status!("+ Removed the token for {} from {}", host, auth::Source::Stored(*store));
+                    }
+                    if let Some((_, source)) = network.resolved() && source.is_explicit() {
+                        status!("- {} is still set and will be used", source);
+                    } else if let Some((_, source)) = auth::borrow(&host) {
+                        status!("- egit will still use the token from {}", source);
+                    }
+                    output::print_result(serde_json::json!({ "success": true, "host": host, "removed": !removed.is_empty() }));
+                },
+            }
+            output::task_end();
+        },
+        Command::Alias { action } => {
+            let mut table = read_config_table()?;
+            match action {
+                AliasAction::Add { name, package } => {
+                    config::parse_alias(&name, &package)
+                        .and_then(|()| config::set(&mut table, &format!("aliases.{}", name), &package))
+                        .map_err(|e| EgitError::Other(format!("Invalid alias: {}", e)))?;
+                    save_config(&table)?;
+                    status!("+ `{}` now stands for `{}`", name, package);
+                    output::print_result(serde_json::json!({ "success": true, "alias": name, "package": package }));
+                },
+                AliasAction::Remove { name } => {
+                    if !config::unset(&mut table, &format!("aliases.{}", name)) {
+                        return Err(EgitError::NotFound(format!("No alias named `{}`", name)));
+                    }
+                    save_config(&table)?;
+                    status!("+ Removed alias `{}`", name);
+                    output::print_result(serde_json::json!({ "success": true, "alias": name }));
+                },
+                AliasAction::List => {
+                    if output::is_json() {
+                        output::print_json(&config.aliases);
+                    } else {
+                        output!("=== Aliases ===");
+                        for (name, package) in &config.aliases {
+                            output!("{} = {}", name, package);
+                        }
+                        output!("=== Total: {} aliases ===", config.aliases.len());
+                    }
+                },
+            }
+            output::task_end();
+        },
+        Command::Config { action } => {
+            let mut table = read_config_table()?;
+            match action {
+                ConfigAction::Get { key } => match config::get(&table, &key) {
+                    Some(value) if output::is_json() => output::print_json(value),
+                    Some(toml::Value::String(value)) => println!("{}", value),
+                    Some(value) => println!("{}", value),
+                    None => return Err(EgitError::NotFound(format!("`{}` is not set", key))),
+                },
+                ConfigAction::Set { key, value } => {
+                    config::set(&mut table, &key, &value)
+                        .map_err(|e| EgitError::Other(format!("Invalid setting `{}`: {}", key, e)))?;
+                    save_config(&table)?;
+                    status!("+ Set `{}`", key);
+                    output::print_result(serde_json::json!({ "success": true, "key": key }));
+                    output::task_end();
+                },
+                ConfigAction::Unset { key } => {
+                    let removed = config::unset(&mut table, &key);
+                    if removed {
+                        save_config(&table)?;
+                        status!("+ Removed `{}`", key);
+                    } else {
+                        status!("- `{}` was not set", key);
+                    }
+                    output::print_result(serde_json::json!({ "success": true, "key": key, "removed": removed }));
+                    output::task_end();
+                },
+                ConfigAction::List => {
+                    config::display_config(&table);
+                    output::task_end();
+                },
+            }
+        },
+        Command::Cache { action } => {
+            match action {
+                CacheAction::List => {
+                    let entries = cache::list().context("Failed to read the cache")?;
+                    if output::is_json() {
+                        output::print_json(&entries);
+                    } else {
+                        output!("=== Cached Assets ===");
+                        for entry in &entries {
+                            output!("{} ({})", entry.key, HumanBytes(entry.size));
+                        }
+                        output!("=== Total: {} assets ===", entries.len());
+                    }
+                },
+                CacheAction::Size => {
+                    let (files, bytes) = cache::size().context("Failed to read the cache")?;
+                    output!("{} in {} files", HumanBytes(bytes), files);
+                    output::print_result(serde_json::json!({ "files": files, "bytes": bytes }));
+                },
+                CacheAction::Clean => {
+                    let (files, bytes) = cache::clean().context("Failed to clean the cache")?;
+                    status!("+ Removed {} files ({})", files, HumanBytes(bytes));
+                    output::print_result(serde_json::json!({ "success": true, "files": files, "bytes": bytes }));
+                },
+            }
+            output::task_end();
+        },
+        Command::Completions { shell } => {
+            // Generated from the derived definition, so new flags are picked up automatically
+            let script = completions::script(shell, &mut Args::command());
+            io::stdout().write_all(script.as_bytes()).context("Failed to write the completion script")?;
+        },
+        Command::GenMan { dir } => {
+            let pages = manpage::write_all(Args::command(), &dir)
+                .context(format!("Failed to write man pages to `{}`", dir.display()))?;
+            status!("+ Wrote {} man pages to `{}`", pages.len(), dir.display());
+            output::print_result(serde_json::json!({ "success": true, "pages": pages }));
+        },
+        Command::CompletePackages => {
+            // Installed packages and aliases, one per line
+            let mut names: Vec<String> = load_registry()?.packages.into_keys().collect();
+            names.extend(config.aliases.into_keys());
+            names.sort();
+            names.dedup();
+            for name in names {
+                output!("{}", name);
+            }
+        },
     }
-    println!("=== Task End ===");
+    Ok(())
 }
 
-fn get_error_message(e: &reqwest::Error) -> String {
-    if e.is_timeout() {
-        "Connection timed out. Please check your network connection or try again later.".to_string()
-    } else if e.is_connect() {
-        "Failed to connect to GitHub. Please check your network connection.".to_string()
-    } else if e.is_status() {
-        format!("GitHub returned an error: {}", e.status().unwrap())
-    } else {
-        format!("An error occurred: {}", e)
+fn list_tags(provider: &dyn ReleaseProvider, owner: &str, repo: &str, since_version: Option<&semver::Version>, limit: Option<usize>) -> Result<(), EgitError> {
+    let mut tags = provider.fetch_tags(owner, repo, limit).context("Failed to fetch tags")?;
+    if let Some(since) = since_version {
+        tags = version::filter_since(tags, since, |t| &t.name);
     }
+    assets::display_tags(&tags);
+    Ok(())
 }
 
-fn sanitize_filename(name: &str) -> String {
-    name.replace('@', "-")
-        .replace('/', "-")
-        .replace(':', "-")
-        .replace('*', "-")
-        .replace('?', "-")
-        .replace('"', "-")
-        .replace('<', "-")
-        .replace('>', "-")
-        .replace('|', "-")
+fn list_releases(provider: &dyn ReleaseProvider, owner: &str, repo: &str, since_version: Option<&semver::Version>, order_by: Option<OrderBy>, limit: Option<usize>) -> Result<(), EgitError> {
+    let mut releases = provider.fetch_releases(owner, repo, limit).context("Failed to fetch releases")?;
+    if let Some(since) = since_version {
+        releases = version::filter_since(releases, since, |r| &r.tag_name);
+    }
+    if let Some(order_by) = order_by {
+        version::sort_releases(&mut releases, order_by);
+    }
+    assets::display_releases(&releases);
+    Ok(())
 }
 
-fn download_source(client: &Client, release: &GitHubRelease, package: &str, multithread: bool, threads: usize) {
-    use std::env::consts::OS;
-    
-    let (source_url, extension) = match OS {
-        "windows" => (&release.zipball_url, "zip"),
-        _ => (&release.tarball_url, "tar.gz"),
+// Resolve the release to download from `owner/repo`. When the repository
+// doesn't exist, offer to download the closest match instead, returning its
+// name along with the release.
+fn resolve_download_release(provider: &dyn ReleaseProvider, owner: &str, repo: &str, order_by: Option<OrderBy>, version: Option<&str>, options: &DownloadOptions) -> Result<(Option<String>, GitHubRelease), EgitError> {
+    let error = match find_package_release(provider, owner, repo, order_by, version, options.pre) {
+        Ok(release) => return Ok((None, release)),
+        Err(e) => e,
     };
+    let similar = similar_packages(provider, owner, repo, &error);
+    let error = did_you_mean(error, &similar);
+    // Never switch packages unattended
+    let Some(closest) = similar.first().filter(|_| !options.no_interactive && !options.assume_yes && confirm::can_prompt()) else {
+        return Err(error);
+    };
+    status!("- `{}/{}` was not found", owner, repo);
+    if !confirm::confirm(&format!("Download `{}` instead?", closest), false) {
+        return Err(error);
+    }
+    let (owner, repo) = closest.split_once('/').expect("full names contain `/`");
+    let release = find_package_release(provider, owner, repo, order_by, version, options.pre)?;
+    Ok((Some(closest.clone()), release))
+}
+
+// Download the best asset of a release, install its executables into
+// `bin_dir` and record them in the install registry
+fn install_package(providers: &Providers, package: &str, bin_dir: &Path, options: &DownloadOptions) -> Result<(), EgitError> {
+    status!("+ Searching for `{}`...", package);
     
-    let sanitized_package = sanitize_filename(package);
-    let filename = format!("{}-source.{}", sanitized_package, extension);
+    let (provider, owner, repo, version) = open_package(providers, package)?;
+    let client = provider.client();
+    let release = &resolve_release(provider.as_ref(), &owner, &repo, None, version.as_deref(), options.pre)?;
+    output::record(|r| r.tag = Some(release.tag_name.clone()));
+    let Some(asset) = select_asset(release, options)? else {
+        return Err(EgitError::NotFound("No assets found for this release".to_string()));
+    };
     
-    println!("+ Downloading `{}@{} -> {}`...", 
-             package, release.tag_name, filename);
+    let key = format!("{}{}/{}", provider.prefix(), owner, repo);
+    if options.dry_run {
+        preflight(asset, options)?;
+        plan_download("install", &key, release, asset, &bin_dir.display().to_string(), options);
+        return Ok(());
+    }
     
-    let start_time = std::time::Instant::now();
+    status!("+ Downloading `{}@{}` ({})...", package, release.tag_name, asset.name);
+    let (files, digests) = install_release_asset(client, &FsStorage, release, asset, &repo, bin_dir, options)?;
     
-    // Get total size for progress tracking
-    let total_size = match client.head(source_url)
-        .header("User-Agent", "egit-cli")
-        .send() {
-        Ok(resp) => resp.content_length().unwrap_or(0),
-        Err(e) => {
-            println!("- Failed to get file size: {}", get_error_message(&e));
-            println!("=== Task End ===");
-            exit(1);
+    let mut registry = load_registry()?;
+    // Files from an earlier install that this release no longer ships
+    if let Some(previous) = registry.packages.get(&key) {
+        for stale in previous.files.iter().filter(|f| !files.contains(f)) {
+            if let Err(e) = std::fs::remove_file(stale) {
+                status!("- Failed to remove `{}`: {}", stale.display(), e);
+            }
         }
+    }
+    registry.packages.insert(key, InstalledPackage {
+        version: release.tag_name.clone(),
+        asset: asset.name.clone(),
+        files,
+        sha256: Some(digests.sha256),
+        sha512: digests.sha512,
+        blake3: digests.blake3,
+    });
+    save_registry(&registry)?;
+    output::record(|r| r.success = true);
+    
+    let on_path = std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|p| p == bin_dir));
+    if !on_path {
+        status!("- `{}` is not on your PATH, add it to run the installed executables", bin_dir.display());
+    }
+    Ok(())
+}
+
+// Download or install one manifest entry into `dir`. A locked entry is
+// fetched straight from its recorded URL and must match its digest.
+// `base` holds the options shared by every entry. With --dry-run nothing is
+// fetched, and the digest is the one the API reports, if any.
+fn sync_package(providers: &Providers, package: &str, spec: &PackageOptions, dir: &Path, locked: Option<&LockedPackage>, base: &DownloadOptions) -> Result<LockedPackage, EgitError> {
+    let (provider, owner, repo, version) = open_package(providers, package)?;
+    if version.is_some() {
+        return Err(EgitError::Other("set the version in the manifest entry, not in the package name".to_string()));
+    }
+    let asset = spec.asset.as_deref()
+        .map(select::parse_asset_pattern)
+        .transpose()
+        .map_err(|e| EgitError::Other(format!("invalid asset pattern: {}", e)))?;
+    let options = DownloadOptions {
+        // Reruns replace what the previous sync downloaded
+        assume_yes: true,
+        asset,
+        pre: spec.pre,
+        no_interactive: true,
+        sha256: locked.map(|locked| locked.sha256.clone()),
+        dir: (!spec.install).then(|| dir.to_path_buf()),
+        authenticated: base.authenticated,
+        retries: 3,
+        dry_run: base.dry_run,
+        hashes: base.hashes.clone(),
+        ..Default::default()
     };
     
-    if multithread {
-        println!("+ Using {} threads for parallel download...", threads);
-        
-        match multitread::download_parallel(client, source_url, &filename, total_size, threads) {
-            Ok(_) => {
-                // Calculate accurate download time
-                let elapsed = start_time.elapsed().as_secs_f64();
-                
-                println!("+ Downloaded `{}@{}` , total size: {:.1}KB | spend {:.1}s.", 
-                         package, release.tag_name, total_size as f64 / 1024.0, elapsed);
-            },
-            Err(e) => {
-                println!("- Parallel download failed: {}", e);
-                println!("=== Task End ===");
-                exit(1);
-            }
+    let release = &match locked {
+        Some(locked) => locked_release(locked),
+        None => resolve_release(provider.as_ref(), &owner, &repo, None, spec.version.as_deref(), spec.pre)?,
+    };
+    let Some(asset) = select_asset(release, &options)? else {
+        return Err(EgitError::NotFound(format!("`{}` has no assets", release.tag_name)));
+    };
+    
+    let digests = if base.dry_run {
+        preflight(asset, &options)?;
+        let (action, destination) = if spec.install {
+            ("install", dir.display().to_string())
+        } else {
+            ("download", options.destination(&asset.name))
+        };
+        plan_download(action, package, release, asset, &destination, &options);
+        Digests {
+            sha256: options.sha256.clone().or(asset.sha256().map(str::to_string)).unwrap_or_default(),
+            ..Default::default()
         }
+    } else if spec.install {
+        status!("+ Installing `{}@{}` ({})...", package, release.tag_name, asset.name);
+        install_release_asset(provider.client(), &FsStorage, release, asset, &repo, dir, &options)?.1
     } else {
-        let response = match client.get(source_url)
-                .header("User-Agent", "egit-cli")
-                .send() {
-                Ok(resp) => resp,
-                Err(e) => {
-                    println!("- Download failed: {}", get_error_message(&e));
-                    println!("=== Task End ===");
-                    exit(1);
-                }
-            };
-            
-            let pb = ProgressBar::new(total_size);
-            pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-                .unwrap()
-                .progress_chars("█▉▊▋▌▍▎▏ "));
-            
-            let mut file = match File::create(&filename) {
-                Ok(file) => file,
-                Err(e) => {
-                    println!("- Failed to create file: {}", e);
-                    println!("=== Task End ===");
-                    exit(1);
-                }
-            };
-            
-            // Start time for accurate download time calculation
-            let start_time = std::time::Instant::now();
-            
-            // Use custom ProgressReader to stream the response with progress updates
-            let mut reader = ProgressReader {
-                reader: response,
-                progress_bar: pb.clone(),
-                bytes_read: 0,
-            };
-            
-            // Copy the response to the file using the ProgressReader
-            if let Err(e) = io::copy(&mut reader, &mut file) {
-                println!("- Download failed: {}", e);
-                println!("=== Task End ===");
-                exit(1);
-            }
-        
-        pb.finish_with_message("Download completed");
-        
-        // Calculate accurate download time
-        let elapsed = start_time.elapsed().as_secs_f64();
+        // Hashed while downloading, before extracting removes the archive.
+        // A kept file was not downloaded, so hash it here instead.
+        let downloaded = download_release_asset(provider.client(), &FsStorage, release, asset, package, &options)?;
+        let filename = options.destination(&asset.name);
+        let digests = match downloaded {
+            Some(digests) => digests,
+            None => hash_digests(&FsStorage, &filename, &options.hashes)?,
+        };
+        if spec.extract {
+            extract_asset(&FsStorage, asset, &filename, &options)?;
+        }
+        digests
+    };
+    Ok(LockedPackage {
+        name: package.to_string(),
+        tag: release.tag_name.clone(),
+        asset: asset.name.clone(),
+        url: asset.browser_download_url.clone(),
+        size: asset.size,
+        sha256: digests.sha256,
+        sha512: digests.sha512,
+        blake3: digests.blake3,
+    })
+}
+
+// A release holding just the locked asset, so no API call is needed
+fn locked_release(locked: &LockedPackage) -> GitHubRelease {
+    GitHubRelease {
+        tag_name: locked.tag.clone(),
+        assets: vec![GitHubAsset {
+            name: locked.asset.clone(),
+            url: locked.url.clone(),
+            browser_download_url: locked.url.clone(),
+            size: locked.size,
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+// Check that the lockfile still describes the manifest before reusing it
+fn check_lockfile(manifest: &Manifest, lockfile: &Lockfile) -> Result<(), EgitError> {
+    for (package, spec) in &manifest.packages {
+        let spec = spec.options();
+        let Some(locked) = lockfile.find(package) else {
+            return Err(EgitError::Other(format!("`{}` is missing from the lockfile, run `egit sync` to update it", package)));
+        };
+        let version_changed = spec.version.as_deref().is_some_and(|v| !version::satisfies(&locked.tag, v));
+        let asset_changed = spec.asset.as_deref().is_some_and(|p| !select::matches_pattern(&locked.asset, p));
+        if version_changed || asset_changed {
+            return Err(EgitError::Other(format!("The lockfile entry for `{}` no longer matches the manifest, run `egit sync` to update it", package)));
+        }
+    }
+    if let Some(stale) = lockfile.packages.iter().find(|locked| !manifest.packages.contains_key(&locked.name)) {
+        return Err(EgitError::Other(format!("`{}` is locked but no longer in the manifest, run `egit sync` to update it", stale.name)));
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Upgrade {
+    package: String,
+    from: String,
+    to: String,
+}
+
+// Compare each installed package with its latest release and report the
+// ones that are out of date
+fn find_upgrades(providers: &Providers, registry: &Registry, packages: &[String]) -> Result<Vec<Upgrade>, EgitError> {
+    let mut upgrades = Vec::new();
+    for package in packages {
+        let installed = &registry.packages[package];
+        let Some(latest) = latest_stable_release(providers, package)? else {
+            status!("- `{}` has no releases, skipping", package);
+            continue;
+        };
         
-        println!("+ Downloaded `{}@{}` , total size: {:.1}KB | spend {:.1}s.", 
-                 package, release.tag_name, total_size as f64 / 1024.0, elapsed);
+        if version::is_newer(&latest.tag_name, &installed.version) {
+            status!("+ `{}`: {} -> {}", package, installed.version, latest.tag_name);
+            upgrades.push(Upgrade {
+                package: package.clone(),
+                from: installed.version.clone(),
+                to: latest.tag_name,
+            });
+        } else {
+            status!("+ `{}` is up to date ({})", package, installed.version);
+        }
     }
-    
-    println!("=== Task End ===");
+    Ok(upgrades)
+}
+
+fn load_registry() -> Result<Registry, EgitError> {
+    Registry::load().context("Failed to read the install registry")
+}
+
+fn save_registry(registry: &Registry) -> Result<(), EgitError> {
+    registry.save().context("Failed to update the install registry")
+}
+
+fn read_config_table() -> Result<toml::Table, EgitError> {
+    config::read_table().context("Failed to read the config")
+}
+
+fn save_config(table: &toml::Table) -> Result<(), EgitError> {
+    config::write_table(table).context("Failed to update the config")
 }
 
+// The asset pattern configured for a repository, if any
+fn config_asset_pattern(config: &Config, key: &str) -> Result<Option<String>, EgitError> {
+    config.assets.get(key)
+        .map(|pattern| select::parse_asset_pattern(pattern))
+        .transpose()
+        .map_err(|e| EgitError::Other(format!("Invalid asset pattern for `{}` in the config: {}", key, e)))
+}
@@ -0,0 +1,86 @@
+//! `egit.toml`: the packages a project wants, for `egit sync`
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Packages a project depends on, read from `egit.toml` by `egit sync`:
+///
+/// ```toml
+/// dir = "tools"
+///
+/// [packages]
+/// "BurntSushi/ripgrep" = "^14"
+/// "sharkdp/fd" = { version = "^10", asset = "*musl*", extract = true }
+/// "cli/cli" = { install = true }
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Directory everything is downloaded to, relative to the manifest
+    #[serde(default = "default_dir")]
+    pub dir: PathBuf,
+    /// Version requirement or options of each package, by `owner/repo`
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageSpec>,
+}
+
+fn default_dir() -> PathBuf {
+    PathBuf::from(".")
+}
+
+/// Either just a version requirement or the full set of options
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum PackageSpec {
+    /// `"owner/repo" = "^1.2"`
+    Version(String),
+    /// `"owner/repo" = { version = "^1.2", ... }`
+    Detailed(PackageOptions),
+}
+
+/// The options of a package
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PackageOptions {
+    /// A tag, `latest` or a semver requirement [default: latest]
+    pub version: Option<String>,
+    /// Glob, `/regex/` or substring picking the asset [default: best match for this platform]
+    pub asset: Option<String>,
+    /// Unpack the downloaded archive next to it
+    #[serde(default)]
+    pub extract: bool,
+    /// Keep only the executables, as `egit install` does
+    #[serde(default)]
+    pub install: bool,
+    /// Allow prereleases
+    #[serde(default)]
+    pub pre: bool,
+}
+
+impl PackageSpec {
+    /// The options, with only the version set for the short form
+    pub fn options(&self) -> PackageOptions {
+        match self {
+            PackageSpec::Version(version) => PackageOptions {
+                version: Some(version.clone()),
+                ..PackageOptions::default()
+            },
+            PackageSpec::Detailed(options) => options.clone(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Read a manifest, resolving `dir` against the manifest's directory
+    pub fn load(path: &Path) -> io::Result<Manifest> {
+        let contents = fs::read_to_string(path)?;
+        let mut manifest: Manifest = toml::from_str(&contents).map_err(io::Error::other)?;
+        if let Some(parent) = path.parent() {
+            manifest.dir = parent.join(&manifest.dir);
+        }
+        Ok(manifest)
+    }
+}
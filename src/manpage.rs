@@ -0,0 +1,147 @@
+use clap::{Arg, Command};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Write a man page for `command` and one for every subcommand, nested ones
+// included, into `dir`: `egit.1`, `egit-download.1`, `egit-alias-add.1`...
+// Returns the files written.
+pub fn write_all(mut command: Command, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    // Propagates global options and usage strings to the subcommands
+    command.build();
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    write_page(&mut command, "egit", None, dir, &mut written)?;
+    Ok(written)
+}
+
+fn write_page(command: &mut Command, name: &str, parent: Option<&str>, dir: &Path, written: &mut Vec<PathBuf>) -> io::Result<()> {
+    let path = dir.join(format!("{}.1", name));
+    fs::write(&path, render(command, name, parent))?;
+    written.push(path);
+    for sub in command.get_subcommands_mut().filter(|sub| !sub.is_hide_set() && sub.get_name() != "help") {
+        let sub_name = format!("{}-{}", name, sub.get_name());
+        write_page(sub, &sub_name, Some(name), dir, written)?;
+    }
+    Ok(())
+}
+
+// The page in roff, as read by `man`
+fn render(command: &mut Command, name: &str, parent: Option<&str>) -> String {
+    let version = command.get_version().unwrap_or(env!("CARGO_PKG_VERSION")).to_string();
+    let mut page = format!(".TH {} 1 \"\" \"egit {}\" \"egit Manual\"\n", escape(&name.to_uppercase()), version);
+
+    page.push_str(".SH NAME\n");
+    let about = command.get_about().map(ToString::to_string).unwrap_or_default();
+    page.push_str(&format!("{} \\- {}\n", escape(name), escape(&about)));
+
+    page.push_str(".SH SYNOPSIS\n");
+    let usage = command.render_usage().to_string();
+    page.push_str(&format!("{}\n", escape(usage.trim_start_matches("Usage:").trim())));
+
+    if let Some(long_about) = command.get_long_about() {
+        page.push_str(".SH DESCRIPTION\n");
+        page.push_str(&paragraphs(&long_about.to_string()));
+    }
+
+    let (positionals, options): (Vec<&Arg>, Vec<&Arg>) = command.get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .partition(|arg| arg.is_positional());
+    if !positionals.is_empty() {
+        page.push_str(".SH ARGUMENTS\n");
+        for arg in positionals {
+            page.push_str(&format!(".TP\n\\fI{}\\fR\n", escape(&value_name(arg))));
+            page.push_str(&paragraphs(&help(arg)));
+        }
+    }
+    if !options.is_empty() {
+        page.push_str(".SH OPTIONS\n");
+        for arg in options {
+            page.push_str(&format!(".TP\n{}\n", flags(arg)));
+            page.push_str(&paragraphs(&help(arg)));
+        }
+    }
+
+    let subcommands: Vec<&Command> = command.get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+        .collect();
+    if !subcommands.is_empty() {
+        page.push_str(".SH COMMANDS\n");
+        for sub in &subcommands {
+            page.push_str(&format!(".TP\n\\fB{}\\-{}\\fR(1)\n", escape(name), escape(sub.get_name())));
+            page.push_str(&paragraphs(&sub.get_about().map(ToString::to_string).unwrap_or_default()));
+        }
+    }
+
+    if let Some(parent) = parent {
+        page.push_str(".SH SEE ALSO\n");
+        page.push_str(&format!("\\fB{}\\fR(1)\n", escape(parent)));
+    }
+    page
+}
+
+// `\-s, \-\-long <VALUE>` in bold, with the value in italics
+fn flags(arg: &Arg) -> String {
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("\\fB\\-{}\\fR", escape(&short.to_string())));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+    }
+    let mut flags = names.join(", ");
+    if arg.get_action().takes_values() {
+        flags.push_str(&format!(" \\fI<{}>\\fR", escape(&value_name(arg))));
+    }
+    flags
+}
+
+fn value_name(arg: &Arg) -> String {
+    match arg.get_value_names() {
+        Some(names) => names.iter().map(ToString::to_string).collect::<Vec<_>>().join(" "),
+        None => arg.get_id().to_string().to_uppercase(),
+    }
+}
+
+// The help text of an argument, followed by its possible and default values
+fn help(arg: &Arg) -> String {
+    let mut help = arg.get_long_help().or(arg.get_help()).map(ToString::to_string).unwrap_or_default();
+    let possible: Vec<String> = arg.get_possible_values().iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if !possible.is_empty() {
+        help.push_str(&format!(" [possible values: {}]", possible.join(", ")));
+    }
+    let defaults: Vec<String> = arg.get_default_values().iter().map(|value| value.to_string_lossy().into_owned()).collect();
+    if !defaults.is_empty() && arg.get_action().takes_values() {
+        help.push_str(&format!(" [default: {}]", defaults.join(", ")));
+    }
+    help
+}
+
+// Text with blank lines turned into paragraph breaks
+fn paragraphs(text: &str) -> String {
+    let mut out = String::new();
+    for (i, paragraph) in text.split("\n\n").enumerate() {
+        if i > 0 {
+            out.push_str(".PP\n");
+        }
+        for line in paragraph.lines() {
+            out.push_str(&escape(line));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// Escape roff's special characters; a leading `.` or `'` would be read as
+// a request
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
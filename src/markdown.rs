@@ -0,0 +1,78 @@
+//! Rendering release notes in the terminal
+
+use regex::Regex;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::LazyLock;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const UNDERLINE: &str = "\x1b[4m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+static CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`]+)`").unwrap());
+static STRONG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\*\*([^*]+)\*\*|__([^_]+)__").unwrap());
+static LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\[\]]+)\]\(([^)\s]+)\)").unwrap());
+static COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+
+/// Whether Markdown should be styled: stdout is a terminal and `NO_COLOR`
+/// is not set
+pub fn use_styles() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Render release notes for the terminal: headings and strong text in bold,
+/// code in cyan, links underlined with their URL, and bullets as `•`. Only
+/// the common constructs of release notes are handled; anything else is
+/// printed as written.
+pub fn render(text: &str) -> String {
+    let text = COMMENT.replace_all(text, "");
+    let mut lines = Vec::new();
+    let mut in_code_block = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            lines.push(format!("    {}{}{}", CYAN, line, RESET));
+            continue;
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        if let Some(heading) = heading(trimmed) {
+            lines.push(format!("{}{}{}", BOLD, inline(heading), RESET));
+        } else if let Some(item) = trimmed.strip_prefix(['-', '*', '+']).and_then(|rest| rest.strip_prefix(' ')) {
+            lines.push(format!("{}• {}", indent, inline(item)));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            lines.push(format!("{}│ {}{}", DIM, inline(quote.trim_start()), RESET));
+        } else if is_rule(trimmed) {
+            lines.push(format!("{}{}{}", DIM, "─".repeat(40), RESET));
+        } else {
+            lines.push(format!("{}{}", indent, inline(trimmed)));
+        }
+    }
+    lines.join("\n")
+}
+
+/// The text of an ATX heading such as `## Fixes`
+fn heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    let level = line.len() - text.len();
+    ((1..=6).contains(&level) && (text.is_empty() || text.starts_with(' '))).then(|| text.trim())
+}
+
+/// `---`, `***` or `___` on a line of its own
+fn is_rule(line: &str) -> bool {
+    let line: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    line.len() >= 3 && ['-', '*', '_'].iter().any(|&c| line.chars().all(|l| l == c))
+}
+
+/// Links go first, as the escape codes added for the rest contain `[`
+fn inline(text: &str) -> String {
+    let text = LINK.replace_all(text, format!("{}$1{} {}($2){}", UNDERLINE, RESET, DIM, RESET));
+    let text = CODE.replace_all(&text, format!("{}$1{}", CYAN, RESET));
+    STRONG.replace_all(&text, format!("{}$1$2{}", BOLD, RESET)).into_owned()
+}
@@ -1,84 +1,413 @@
-use std::fs::File;
-use std::io::{self, Read, Write};
-use std::thread;
-use reqwest::blocking::Client;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+//! Parallel downloads (`--multithread`). This is the only part of egit that
+//! is async: API calls, single-stream downloads and everything else use the
+//! blocking client, while the range requests here run as tasks on a tokio
+//! runtime so that many of them share a few threads. Timeouts and the retry
+//! policy are the same on both sides (see `client` and `retry`).
 
-// Parallel download function
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use bytes::Bytes;
+use tokio::runtime::{self, Runtime};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use reqwest::header::{ACCEPT, ACCEPT_RANGES, CONTENT_RANGE};
+use reqwest::{StatusCode, Url};
+use indicatif::{ProgressBar, ProgressStyle};
+use crate::checksum::{Digests, Hasher};
+use crate::client::{self, Client, ClientOptions};
+use crate::download::DownloadOptions;
+use crate::progress;
+use crate::retry;
+use crate::sparkline::Sparkline;
+use crate::storage::Storage;
+use crate::throttle;
+
+/// One runtime for the whole process, shared by concurrent `sync` jobs
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start the async runtime"))
+}
+
+/// Bounds for the size of the chunks in the queue. Many small chunks let fast
+/// connections take over the work of slow ones, while the lower bound keeps
+/// the overhead of a request per chunk small.
+const MIN_CHUNK_SIZE: u64 = 256 * 1024;
+const MAX_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+const CHUNKS_PER_THREAD: u64 = 8;
+
+/// How much data that arrived ahead of the bytes before it is held back for
+/// hashing, before giving up on hashing in order
+const MAX_HASH_BACKLOG: usize = 64 * 1024 * 1024;
+
+/// Parallel download function. The file is split into a queue of small
+/// chunks that `options.threads` workers take from until it is empty, each
+/// worker a task on the async runtime. The first failure cancels the other
+/// workers. Returns the digests of the file, or `None` when the chunks
+/// arrived too far out of order to hash them on the way.
 pub fn download_parallel(
     client: &Client,
+    storage: &dyn Storage,
     url: &str,
     filename: &str,
     total_size: u64,
-    num_threads: usize,
-) -> io::Result<()>
+    options: &DownloadOptions,
+) -> io::Result<Option<Digests>>
 {
+    // Without a known size there is nothing to split
+    if total_size == 0 {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "the file size is unknown"));
+    }
+    
+    // The token is added to requests for the original host only, so it never
+    // reaches a CDN the download redirects to
+    let client_options = client.options();
+    let async_client = client::build_async_client(&ClientOptions { token: None, ..client_options.clone() })
+        .map_err(io::Error::other)?;
+    let (url, token) = runtime().block_on(resolve(&async_client, url, client_options.token.as_deref(), options.retries))?;
+    
     // Create multi-progress instance to manage multiple progress bars
-    let mp = MultiProgress::new();
+    let mp = progress::multi();
     
-    // Calculate chunk size
-    let chunk_size = (total_size + num_threads as u64 - 1) / num_threads as u64;
+    // Calculate chunk size, dropping workers that would find the queue empty
+    let num_threads = options.threads.max(1) as u64;
+    let chunk_size = (total_size / (num_threads * CHUNKS_PER_THREAD)).clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE);
+    let num_threads = num_threads.min(total_size.div_ceil(chunk_size)) as usize;
     
-    // Create threads and download chunks
-    let mut handles = vec![];
+    // Pre-size the output so every chunk can be written in place instead of
+    // buffering the whole file in memory
+    storage.preallocate(filename, total_size)?;
+    
+    // Aggregate bar on top tracks the whole file and the combined speed, and
+    // with --sparkline charts the combined rate of all workers. Reported under
+    // the name the file will have once complete.
+    let total_pb = progress::track(filename.strip_suffix(".tmp").unwrap_or(filename), mp.add(ProgressBar::new(total_size)));
+    let mut template = "Total:    {spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})".to_string();
+    if options.sparkline {
+        template.push_str(" {msg:.cyan}");
+    }
+    let mut sparkline = options.sparkline.then(|| Sparkline::new(0));
+    total_pb.set_style(ProgressStyle::with_template(&template)
+        .unwrap()
+        .progress_chars("█▉▊▋▌▍▎▏ "));
+    
+    // Workers send what they receive to this thread, which owns the storage
+    // and writes it in place
+    let (sender, mut pieces) = mpsc::channel(num_threads * 4);
+    let next = Arc::new(AtomicU64::new(0));
+    let mut tasks = JoinSet::new();
     
     for i in 0..num_threads {
-        let client = client.clone();
-        let url = url.to_string();
-        let start = i as u64 * chunk_size;
-        let end = std::cmp::min(start + chunk_size - 1, total_size - 1);
-        let chunk_length = end - start + 1;
-        
-        // Create individual progress bar for each thread
-        let pb = mp.add(ProgressBar::new(chunk_length));
-        let template = format!("Thread {}: {{spinner:.green}} [{{elapsed_precise}}] [{{bar:40.cyan/blue}}] {{bytes}}/{{total_bytes}} ({{bytes_per_sec}}, {{eta}})", i+1);
-        pb.set_style(ProgressStyle::with_template(&template)
-            .unwrap()
-            .progress_chars("█▉▊▋▌▍▎▏ "));
-        pb.set_message(format!("Downloading chunk {}-{}", start, end));
+        // Each worker's bar counts the bytes it fetched, across chunks.
+        // With --progress single only the aggregate bar is drawn.
+        let pb = if progress::mode() == progress::Mode::PerThread {
+            let pb = mp.add(ProgressBar::no_length());
+            let template = format!("Thread {}: {{spinner:.green}} [{{elapsed_precise}}] {{bytes}} ({{bytes_per_sec}}) {{msg}}", i+1);
+            pb.set_style(ProgressStyle::with_template(&template).unwrap());
+            pb
+        } else {
+            ProgressBar::hidden()
+        };
         
-        handles.push(thread::spawn(move || {
-            let mut chunk = Vec::new();
-            let range_header = format!("bytes={}-{}", start, end);
-            
-            let mut response = client.get(&url)
-                .header("User-Agent", "egit-cli")
-                .header("Range", range_header)
-                .send()
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-            
-            // Read response in chunks to update progress
-            let mut buffer = [0; 8192];
-            loop {
-                match response.read(&mut buffer) {
-                    Ok(0) => break, // End of file
-                    Ok(n) => {
-                        chunk.extend_from_slice(&buffer[..n]);
-                        pb.inc(n as u64);
-                    },
-                    Err(e) => {
-                        return Err(io::Error::new(io::ErrorKind::Other, e));
-                    }
+        let worker = Worker {
+            client: async_client.clone(),
+            url: url.clone(),
+            token: token.clone(),
+            index: i,
+            next: next.clone(),
+            chunk_size,
+            total_size,
+            pieces: sender.clone(),
+            pb,
+            total_pb: total_pb.clone(),
+            retries: options.retries,
+        };
+        tasks.spawn_on(worker.run(), runtime().handle());
+    }
+    drop(sender);
+    
+    // Stop at the first failure, aborting the other workers and waiting for
+    // them to wind down so none is still running when the caller falls back
+    // or cleans up
+    let mut hasher = OrderedHasher::new(Hasher::new(&options.hashes));
+    runtime().block_on(async {
+        let mut writers: Vec<Option<(u64, Box<dyn Write + Send>)>> = (0..num_threads).map(|_| None).collect();
+        while let Some(piece) = pieces.recv().await {
+            let written = piece.and_then(|piece| {
+                piece.write(storage, filename, &mut writers[piece.worker])?;
+                hasher.add(piece.offset, piece.bytes);
+                if let Some(rendered) = sparkline.as_mut().and_then(|s| s.sample(total_pb.position())) {
+                    total_pb.set_message(rendered);
                 }
+                Ok(())
+            });
+            if let Err(e) = written {
+                tasks.shutdown().await;
+                return Err(e);
             }
-            
-            pb.finish_with_message(format!("Chunk {}-{} completed", start, end));
-            Ok(chunk)
-        }));
+        }
+        // Every worker is done once the channel closes, but may have panicked
+        while let Some(result) = tasks.join_next().await {
+            result.map_err(io::Error::other)?;
+        }
+        for (_, mut file) in writers.into_iter().flatten() {
+            file.flush()?;
+        }
+        Ok(())
+    })?;
+    progress::finish(&total_pb, "Download completed");
+    
+    let written = storage.size(filename)?;
+    if written != total_size {
+        return Err(io::Error::other(format!(
+            "downloaded file is {} bytes, expected {}", written, total_size)));
+    }
+    
+    Ok(hasher.finish(total_size))
+}
+
+/// Hashes the pieces of a parallel download in file order. Pieces arriving
+/// ahead of the bytes before them wait in memory, up to MAX_HASH_BACKLOG;
+/// past that the hash is dropped and the file hashed once complete instead.
+struct OrderedHasher {
+    hasher: Option<Hasher>,
+    // Offset of the first byte not hashed yet
+    next: u64,
+    backlog: BTreeMap<u64, Bytes>,
+    backlog_size: usize,
+}
+
+impl OrderedHasher {
+    fn new(hasher: Hasher) -> Self {
+        OrderedHasher { hasher: Some(hasher), next: 0, backlog: BTreeMap::new(), backlog_size: 0 }
     }
     
-    // Wait for all threads to complete and collect chunks
-    let mut results = vec![];
-    for handle in handles {
-        let result = handle.join().unwrap()?;
-        results.push(result);
+    fn add(&mut self, offset: u64, bytes: Bytes) {
+        let Some(hasher) = &mut self.hasher else {
+            return;
+        };
+        if offset != self.next {
+            self.backlog_size += bytes.len();
+            self.backlog.insert(offset, bytes);
+            if self.backlog_size > MAX_HASH_BACKLOG {
+                log::debug!("Chunks arrived too far out of order, hashing the file once complete");
+                self.hasher = None;
+                self.backlog.clear();
+            }
+            return;
+        }
+        hasher.update(&bytes);
+        self.next += bytes.len() as u64;
+        while let Some(bytes) = self.backlog.remove(&self.next) {
+            self.backlog_size -= bytes.len();
+            hasher.update(&bytes);
+            self.next += bytes.len() as u64;
+        }
     }
     
-    // Write all chunks to file in order
-    let mut file = File::create(filename)?;
-    for chunk in results {
-        file.write_all(&chunk)?;
+    fn finish(self, total_size: u64) -> Option<Digests> {
+        self.hasher.filter(|_| self.next == total_size).map(Hasher::finish)
+    }
+}
+
+/// Follow the redirects of `url` once with a HEAD request and check that the
+/// server at the end honors byte ranges. The workers then go straight to the
+/// final URL, over the connection this request left in the pool. Returns the
+/// final URL and the token to send there, if it is still on the same host.
+async fn resolve(client: &reqwest::Client, url: &str, token: Option<&str>, retries: u32) -> io::Result<(Url, Option<String>)> {
+    let unsupported = |message: String| io::Error::new(io::ErrorKind::Unsupported, message);
+    let request = || {
+        let request = client.head(url)
+            .header("User-Agent", "egit-cli")
+            .header(ACCEPT, "application/octet-stream");
+        match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    };
+    let response = retry::send_with_retry_async(retries, request).await
+        .map_err(|e| unsupported(format!("failed to check for range support: {}", e)))?;
+    let supported = response.status().is_success() && response.headers()
+        .get(ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("bytes"));
+    if !supported {
+        return Err(unsupported("server does not support range requests".to_string()));
     }
     
-    Ok(())
+    let resolved = response.url().clone();
+    if resolved.as_str() != url {
+        log::debug!("Resolved {} to {}", url, resolved);
+    }
+    let same_host = Url::parse(url).is_ok_and(|url| url.host_str() == resolved.host_str());
+    let token = token.filter(|_| same_host).map(str::to_string);
+    Ok((resolved, token))
+}
+
+/// Bytes a worker received, starting at `offset` in the file
+struct Piece {
+    worker: usize,
+    offset: u64,
+    bytes: Bytes,
+}
+
+impl Piece {
+    /// Write through the worker's current handle, opening a new one when the
+    /// worker moved on to another chunk
+    fn write(&self, storage: &dyn Storage, filename: &str, writer: &mut Option<(u64, Box<dyn Write + Send>)>) -> io::Result<()> {
+        let (position, file) = match writer {
+            Some((position, file)) if *position == self.offset => (position, file),
+            _ => {
+                if let Some((_, file)) = writer {
+                    file.flush()?;
+                }
+                let (position, file) = writer.insert((self.offset, storage.open_at(filename, self.offset)?));
+                (position, file)
+            },
+        };
+        file.write_all(&self.bytes)?;
+        *position += self.bytes.len() as u64;
+        Ok(())
+    }
+}
+
+/// Takes chunks from the shared queue until it is empty
+struct Worker {
+    client: reqwest::Client,
+    // The download URL after redirects
+    url: Url,
+    token: Option<String>,
+    index: usize,
+    // Start of the next chunk nobody has taken yet
+    next: Arc<AtomicU64>,
+    chunk_size: u64,
+    total_size: u64,
+    pieces: mpsc::Sender<io::Result<Piece>>,
+    pb: ProgressBar,
+    total_pb: ProgressBar,
+    retries: u32,
+}
+
+impl Worker {
+    async fn run(self) {
+        if let Err(e) = self.download_chunks().await {
+            let _ = self.pieces.send(Err(e)).await;
+        }
+    }
+    
+    async fn download_chunks(&self) -> io::Result<()> {
+        loop {
+            let start = self.next.fetch_add(self.chunk_size, Ordering::Relaxed);
+            if start >= self.total_size {
+                break;
+            }
+            let end = std::cmp::min(start + self.chunk_size, self.total_size) - 1;
+            self.pb.set_message(format!("chunk {}-{}", start, end));
+            self.download_chunk(start, end).await?;
+        }
+        // Only the aggregate bar remains once the queue is empty
+        self.pb.finish_and_clear();
+        Ok(())
+    }
+    
+    /// Fetch the byte range `start..=end`. A failed attempt is retried with
+    /// backoff from the first byte not yet received, so the chunks other
+    /// workers finished are kept.
+    async fn download_chunk(&self, start: u64, end: u64) -> io::Result<()> {
+        let mut received = 0;
+        let mut attempt = 0;
+        loop {
+            let e = match self.fetch_range(start + received, end, &mut received).await {
+                Ok(()) => return Ok(()),
+                Err(e) if !is_retryable(&e) => return Err(e),
+                Err(e) if attempt >= self.retries => {
+                    return Err(io::Error::new(e.kind(), format!(
+                        "failed to fetch bytes {}-{} after {} attempts: {}", start, end, attempt + 1, e)));
+                },
+                Err(e) => e,
+            };
+            let delay = retry::backoff(attempt);
+            attempt += 1;
+            status!("- Bytes {}-{} failed ({}), retrying in {:.1}s (attempt {}/{})...",
+                     start, end, e, delay.as_secs_f64(), attempt, self.retries);
+            tokio::time::sleep(delay).await;
+        }
+    }
+    
+    /// One attempt at the byte range `from..=end`, counting the bytes passed
+    /// on to the writer in `received`
+    async fn fetch_range(&self, from: u64, end: u64, received: &mut u64) -> io::Result<()> {
+        let mut request = self.client.get(self.url.clone())
+            .header("User-Agent", "egit-cli")
+            .header(ACCEPT, "application/octet-stream")
+            .header("Range", format!("bytes={}-{}", from, end));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let mut response = client::send_async(request).await.map_err(|e| {
+            if retry::is_transient(&e) { network_error(e) } else { io::Error::other(e) }
+        })?;
+        if response.status().is_server_error() {
+            return Err(io::Error::new(io::ErrorKind::ConnectionAborted, format!("server returned {}", response.status())));
+        }
+        
+        // A server that ignores the Range header sends the whole file with
+        // 200 OK; trusting it would concatenate N full copies
+        let expected_range = format!("bytes {}-{}/", from, end);
+        let honored = response.status() == StatusCode::PARTIAL_CONTENT && response.headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with(&expected_range));
+        if !honored {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, format!(
+                "server ignored range request for bytes {}-{} (status {})",
+                from, end, response.status())));
+        }
+        
+        // Hand the response over as it arrives, updating progress
+        let length = end - from + 1;
+        let mut written = 0;
+        while let Some(bytes) = response.chunk().await.map_err(network_error)? {
+            let n = bytes.len() as u64;
+            // Never write past the end of this chunk's range
+            if written + n > length {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "server sent more than the requested bytes {}-{}", from, end)));
+            }
+            let piece = Piece { worker: self.index, offset: from + written, bytes };
+            if self.pieces.send(Ok(piece)).await.is_err() {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "download cancelled"));
+            }
+            written += n;
+            *received += n;
+            self.pb.inc(n);
+            self.total_pb.inc(n);
+            progress::inc(n);
+            tokio::time::sleep(throttle::reserve(n as usize)).await;
+        }
+        
+        if written != length {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!(
+                "connection closed after {} of {} bytes", written, length)));
+        }
+        Ok(())
+    }
+}
+
+/// A failure that another attempt at the same range may not run into again
+fn network_error(e: reqwest::Error) -> io::Error {
+    let kind = if e.is_timeout() { io::ErrorKind::TimedOut } else { io::ErrorKind::ConnectionAborted };
+    io::Error::new(kind, e)
+}
+
+/// Whether a chunk should be retried after failing with `e`. A server that
+/// doesn't honor ranges or sends too much won't do better next time.
+fn is_retryable(e: &io::Error) -> bool {
+    matches!(e.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::ConnectionAborted | io::ErrorKind::UnexpectedEof)
 }
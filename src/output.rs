@@ -0,0 +1,225 @@
+//! Status lines, machine readable reports and the verbosity and format
+//! settings behind them
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use crate::checksum::Digests;
+use crate::error::EgitError;
+
+/// How results are printed
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Human-readable lines
+    Text,
+    /// JSON on stdout, for scripts
+    Json,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+static REPORT: Mutex<Option<Report>> = Mutex::new(None);
+
+/// Switch between text and JSON output (`--format`)
+pub fn set_format(format: Format) {
+    JSON.store(format == Format::Json, Ordering::Relaxed);
+}
+
+/// Whether `--format json` is set
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Keep stdout free for downloaded bytes (`--stdout`)
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+/// Whether status lines must stay off stdout
+pub fn status_to_stderr() -> bool {
+    is_json() || STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// Print a human-readable status line, hidden by --quiet
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        log::info!($($arg)*)
+    };
+}
+
+/// Print part of a command's result, such as a listing. In JSON mode or with
+/// --stdout, stdout is reserved for the result proper, so these lines go to
+/// stderr instead.
+#[macro_export]
+macro_rules! output {
+    ($($arg:tt)*) => {
+        if $crate::output::status_to_stderr() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints status lines where command output goes, and the `-v` request log
+/// and `-vv` response headers on stderr
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // Dependencies such as reqwest log their internals too
+        metadata.level() <= log::max_level() && metadata.target().starts_with("egit")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            log::Level::Error | log::Level::Warn | log::Level::Info => {
+                crate::progress::suspend(|| output!("{}", record.args()));
+            },
+            log::Level::Debug | log::Level::Trace => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Show only warnings and errors with --quiet, or requests (`-v`) and response headers
+/// (`-vv`) on top of the status lines
+pub fn set_verbosity(quiet: bool, verbose: u8) {
+    let level = match (quiet, verbose) {
+        (true, _) => log::LevelFilter::Warn,
+        (false, 0) => log::LevelFilter::Info,
+        (false, 1) => log::LevelFilter::Debug,
+        (false, _) => log::LevelFilter::Trace,
+    };
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
+
+/// Whether status lines and progress bars are hidden
+pub fn is_quiet() -> bool {
+    log::max_level() < log::LevelFilter::Info
+}
+
+/// Result of a download, printed as a single JSON object in JSON mode
+#[derive(Serialize, Default, Debug)]
+pub struct Report {
+    /// Whether the download succeeded
+    pub success: bool,
+    /// The package as given on the command line
+    pub package: String,
+    /// The release tag, once resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Where the download was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    /// Bytes downloaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+    /// How long the download took
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_secs: Option<f64>,
+    /// With `--compare-local`, whether the local file matches the asset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identical: Option<bool>,
+    /// Whether the asset was copied from the download cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached: Option<bool>,
+    /// The error, if it failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Computed while downloading
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digests: Option<Digests>,
+    /// What --dry-run would have downloaded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub planned: Vec<Planned>,
+}
+
+/// A download skipped by --dry-run
+#[derive(Serialize, Debug)]
+pub struct Planned {
+    /// The URL it would be downloaded from
+    pub url: String,
+    /// Size in bytes, if known in advance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Where it would be saved
+    pub destination: String,
+}
+
+/// Start collecting a report for `package`
+pub fn begin_report(package: &str) {
+    *REPORT.lock().unwrap() = Some(Report {
+        package: package.to_string(),
+        ..Report::default()
+    });
+}
+
+/// Fill in fields of the current report, if one was started
+pub fn record(update: impl FnOnce(&mut Report)) {
+    if let Some(report) = REPORT.lock().unwrap().as_mut() {
+        update(report);
+    }
+}
+
+/// Print a value as JSON on stdout
+pub fn print_json<T: Serialize + ?Sized>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("- Failed to serialize output: {}", e),
+    }
+}
+
+/// Print the result of a command without a report, in JSON mode only
+pub fn print_result(result: serde_json::Value) {
+    if is_json() {
+        print_json(&result);
+    }
+}
+
+/// Mark the end of the task, printing the report in JSON mode
+pub fn task_end() {
+    status!("=== Task End ===");
+    if is_json()
+        && let Some(report) = REPORT.lock().unwrap().take() {
+        print_json(&report);
+    }
+}
+
+/// Exit code of `download --compare-local` when the files differ, apart from
+/// the codes of the errors
+pub const EXIT_DIFFERS: i32 = 10;
+
+/// End the task and exit with `code`
+pub fn finish(code: i32) -> ! {
+    task_end();
+    process::exit(code);
+}
+
+/// Report an error and exit with the code for its kind
+pub fn fail(error: &EgitError) -> ! {
+    let message = error.to_string();
+    log::error!("- {}", message);
+    if is_json() {
+        let mut report = REPORT.lock().unwrap();
+        match report.as_mut() {
+            Some(report) => {
+                report.success = false;
+                report.error = Some(message);
+            },
+            // Commands without a report still get a machine-readable error
+            None => print_json(&serde_json::json!({ "success": false, "error": message })),
+        }
+    }
+    finish(error.exit_code());
+}
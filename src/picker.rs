@@ -0,0 +1,45 @@
+//! Interactive pickers for assets and repositories
+
+use crate::assets::GitHubAsset;
+use dialoguer::Select;
+use indicatif::HumanBytes;
+use std::io::{self, IsTerminal};
+
+/// Whether there is a terminal to show a menu on
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Show a menu of assets with their size and content type, starting on
+/// `default`. Returns `None` when the user cancels with Esc or q.
+pub fn pick_asset<'a>(prompt: &str, assets: &[&'a GitHubAsset], default: usize) -> Option<&'a GitHubAsset> {
+    let items: Vec<String> = assets
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            let content_type = a.content_type.as_deref().unwrap_or("unknown type");
+            format!("{:>2}) {}  ({}, {})", i + 1, a.name, HumanBytes(a.size), content_type)
+        })
+        .collect();
+
+    Select::new()
+        .with_prompt(prompt)
+        .items(&items)
+        .default(default)
+        .interact_opt()
+        .ok()
+        .flatten()
+        .map(|i| assets[i])
+}
+
+/// Show a menu of repository names. Returns `None` when the user cancels.
+pub fn pick_repository<'a>(prompt: &str, names: &'a [String]) -> Option<&'a String> {
+    Select::new()
+        .with_prompt(prompt)
+        .items(names)
+        .default(0)
+        .interact_opt()
+        .ok()
+        .flatten()
+        .map(|i| &names[i])
+}
@@ -0,0 +1,240 @@
+//! Progress bars and progress events, and how they are shown
+
+use clap::ValueEnum;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde::Serialize;
+use std::io::{self, IsTerminal};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::Duration;
+use crate::output;
+
+/// How download progress is shown (`--progress`)
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Mode {
+    /// One bar for the whole file
+    Single,
+    /// A bar per thread of a parallel download below the one for the file
+    PerThread,
+    /// No bars, only the status lines
+    None,
+    /// Newline-delimited JSON events on stderr instead of bars
+    Json,
+}
+
+static MODE: Mutex<Mode> = Mutex::new(Mode::PerThread);
+
+/// Choose how progress is shown (`--progress`)
+pub fn set_mode(mode: Mode) {
+    *MODE.lock().unwrap() = mode;
+}
+
+/// How progress is shown
+pub fn mode() -> Mode {
+    *MODE.lock().unwrap()
+}
+
+/// Whether progress bars are hidden, by --progress none or json, or --quiet
+pub fn is_hidden() -> bool {
+    matches!(mode(), Mode::None | Mode::Json) || output::is_quiet()
+}
+
+/// One line of --progress json. Phases without a byte count, such as
+/// `verify` and `extract`, only name the file.
+#[derive(Serialize)]
+struct Event<'a> {
+    phase: &'a str,
+    file: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<u64>,
+    // Bytes per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed: Option<u64>,
+    // Seconds left, when the total is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    eta: Option<f64>,
+}
+
+impl Event<'_> {
+    fn emit(&self) {
+        if let Ok(line) = serde_json::to_string(self) {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+/// How often the bars are sampled for --progress json
+const JSON_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The hidden bars of running downloads, reported by --progress json
+static TRACKED: Mutex<Vec<(String, ProgressBar)>> = Mutex::new(Vec::new());
+static TICKER: Once = Once::new();
+
+/// With --progress json, report `pb`, the progress of downloading `file`,
+/// until it finishes. A `download` event is printed for it every 250ms, and
+/// a `complete` one at the end.
+pub fn track(file: &str, pb: ProgressBar) -> ProgressBar {
+    if mode() != Mode::Json {
+        return pb;
+    }
+    TICKER.call_once(|| {
+        thread::spawn(|| loop {
+            thread::sleep(JSON_INTERVAL);
+            report_tracked();
+        });
+    });
+    TRACKED.lock().unwrap().push((file.to_string(), pb.clone()));
+    pb
+}
+
+/// Print an event for each tracked bar, dropping the finished ones
+fn report_tracked() {
+    TRACKED.lock().unwrap().retain(|(file, pb)| {
+        let finished = pb.is_finished();
+        let total = pb.length().filter(|&total| total > 0);
+        Event {
+            phase: if finished { "complete" } else { "download" },
+            file,
+            bytes: Some(pb.position()),
+            total,
+            speed: Some(pb.per_sec() as u64),
+            eta: total.filter(|_| !finished).map(|_| (pb.eta().as_secs_f64() * 10.0).round() / 10.0),
+        }.emit();
+        !finished
+    });
+}
+
+/// With --progress json, announce that `file` entered `phase`
+pub fn phase(phase: &str, file: &str) {
+    if mode() == Mode::Json {
+        Event { phase, file, bytes: None, total: None, speed: None, eta: None }.emit();
+    }
+}
+
+/// Overall progress of a command that downloads several files (`sync`,
+/// `--all-assets`, `upgrade`), drawn above the bars of the single files
+struct Batch {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    files: usize,
+    done: usize,
+    // The byte total was known up front, so starting a file doesn't grow it
+    sized: bool,
+    // Off a terminal only the overall bar is kept, not one per file
+    terminal: bool,
+}
+
+static BATCH: Mutex<Option<Batch>> = Mutex::new(None);
+
+/// Ends the batch when dropped, also when the command fails midway
+pub struct BatchGuard;
+
+impl Drop for BatchGuard {
+    fn drop(&mut self) {
+        if let Some(batch) = BATCH.lock().unwrap().take() {
+            batch.overall.finish();
+        }
+    }
+}
+
+/// Start an overall bar for `files` downloads of `total` bytes. A single file
+/// only gets its own bar. Without a `total` the byte count grows as each file
+/// starts, as `sync` and `upgrade` only learn the sizes once the releases are
+/// resolved. When stderr isn't a terminal the bars of the single files are
+/// left out and only the overall one is kept.
+pub fn begin_batch(files: usize, total: Option<u64>) -> BatchGuard {
+    if files > 1 && !is_hidden() {
+        let multi = MultiProgress::new();
+        let overall = multi.add(ProgressBar::new(total.unwrap_or(0)));
+        overall.set_style(ProgressStyle::with_template("Overall:  {spinner:.green} [{elapsed_precise}] [{bar:40.green/blue}] {msg} {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+            .unwrap()
+            .progress_chars("█▉▊▋▌▍▎▏ "));
+        overall.set_message(format!("0/{} files", files));
+        *BATCH.lock().unwrap() = Some(Batch { multi, overall, files, done: 0, sized: total.is_some(), terminal: io::stderr().is_terminal() });
+    }
+    BatchGuard
+}
+
+/// Draw `pb` below the overall bar while a batch runs
+pub fn add(pb: ProgressBar) -> ProgressBar {
+    match BATCH.lock().unwrap().as_ref() {
+        Some(batch) if batch.terminal => batch.multi.add(pb),
+        Some(_) => {
+            pb.set_draw_target(ProgressDrawTarget::hidden());
+            pb
+        },
+        None => pb,
+    }
+}
+
+/// The bars of a parallel download go below the overall bar while a batch
+/// runs, or in a set of their own
+pub fn multi() -> MultiProgress {
+    let terminal = match BATCH.lock().unwrap().as_ref() {
+        Some(batch) if batch.terminal => return batch.multi.clone(),
+        Some(_) => false,
+        None => true,
+    };
+    if is_hidden() || !terminal {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+        MultiProgress::new()
+    }
+}
+
+/// A file of `size` bytes started downloading
+pub fn file_started(size: u64) {
+    if let Some(batch) = BATCH.lock().unwrap().as_ref()
+        && !batch.sized {
+        batch.overall.inc_length(size);
+    }
+}
+
+/// `n` bytes of the current files arrived
+pub fn inc(n: u64) {
+    if let Some(batch) = BATCH.lock().unwrap().as_ref() {
+        batch.overall.inc(n);
+    }
+}
+
+/// Count a file of the current batch as done
+pub fn file_done() {
+    if let Some(batch) = BATCH.lock().unwrap().as_mut() {
+        batch.done += 1;
+        batch.overall.set_message(format!("{}/{} files", batch.done, batch.files));
+    }
+}
+
+/// Finish the bar of one file. In a batch it is removed, so that only the
+/// files still downloading are shown.
+pub fn finish(pb: &ProgressBar, message: &'static str) {
+    if BATCH.lock().unwrap().is_some() {
+        pb.finish_and_clear();
+    } else {
+        pb.finish_with_message(message);
+    }
+    // Without waiting for the next sample, which may never come
+    if mode() == Mode::Json {
+        report_tracked();
+    }
+}
+
+/// Finish a bar that isn't kept on screen
+pub fn clear(pb: &ProgressBar) {
+    pb.finish_and_clear();
+    if mode() == Mode::Json {
+        report_tracked();
+    }
+}
+
+/// Run `f`, which prints a line, with the bars of a batch hidden so the line
+/// doesn't end up in the middle of them
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    let multi = BATCH.lock().unwrap().as_ref().map(|batch| batch.multi.clone());
+    match multi {
+        Some(multi) => multi.suspend(f),
+        None => f(),
+    }
+}
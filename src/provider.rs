@@ -0,0 +1,158 @@
+//! Release providers (GitHub, GitLab, Gitea and Forgejo) and choosing one
+//! for a package
+
+use std::collections::BTreeMap;
+use crate::assets::{self, ApiError, GitHubRelease, GitHubRepo, GitHubTag};
+use crate::auth;
+use crate::client::{self, Client, ClientOptions};
+use crate::gitea::Gitea;
+use crate::gitlab::GitLab;
+
+/// A forge that hosts releases. Every provider maps its API onto the GitHub
+/// types, so listing, selecting and downloading assets work the same for all.
+pub trait ReleaseProvider {
+    /// Client for this provider's API and downloads. Each provider has its own
+    /// so that tokens are never sent to another forge.
+    fn client(&self) -> &Client;
+
+    /// Prefix of the package names using this provider, e.g. `gitlab:`
+    fn prefix(&self) -> &'static str;
+
+    /// The repository, for its default branch
+    fn fetch_repo(&self, owner: &str, repo: &str) -> Result<GitHubRepo, ApiError>;
+
+    /// The newest stable release according to the provider, if there is one
+    fn fetch_latest_release(&self, owner: &str, repo: &str) -> Result<Option<GitHubRelease>, ApiError>;
+
+    /// The published release for `tag`, if there is one
+    fn fetch_release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Option<GitHubRelease>, ApiError>;
+
+    /// Fetch releases, newest first, page by page until `stop` returns true
+    /// for the page just fetched
+    fn fetch_releases_until(&self, owner: &str, repo: &str, stop: &mut dyn FnMut(&[GitHubRelease]) -> bool) -> Result<Vec<GitHubRelease>, ApiError>;
+
+    /// Fetch tags, newest first, stopping once `limit` tags have been fetched
+    fn fetch_tags(&self, owner: &str, repo: &str, limit: Option<usize>) -> Result<Vec<GitHubTag>, ApiError>;
+
+    /// URL of the `tarball` or `zipball` archive of any branch, tag or commit
+    fn archive_url(&self, owner: &str, repo: &str, kind: &str, git_ref: &str) -> String;
+
+    /// Fetch releases, newest first, stopping once `limit` releases have been fetched
+    fn fetch_releases(&self, owner: &str, repo: &str, limit: Option<usize>) -> Result<Vec<GitHubRelease>, ApiError> {
+        let mut releases = self.fetch_releases_until(owner, repo, &mut assets::stop_after(limit))?;
+        releases.truncate(limit.unwrap_or(usize::MAX));
+        Ok(releases)
+    }
+}
+
+/// Releases from GitHub, or the GitHub Enterprise Server of `--api-url`
+pub struct GitHub {
+    client: Client,
+}
+
+impl ReleaseProvider for GitHub {
+    fn client(&self) -> &Client {
+        &self.client
+    }
+
+    fn prefix(&self) -> &'static str {
+        ""
+    }
+
+    fn fetch_repo(&self, owner: &str, repo: &str) -> Result<GitHubRepo, ApiError> {
+        assets::fetch_repo(&self.client, owner, repo)
+    }
+
+    fn fetch_latest_release(&self, owner: &str, repo: &str) -> Result<Option<GitHubRelease>, ApiError> {
+        assets::fetch_latest_release(&self.client, owner, repo)
+    }
+
+    fn fetch_release_by_tag(&self, owner: &str, repo: &str, tag: &str) -> Result<Option<GitHubRelease>, ApiError> {
+        assets::fetch_release_by_tag(&self.client, owner, repo, tag)
+    }
+
+    fn fetch_releases_until(&self, owner: &str, repo: &str, stop: &mut dyn FnMut(&[GitHubRelease]) -> bool) -> Result<Vec<GitHubRelease>, ApiError> {
+        assets::fetch_releases_until(&self.client, owner, repo, stop)
+    }
+
+    fn fetch_tags(&self, owner: &str, repo: &str, limit: Option<usize>) -> Result<Vec<GitHubTag>, ApiError> {
+        assets::fetch_tags(&self.client, owner, repo, limit)
+    }
+
+    fn archive_url(&self, owner: &str, repo: &str, kind: &str, git_ref: &str) -> String {
+        assets::archive_url(owner, repo, kind, git_ref)
+    }
+}
+
+/// Picks the provider of each package from its `provider:` prefix
+pub struct Providers {
+    github: Client,
+    options: ClientOptions,
+    // Base URL of a self-hosted instance (`--host`)
+    host: Option<String>,
+    // Base URL for each provider from the config
+    hosts: BTreeMap<String, String>,
+    // Token for each host from the config
+    tokens: BTreeMap<String, String>,
+}
+
+impl Providers {
+    /// A provider set that uses `github` for GitHub, so that its token
+    /// and settings apply there, and builds clients from `options` for other
+    /// hosts. `host` is `--host`; `hosts` and `tokens` come from the config.
+    pub fn new(github: Client, options: ClientOptions, host: Option<String>, hosts: BTreeMap<String, String>, tokens: BTreeMap<String, String>) -> Self {
+        Providers { github, options, host, hosts, tokens }
+    }
+
+    /// `--host`, then the configured host of `provider`, then `default`
+    fn host<'a>(&'a self, provider: &str, default: &'a str) -> &'a str {
+        self.host.as_deref()
+            .or_else(|| self.hosts.get(provider).map(String::as_str))
+            .unwrap_or(default)
+    }
+
+    /// Split `[provider:]owner/repo[@version]` into its provider and the rest
+    /// of the package name
+    pub fn for_package<'a>(&self, package: &'a str) -> Result<(Box<dyn ReleaseProvider>, &'a str), String> {
+        let (name, rest) = match package.split_once(':') {
+            Some((name, rest)) if !name.contains('/') => (name, rest),
+            _ => return Ok((self.github(), package)),
+        };
+        match name {
+            "github" => Ok((self.github(), rest)),
+            "gitlab" => {
+                let host = self.host("gitlab", "https://gitlab.com");
+                Ok((Box::new(GitLab::new(host, self.client_with_token("GITLAB_TOKEN", host)?)?), rest))
+            },
+            // Forgejo is a Gitea fork with the same API, and runs Codeberg
+            "gitea" | "forgejo" | "codeberg" => {
+                let host = self.host(name, "https://codeberg.org");
+                let prefix = match name {
+                    "gitea" => "gitea:",
+                    "forgejo" => "forgejo:",
+                    _ => "codeberg:",
+                };
+                Ok((Box::new(Gitea::new(host, self.client_with_token("GITEA_TOKEN", host)?, prefix)?), rest))
+            },
+            _ => Err(format!("unknown provider `{}`, expected `github`, `gitlab`, `gitea`, `forgejo` or `codeberg`", name)),
+        }
+    }
+
+    fn github(&self) -> Box<dyn ReleaseProvider> {
+        Box::new(GitHub { client: self.github.clone() })
+    }
+
+    /// A client for the forge at `base_url`, authenticated with the token in
+    /// `var` if set, or else the one configured for its host
+    fn client_with_token(&self, var: &str, base_url: &str) -> Result<Client, String> {
+        let configured = || auth::host_key(base_url).and_then(|host| self.tokens.get(&host).cloned());
+        let options = ClientOptions {
+            token: std::env::var(var).ok()
+                .or_else(configured)
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty()),
+            ..self.options.clone()
+        };
+        client::build_client(&options)
+    }
+}
@@ -0,0 +1,124 @@
+//! The install registry `~/.egit/installed.toml`: what `egit install` put
+//! where, for `list`, `outdated`, `upgrade` and `uninstall`
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::{info, output};
+
+/// Packages installed by `egit install`, stored in `~/.egit/installed.toml`
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Registry {
+    /// Installed packages by name
+    #[serde(default)]
+    pub packages: BTreeMap<String, InstalledPackage>,
+}
+
+/// An installed package
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct InstalledPackage {
+    /// The tag of the installed release
+    pub version: String,
+    /// The asset it was installed from
+    pub asset: String,
+    /// Every file written by the install, binaries and version files
+    pub files: Vec<PathBuf>,
+    /// SHA-256 of the asset, computed while downloading it. Installs from
+    /// before egit recorded digests have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// SHA-512 of the asset, if requested with `--hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha512: Option<String>,
+    /// BLAKE3 of the asset, if requested with `--hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+}
+
+/// Directory egit keeps its own state in
+pub fn egit_dir() -> io::Result<PathBuf> {
+    env::home_dir()
+        .map(|home| home.join(".egit"))
+        .ok_or_else(|| io::Error::other("cannot find the home directory"))
+}
+
+fn registry_path() -> io::Result<PathBuf> {
+    Ok(egit_dir()?.join("installed.toml"))
+}
+
+impl Registry {
+    /// Read the registry, starting empty when nothing has been installed yet
+    pub fn load() -> io::Result<Registry> {
+        match fs::read_to_string(registry_path()?) {
+            Ok(contents) => toml::from_str(&contents).map_err(io::Error::other),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Registry::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write the registry, replacing the previous one
+    pub fn save(&self) -> io::Result<()> {
+        let path = registry_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, contents)
+    }
+}
+
+/// Print the installed packages with their version and files
+pub fn display_installed(registry: &Registry) {
+    if output::is_json() {
+        output::print_json(&registry.packages);
+        return;
+    }
+    output!("=== Installed Packages ===");
+    if registry.packages.is_empty() {
+        output!("- No packages installed");
+    }
+    for (package, installed) in &registry.packages {
+        output!("{}@{}", package, installed.version);
+        for file in &installed.files {
+            output!("  {}", file.display());
+        }
+    }
+    output!("=== Total: {} packages ===", registry.packages.len());
+}
+
+/// An installed package next to its latest release
+#[derive(Serialize, Debug)]
+pub struct Outdated {
+    /// The package name
+    pub package: String,
+    /// The installed tag
+    pub installed: String,
+    /// None when the repository has no stable release
+    pub latest: Option<String>,
+    /// Whether `latest` is newer than `installed`
+    pub update_available: bool,
+}
+
+/// Print a table of installed and latest versions
+pub fn display_outdated(packages: &[Outdated]) {
+    if output::is_json() {
+        output::print_json(packages);
+        return;
+    }
+    if packages.is_empty() {
+        output!("- No packages installed");
+        return;
+    }
+    let rows: Vec<[String; 4]> = packages.iter()
+        .map(|package| [
+            package.package.clone(),
+            package.installed.clone(),
+            package.latest.clone().unwrap_or_else(|| "-".to_string()),
+            if package.update_available { "yes" } else { "no" }.to_string(),
+        ])
+        .collect();
+    info::print_table(["NAME", "INSTALLED", "LATEST", "UPDATE"], &rows);
+}
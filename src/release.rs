@@ -0,0 +1,237 @@
+//! Resolving packages to releases: parsing `owner/repo@version`, expanding
+//! aliases and bare names, and picking the release a version requirement
+//! asks for
+
+use regex::Regex;
+use reqwest::StatusCode;
+use semver::Version;
+use crate::assets::{ApiError, GitHubRelease};
+use crate::client::{self, Client};
+use crate::config;
+use crate::error::{Context, EgitError};
+use crate::known;
+use crate::picker;
+use crate::provider::{Providers, ReleaseProvider};
+use crate::search;
+use crate::version::{self, OrderBy};
+
+/// Find the requested release, asking GitHub for it directly when possible
+/// and falling back to scanning the release list. When the repository
+/// doesn't exist, the error suggests similarly named ones.
+pub fn resolve_release(provider: &dyn ReleaseProvider, owner: &str, repo: &str, order_by: Option<OrderBy>, version: Option<&str>, pre: bool) -> Result<GitHubRelease, EgitError> {
+    find_package_release(provider, owner, repo, order_by, version, pre).map_err(|e| {
+        let similar = similar_packages(provider, owner, repo, &e);
+        did_you_mean(e, &similar)
+    })
+}
+
+/// `resolve_release` without suggestions for a repository that doesn't exist
+pub fn find_package_release(provider: &dyn ReleaseProvider, owner: &str, repo: &str, order_by: Option<OrderBy>, version: Option<&str>, pre: bool) -> Result<GitHubRelease, EgitError> {
+    let direct = match version {
+        // GitHub's latest release is the newest stable one, as with the list
+        Some("latest") | None if order_by.is_none() && !pre => provider.fetch_latest_release(owner, repo),
+        Some(v) if v != "latest" && !version::is_requirement(v) => fetch_tagged_release(provider, owner, repo, v),
+        _ => Ok(None),
+    };
+    if let Some(release) = direct.context("Failed to fetch releases")? {
+        return Ok(release);
+    }
+    
+    let releases = fetch_sorted_releases(provider, owner, repo, order_by, version, pre)?;
+    find_release(provider, owner, repo, &releases, version, pre).cloned()
+}
+
+/// GitHub repositories named like `owner/repo`, when it doesn't exist. A
+/// missing version or a repository without releases gets no suggestions.
+pub fn similar_packages(provider: &dyn ReleaseProvider, owner: &str, repo: &str, error: &EgitError) -> Vec<String> {
+    if !matches!(error, EgitError::NotFound(_)) || !provider.prefix().is_empty() || client::is_offline() {
+        return Vec::new();
+    }
+    let missing = matches!(provider.fetch_repo(owner, repo),
+        Err(ApiError::Http(e)) if e.status() == Some(StatusCode::NOT_FOUND));
+    if !missing {
+        return Vec::new();
+    }
+    search::similar_repositories(provider.client(), owner, repo).unwrap_or_else(|e| {
+        log::debug!("Failed to search for repositories like {}/{}: {}", owner, repo, e);
+        Vec::new()
+    })
+}
+
+/// Add the suggestions to a not-found error
+pub fn did_you_mean(error: EgitError, similar: &[String]) -> EgitError {
+    let names: Vec<String> = similar.iter().map(|name| format!("`{}`", name)).collect();
+    match names.as_slice() {
+        [] => error,
+        [name] => EgitError::NotFound(format!("{}. Did you mean {}?", error, name)),
+        [names @ .., last] => EgitError::NotFound(format!("{}. Did you mean {} or {}?", error, names.join(", "), last)),
+    }
+}
+
+/// The releases after `from` up to and including `to`, newest first in API
+/// order. Prereleases are skipped unless `pre` is set or one is `to`. With
+/// `since` instead of `from`, only the releases whose tag is a semver version
+/// newer than `since` are kept, as with `list --since-version`.
+pub fn changelog_releases(provider: &dyn ReleaseProvider, owner: &str, repo: &str, from: Option<&str>, since: Option<&Version>, to: Option<&str>, pre: bool) -> Result<Vec<GitHubRelease>, EgitError> {
+    let mut reached_from = |page: &[GitHubRelease]| match since {
+        Some(since) => page.iter().any(|r| version::parse_tag(&r.tag_name).is_some_and(|version| version <= *since)),
+        None => from.is_some_and(|from| page.iter().any(|r| version::is_exact(&r.tag_name, from))),
+    };
+    let releases = provider.fetch_releases_until(owner, repo, &mut reached_from).context("Failed to fetch releases")?;
+    let find = |tag: &str| releases.iter()
+        .position(|r| version::is_exact(&r.tag_name, tag))
+        .ok_or_else(|| EgitError::NotFound(format!("Release `{}` not found", tag)));
+    let end = to.map(find).transpose()?.unwrap_or(0);
+    let start = from.map(find).transpose()?.unwrap_or(releases.len());
+    if start < end {
+        return Err(EgitError::Other(format!("`{}` is newer than `{}`", from.unwrap_or_default(), to.unwrap_or_default())));
+    }
+    let releases: Vec<GitHubRelease> = releases[end..start].iter()
+        .filter(|r| version::is_selectable(r, pre) || to.is_some_and(|to| version::is_exact(&r.tag_name, to)))
+        .cloned()
+        .collect();
+    Ok(match since {
+        Some(since) => version::filter_since(releases, since, |r| &r.tag_name),
+        None => releases,
+    })
+}
+
+/// The release tagged `tag`, also trying a `v` prefix for bare versions
+fn fetch_tagged_release(provider: &dyn ReleaseProvider, owner: &str, repo: &str, tag: &str) -> Result<Option<GitHubRelease>, ApiError> {
+    if let Some(release) = provider.fetch_release_by_tag(owner, repo, tag)? {
+        return Ok(Some(release));
+    }
+    if version::parse_tag(tag).is_some() && !tag.starts_with(['v', 'V']) {
+        return provider.fetch_release_by_tag(owner, repo, &format!("v{}", tag));
+    }
+    Ok(None)
+}
+
+/// Fetch the releases needed to resolve `version`. Pages are only fetched
+/// until the version is found, unless a sort order needs the full list.
+fn fetch_sorted_releases(provider: &dyn ReleaseProvider, owner: &str, repo: &str, order_by: Option<OrderBy>, version: Option<&str>, pre: bool) -> Result<Vec<GitHubRelease>, EgitError> {
+    let mut found = |page: &[GitHubRelease]| match version {
+        _ if order_by.is_some() => false,
+        Some("latest") | None => page.iter().any(|r| version::is_selectable(r, pre)),
+        Some(v) => page.iter().any(|r| version::is_exact(&r.tag_name, v)),
+    };
+    
+    let mut releases = provider.fetch_releases_until(owner, repo, &mut found).context("Failed to fetch releases")?;
+    if let Some(order_by) = order_by {
+        version::sort_releases(&mut releases, order_by);
+    }
+    Ok(releases)
+}
+
+/// Resolve a requested version (`latest`, a tag or a semver requirement) to a
+/// release. Drafts are skipped, and so are prereleases unless `pre` is set.
+fn find_release<'a>(provider: &dyn ReleaseProvider, owner: &str, repo: &str, releases: &'a [GitHubRelease], version: Option<&str>, pre: bool) -> Result<&'a GitHubRelease, EgitError> {
+    match version {
+        Some("latest") | None => {
+            if let Some(release) = releases.iter().find(|r| version::is_selectable(r, pre)) {
+                return Ok(release);
+            }
+            if releases.iter().any(|r| r.prerelease && !r.draft) {
+                return Err(EgitError::NotFound("No stable release found, pass --pre to include prereleases".to_string()));
+            }
+            Err(no_releases(provider, owner, repo))
+        },
+        Some(v) => {
+            version::find_matching(releases, v, pre).ok_or_else(|| {
+                status!("- Version {} not found", v);
+                if releases.is_empty() {
+                    return EgitError::NotFound("No releases found for this package".to_string());
+                }
+                let tags: Vec<&str> = releases.iter().map(|r| r.tag_name.as_str()).collect();
+                EgitError::NotFound(format!("Available versions: {}", tags.join(", ")))
+            })
+        },
+    }
+}
+
+/// Split `owner/repo[@version]`, or `None` for a bare name
+fn parse_package(package: &str) -> Option<(String, String, Option<String>)> {
+    let re = Regex::new(r"^([^/@]+)/([^@]+)(?:@(.+))?$").unwrap();
+    
+    let captures = re.captures(package)?;
+    let owner = captures.get(1).unwrap().as_str().to_string();
+    let repo = captures.get(2).unwrap().as_str().to_string();
+    let version = captures.get(3).map(|v| v.as_str().to_string());
+    Some((owner, repo, version))
+}
+
+/// A package's provider, owner, repository and version
+pub type OpenPackage = (Box<dyn ReleaseProvider>, String, String, Option<String>);
+
+/// The provider of `package` along with its owner, repository and version,
+/// after expanding aliases and bare names
+pub fn open_package(providers: &Providers, package: &str) -> Result<OpenPackage, EgitError> {
+    let package = &config::expand_alias(package);
+    let (provider, rest) = providers.for_package(package)
+        .map_err(|e| EgitError::Other(format!("Invalid package `{}`: {}", package, e)))?;
+    if let Some((owner, repo, version)) = parse_package(rest) {
+        return Ok((provider, owner, repo, version));
+    }
+    
+    let (name, version) = match rest.split_once('@') {
+        Some((name, version)) => (name, Some(version.to_string())),
+        None => (rest, None),
+    };
+    // Only GitHub can be searched for a bare name
+    if name.is_empty() || !provider.prefix().is_empty() {
+        return Err(EgitError::Other(format!("Invalid package `{}`: expected `owner/repo`", package)));
+    }
+    let repository = resolve_bare_name(provider.client(), name)?;
+    let (owner, repo) = repository.split_once('/').expect("full names contain `/`");
+    Ok((provider, owner.to_string(), repo.to_string(), version))
+}
+
+/// The `owner/repo` behind a bare name like `ripgrep`: a well-known tool, or
+/// else the GitHub repository of that name, asking which one when several
+/// are named the same
+fn resolve_bare_name(client: &Client, name: &str) -> Result<String, EgitError> {
+    let repository = match known::lookup(name) {
+        Some(repository) => repository.to_string(),
+        None => {
+            let found = search::find_by_name(client, name)
+                .context(format!("Failed to look up `{}`", name))?;
+            match found.as_slice() {
+                [] => return Err(EgitError::NotFound(format!("No repository named `{}` was found, pass it as `owner/repo`", name))),
+                [only] => only.clone(),
+                _ if picker::is_interactive() => {
+                    picker::pick_repository(&format!("Several repositories are named `{}`, pick one", name), &found)
+                        .cloned()
+                        .ok_or_else(|| EgitError::Other("No repository selected".to_string()))?
+                },
+                _ => return Err(EgitError::NotFound(format!(
+                    "`{}` matches several repositories ({}), pass one of them as `owner/repo`", name, found.join(", ")))),
+            }
+        },
+    };
+    status!("+ Resolved `{}` to `{}`", name, repository);
+    Ok(repository)
+}
+
+/// Name of `package` in the install registry, without its version
+pub fn package_key(providers: &Providers, package: &str) -> Result<String, EgitError> {
+    let (provider, owner, repo, _) = open_package(providers, package)?;
+    Ok(format!("{}{}/{}", provider.prefix(), owner, repo))
+}
+
+fn no_releases(provider: &dyn ReleaseProvider, owner: &str, repo: &str) -> EgitError {
+    // Point at the default branch so the user knows the repository exists
+    if let Ok(repo) = provider.fetch_repo(owner, repo) {
+        status!("- No releases found for this package");
+        return EgitError::NotFound(format!("`{}` has no releases, its default branch is `{}`", repo.full_name, repo.default_branch));
+    }
+    EgitError::NotFound("No releases found for this package".to_string())
+}
+
+/// The newest release of `package` that isn't a draft or prerelease
+pub fn latest_stable_release(providers: &Providers, package: &str) -> Result<Option<GitHubRelease>, EgitError> {
+    let (provider, owner, repo, _) = open_package(providers, package)?;
+    let stable = |r: &GitHubRelease| version::is_selectable(r, false);
+    let releases = provider.fetch_releases_until(&owner, &repo, &mut |page| page.iter().any(stable))
+        .context(format!("Failed to fetch releases for `{}`", package))?;
+    Ok(releases.into_iter().find(stable))
+}
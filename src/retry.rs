@@ -0,0 +1,82 @@
+//! Retrying requests that failed for reasons that may go away on their own
+
+use reqwest::StatusCode;
+use reqwest::blocking::{RequestBuilder, Response};
+use std::thread;
+use std::time::Duration;
+use crate::client::{self, Client};
+
+/// Timeouts and connection failures may go away on their own; HTTP status
+/// errors such as 4xx won't, so they are never retried
+pub fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect()
+}
+
+/// Exponential backoff (1s, 2s, 4s, ...) plus up to 50% random jitter so
+/// parallel chunks don't all retry at the same moment
+pub fn backoff(attempt: u32) -> Duration {
+    let base = Duration::from_secs(1 << attempt.min(6));
+    base + base.mul_f64(fastrand::f64() / 2.0)
+}
+
+/// Why the attempt that ended with `outcome` should be retried, or None once
+/// the result should be returned as-is
+fn retry_reason(outcome: Result<StatusCode, &reqwest::Error>, attempt: u32, retries: u32) -> Option<String> {
+    match outcome {
+        Err(e) if attempt < retries && is_transient(e) => {
+            if e.is_timeout() { Some("Request timed out".to_string()) } else { Some("Connection failed".to_string()) }
+        },
+        Ok(status) if attempt < retries && status.is_server_error() => {
+            Some(format!("Server returned {}", status))
+        },
+        Err(e) if is_transient(e) => {
+            log::debug!("Giving up after {} attempts", attempt + 1);
+            None
+        },
+        Err(_) => {
+            log::debug!("Not retrying, the error is not transient");
+            None
+        },
+        Ok(status) if status.is_server_error() => {
+            log::debug!("Giving up after {} attempts", attempt + 1);
+            None
+        },
+        Ok(_) => None,
+    }
+}
+
+/// Back off before the next attempt, returning how long to wait
+fn next_delay(reason: &str, attempt: &mut u32, retries: u32) -> Duration {
+    let delay = backoff(*attempt);
+    *attempt += 1;
+    status!("- {}, retrying in {:.1}s (attempt {}/{})...",
+             reason, delay.as_secs_f64(), attempt, retries);
+    delay
+}
+
+/// Send the request built by `build`, retrying transient failures and 5xx
+/// responses up to `retries` times with backoff. The last 5xx response is
+/// returned as-is once the retries are used up.
+pub fn send_with_retry(client: &Client, retries: u32, build: impl Fn() -> RequestBuilder) -> reqwest::Result<Response> {
+    let mut attempt = 0;
+    loop {
+        let result = client.send(build());
+        let Some(reason) = retry_reason(result.as_ref().map(Response::status), attempt, retries) else {
+            return result;
+        };
+        thread::sleep(next_delay(&reason, &mut attempt, retries));
+    }
+}
+
+/// `send_with_retry` for the async client of parallel downloads, with the
+/// same policy, backoff and messages
+pub async fn send_with_retry_async(retries: u32, build: impl Fn() -> reqwest::RequestBuilder) -> reqwest::Result<reqwest::Response> {
+    let mut attempt = 0;
+    loop {
+        let result = client::send_async(build()).await;
+        let Some(reason) = retry_reason(result.as_ref().map(reqwest::Response::status), attempt, retries) else {
+            return result;
+        };
+        tokio::time::sleep(next_delay(&reason, &mut attempt, retries)).await;
+    }
+}
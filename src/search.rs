@@ -0,0 +1,155 @@
+//! Searching GitHub for repositories, and suggestions for misspelled ones
+
+use clap::ValueEnum;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use crate::assets::{self, ApiError};
+use crate::client::Client;
+use crate::info;
+use crate::output;
+
+/// Order of search results; GitHub's best match when not given
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SearchSort {
+    /// Most stars first
+    Stars,
+    /// Most recently updated first
+    Updated,
+}
+
+/// A repository found by a search
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Repository {
+    /// `owner/repo`
+    pub full_name: String,
+    /// The description, if there is one
+    pub description: Option<String>,
+    /// Number of stars
+    pub stargazers_count: u64,
+    /// When the repository was last updated (RFC 3339)
+    pub updated_at: Option<String>,
+    /// The repository page in the browser
+    pub html_url: Option<String>,
+    /// Tag of the latest release, filled in after the search
+    #[serde(default)]
+    pub latest_release: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchResults {
+    items: Vec<Repository>,
+}
+
+/// Search GitHub repositories matching `query`, returning at most `limit`
+/// (up to 100) of them
+pub fn search_repositories(client: &Client, query: &str, sort: Option<SearchSort>, limit: usize) -> Result<Vec<Repository>, ApiError> {
+    let mut url = Url::parse(&format!("{}/search/repositories", assets::api_url()))
+        .expect("valid API URL");
+    url.query_pairs_mut()
+        .append_pair("q", query)
+        .append_pair("per_page", &limit.to_string());
+    if let Some(sort) = sort {
+        let sort = match sort {
+            SearchSort::Stars => "stars",
+            SearchSort::Updated => "updated",
+        };
+        url.query_pairs_mut().append_pair("sort", sort).append_pair("order", "desc");
+    }
+    let results: SearchResults = assets::get(client, url.as_str())?.json()?;
+    Ok(results.items)
+}
+
+/// Look up the latest release of each repository. Repositories without
+/// releases, or whose lookup fails, are shown without one.
+pub fn fill_latest_releases(client: &Client, repositories: &mut [Repository]) {
+    for repository in repositories {
+        let Some((owner, repo)) = repository.full_name.split_once('/') else {
+            continue;
+        };
+        match assets::fetch_latest_release(client, owner, repo) {
+            Ok(release) => repository.latest_release = release.map(|r| r.tag_name),
+            Err(e) => log::debug!("Failed to fetch the latest release of {}: {}", repository.full_name, e),
+        }
+    }
+}
+
+/// Print search results, as JSON with `--format json`
+pub fn display_repositories(repositories: &[Repository]) {
+    if output::is_json() {
+        output::print_json(repositories);
+        return;
+    }
+    output!("=== Repositories ===");
+    if repositories.is_empty() {
+        output!("- No repositories found");
+    } else {
+        let rows: Vec<[String; 4]> = repositories
+            .iter()
+            .map(|r| [
+                r.full_name.clone(),
+                r.stargazers_count.to_string(),
+                r.latest_release.clone().unwrap_or_else(|| "-".to_string()),
+                r.description.as_deref().map_or_else(|| "-".to_string(), truncate),
+            ])
+            .collect();
+        info::print_table(["NAME", "STARS", "LATEST", "DESCRIPTION"], &rows);
+    }
+    output!("=== Total: {} repositories ===", repositories.len());
+}
+
+/// Keep descriptions on one line of reasonable length
+fn truncate(description: &str) -> String {
+    const MAX: usize = 60;
+    let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+    if description.chars().count() <= MAX {
+        return description;
+    }
+    let mut short: String = description.chars().take(MAX - 3).collect();
+    short.push_str("...");
+    short
+}
+
+/// Repositories whose name is close to `owner/repo`, closest first, for
+/// suggesting a package that doesn't exist
+pub fn similar_repositories(client: &Client, owner: &str, repo: &str) -> Result<Vec<String>, ApiError> {
+    let wanted = format!("{}/{}", owner, repo).to_lowercase();
+    let repo = repo.to_lowercase();
+    // Allow about one typo per three characters
+    let max_distance = (repo.chars().count() / 3).max(2);
+    let mut similar: Vec<(usize, String)> = search_repositories(client, &format!("{} in:name", repo), None, 10)?
+        .into_iter()
+        .filter(|r| r.full_name.to_lowercase() != wanted)
+        .filter_map(|r| {
+            let (_, name) = r.full_name.split_once('/')?;
+            let distance = levenshtein(&repo, &name.to_lowercase());
+            (distance <= max_distance).then_some((distance, r.full_name))
+        })
+        .collect();
+    // Stable, so equally close names keep GitHub's best-match order
+    similar.sort_by_key(|(distance, _)| *distance);
+    Ok(similar.into_iter().take(3).map(|(_, name)| name).collect())
+}
+
+/// Number of single-character edits turning `a` into `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Repositories named exactly `name`, most starred first
+pub fn find_by_name(client: &Client, name: &str) -> Result<Vec<String>, ApiError> {
+    Ok(search_repositories(client, &format!("{} in:name", name), Some(SearchSort::Stars), 10)?
+        .into_iter()
+        .map(|r| r.full_name)
+        .filter(|full_name| full_name.split_once('/').is_some_and(|(_, repo)| repo.eq_ignore_ascii_case(name)))
+        .collect())
+}
@@ -0,0 +1,132 @@
+//! Choosing the asset of a release that fits a pattern or this platform
+
+use crate::assets::GitHubAsset;
+use glob::Pattern;
+use regex::Regex;
+use std::env::consts::{ARCH, OS};
+
+/// Match an asset name against a regex written as `/.../`, a glob
+/// (`*linux*.tar.gz`) or, when the pattern has neither, a plain substring
+pub fn matches_pattern(name: &str, pattern: &str) -> bool {
+    if let Some(re) = regex_body(pattern) {
+        Regex::new(re).is_ok_and(|re| re.is_match(name))
+    } else if pattern.contains(['*', '?', '[']) {
+        Pattern::new(pattern).is_ok_and(|p| p.matches(name))
+    } else {
+        name.contains(pattern)
+    }
+}
+
+/// The regex inside a `/.../` pattern; asset names never contain slashes
+fn regex_body(pattern: &str) -> Option<&str> {
+    pattern
+        .strip_prefix('/')
+        .and_then(|p| p.strip_suffix('/'))
+        .filter(|p| !p.is_empty())
+}
+
+/// Validate the pattern given to `--asset`
+pub fn parse_asset_pattern(value: &str) -> Result<String, String> {
+    if let Some(re) = regex_body(value) {
+        Regex::new(re).map_err(|e| format!("invalid regex: {}", e))?;
+    } else if value.contains(['*', '?', '[']) {
+        Pattern::new(value).map_err(|e| format!("invalid glob: {}", e))?;
+    }
+    Ok(value.to_string())
+}
+
+/// Names used for the current OS and architecture in release asset names
+fn platform_keywords() -> (&'static [&'static str], &'static [&'static str]) {
+    let os: &[&str] = match OS {
+        "linux" => &["linux"],
+        "macos" => &["darwin", "macos", "apple", "osx"],
+        "windows" => &["windows", "win64", "win32"],
+        "freebsd" => &["freebsd"],
+        _ => &[],
+    };
+    let arch: &[&str] = match ARCH {
+        "x86_64" => &["x86_64", "amd64", "x64"],
+        "aarch64" => &["aarch64", "arm64"],
+        "x86" => &["i686", "i386", "x86"],
+        "arm" => &["armv7", "armhf", "arm"],
+        _ => &[],
+    };
+    (os, arch)
+}
+
+/// Toolchain or C library of the current target as it appears in asset names
+fn env_keywords() -> &'static [&'static str] {
+    if cfg!(target_env = "musl") {
+        &["musl"]
+    } else if cfg!(target_env = "msvc") {
+        &["msvc"]
+    } else if cfg!(target_env = "gnu") {
+        &["gnu"]
+    } else {
+        &[]
+    }
+}
+
+/// Files published next to the binaries that are never the download itself
+const SIDECAR_SUFFIXES: &[&str] = &[
+    ".sha256", ".sha256sum", ".sha512", ".md5", ".sig", ".asc", ".pem", ".sbom", ".txt", ".json",
+];
+
+/// Whether `name` looks like a checksum, signature or other file
+/// published next to the binaries
+pub fn is_sidecar(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SIDECAR_SUFFIXES.iter().any(|s| name.ends_with(s))
+}
+
+/// How well an asset name fits the current platform: 0 when it is not built
+/// for this OS, then higher for a matching architecture and toolchain
+pub fn platform_score(name: &str) -> u32 {
+    let name = name.to_lowercase();
+    let (os, arch) = platform_keywords();
+    if !os.iter().any(|k| name.contains(k)) {
+        return 0;
+    }
+
+    let mut score = 4;
+    if arch.iter().any(|k| name.contains(k)) || (OS == "macos" && name.contains("universal")) {
+        score += 2;
+    }
+    if env_keywords().iter().any(|k| name.contains(k)) {
+        score += 1;
+    }
+    score
+}
+
+/// The candidate that best fits the current platform, or the tied candidates
+/// when scoring cannot tell them apart. Checksums and signatures only win
+/// when nothing else is left.
+pub fn best_for_platform<'a>(all: &[&'a GitHubAsset]) -> Result<&'a GitHubAsset, Vec<&'a GitHubAsset>> {
+    let mut candidates: Vec<_> = all.iter().copied().filter(|a| !is_sidecar(&a.name)).collect();
+    if candidates.is_empty() {
+        return Err(all.to_vec());
+    }
+
+    let best = candidates.iter().map(|a| platform_score(&a.name)).max().unwrap_or(0);
+    candidates.retain(|a| platform_score(&a.name) == best);
+    match candidates.as_slice() {
+        [asset] => Ok(asset),
+        _ => Err(candidates),
+    }
+}
+
+/// Whether an asset name mentions both the current OS and architecture
+pub fn matches_platform(name: &str) -> bool {
+    let name = name.to_lowercase();
+    let (os, arch) = platform_keywords();
+    os.iter().any(|k| name.contains(k)) && arch.iter().any(|k| name.contains(k))
+}
+
+/// Assets passing the `--asset` pattern and `--auto` platform filters
+pub fn filter_assets<'a>(assets: &'a [GitHubAsset], pattern: Option<&str>, auto: bool) -> Vec<&'a GitHubAsset> {
+    assets
+        .iter()
+        .filter(|a| pattern.is_none_or(|p| matches_pattern(&a.name, p)))
+        .filter(|a| !auto || matches_platform(&a.name))
+        .collect()
+}
@@ -0,0 +1,52 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Where egit itself is released
+pub const REPOSITORY: &str = "EdwardJoke/egit";
+
+// The running executable, with symlinks resolved so that a link on PATH
+// isn't replaced by a copy
+pub fn current_exe() -> io::Result<PathBuf> {
+    env::current_exe()?.canonicalize()
+}
+
+// Swap the running executable for `new`. The new binary is copied next to
+// it first and renamed over it, so the executable is never half-written. A
+// running executable can't be replaced on Windows but can be renamed, so it
+// is moved to `<name>.old` first and put back if the swap fails.
+pub fn replace_exe(current: &Path, new: &Path) -> io::Result<()> {
+    let name = current.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let temp = current.with_file_name(format!(".{}.new", name));
+    // Keeps the permission bits of `new`
+    fs::copy(new, &temp)?;
+
+    let swapped = if cfg!(windows) {
+        let old = old_exe(current);
+        let _ = fs::remove_file(&old);
+        fs::rename(current, &old).and_then(|()| fs::rename(&temp, current).inspect_err(|_| {
+            let _ = fs::rename(&old, current);
+        }))
+    } else {
+        fs::rename(&temp, current)
+    };
+    if swapped.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    swapped
+}
+
+// Delete the executable a previous update left behind on Windows, which
+// could not be removed while it was running
+pub fn remove_old_exe(current: &Path) {
+    let old = old_exe(current);
+    if old.exists() && let Err(e) = fs::remove_file(&old) {
+        log::debug!("Failed to remove {}: {}", old.display(), e);
+    }
+}
+
+fn old_exe(current: &Path) -> PathBuf {
+    let name = current.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    current.with_file_name(format!("{}.old", name))
+}
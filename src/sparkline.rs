@@ -0,0 +1,61 @@
+//! A small chart of the recent download speed for the progress bar
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_SAMPLES: usize = 20;
+
+/// Ring buffer of recent download rates rendered with unicode blocks
+pub struct Sparkline {
+    samples: VecDeque<f64>,
+    last_sample: Instant,
+    last_bytes: u64,
+}
+
+impl Sparkline {
+    /// A sparkline for a download that starts at `start_bytes`, e.g. when
+    /// resuming
+    pub fn new(start_bytes: u64) -> Self {
+        Sparkline {
+            samples: VecDeque::with_capacity(MAX_SAMPLES),
+            last_sample: Instant::now(),
+            last_bytes: start_bytes,
+        }
+    }
+
+    /// Record the current byte count, returning the rendered sparkline
+    /// whenever a new rate sample was taken
+    pub fn sample(&mut self, bytes: u64) -> Option<String> {
+        let elapsed = self.last_sample.elapsed();
+        if elapsed < SAMPLE_INTERVAL {
+            return None;
+        }
+
+        let rate = bytes.saturating_sub(self.last_bytes) as f64 / elapsed.as_secs_f64();
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rate);
+        self.last_sample = Instant::now();
+        self.last_bytes = bytes;
+
+        Some(self.render())
+    }
+
+    fn render(&self) -> String {
+        let max = self.samples.iter().cloned().fold(0.0, f64::max);
+        self.samples
+            .iter()
+            .map(|rate| {
+                if max <= 0.0 {
+                    BLOCKS[0]
+                } else {
+                    let level = (rate / max * (BLOCKS.len() - 1) as f64).round() as usize;
+                    BLOCKS[level.min(BLOCKS.len() - 1)]
+                }
+            })
+            .collect()
+    }
+}
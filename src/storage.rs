@@ -0,0 +1,194 @@
+//! Where downloads are written: the `Storage` trait and its file system
+//! implementation
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Destination for downloaded files. The download code only ever writes
+/// through this trait, so backends other than the local filesystem (e.g.
+/// object stores for mirroring) can be plugged in without touching it.
+pub trait Storage {
+    /// Open a writer for `name`; `size` is the expected total length in bytes
+    /// (0 when unknown) for backends that need it up front
+    fn create_writer(&self, name: &str, size: u64) -> io::Result<Box<dyn Write>>;
+
+    /// Open `name` for appending, creating it if needed, to resume a download
+    fn append_writer(&self, name: &str) -> io::Result<Box<dyn Write>>;
+
+    /// Move a finished file into place under its final name
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+
+    /// Whether `name` already exists and would be overwritten
+    fn exists(&self, name: &str) -> bool;
+
+    /// Create `name` pre-sized to `size` bytes so that parallel chunks can be
+    /// written in place with `open_at`
+    fn preallocate(&self, name: &str, size: u64) -> io::Result<()>;
+
+    /// Open an independent writer positioned at `offset` in a preallocated file
+    fn open_at(&self, name: &str, offset: u64) -> io::Result<Box<dyn Write + Send>>;
+
+    /// Current length in bytes of the stored file
+    fn size(&self, name: &str) -> io::Result<u64>;
+
+    /// Read back a stored file, e.g. to verify its checksum
+    fn open_reader(&self, name: &str) -> io::Result<Box<dyn Read>>;
+
+    /// Delete a stored file that turned out to be bad
+    fn remove(&self, name: &str) -> io::Result<()>;
+
+    /// Create the directory `name` and any missing parents; an existing one
+    /// is left as it is
+    fn create_dir(&self, name: &str) -> io::Result<()>;
+
+    /// Store the local file at `path` as `name`, replacing a file of that name,
+    /// e.g. an entry unpacked from an archive. `path` is consumed.
+    fn import(&self, path: &Path, name: &str) -> io::Result<()> {
+        let metadata = fs::metadata(path)?;
+        let mut writer = self.create_writer(name, metadata.len())?;
+        io::copy(&mut File::open(path)?, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        if is_executable(&metadata) {
+            self.set_executable(name)?;
+        }
+        fs::remove_file(path)
+    }
+
+    /// Mark a stored file as executable, for installed binaries
+    fn set_executable(&self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// A new, empty local directory to unpack archives into before their
+    /// files are stored. Backends that aren't a local filesystem get one in
+    /// the temporary directory; the caller removes it when done.
+    fn scratch_dir(&self, _near: &str) -> io::Result<PathBuf> {
+        let dir = unique_name(&env::temp_dir().join("egit"), "d");
+        fs::create_dir(&dir)?;
+        Ok(dir)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// A name next to `path` that no other call, in this process or another,
+/// uses: `.{file name}.{pid}-{counter}.{suffix}`
+fn unique_name(path: &Path, suffix: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}-{}.{}", file_name, process::id(), counter, suffix))
+}
+
+/// Default backend writing files relative to the current directory
+pub struct FsStorage;
+
+/// Create the directories leading up to `name` so downloads can target new
+/// locations
+fn create_parent_dirs(name: &str) -> io::Result<()> {
+    match Path::new(name).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+impl Storage for FsStorage {
+    fn create_writer(&self, name: &str, _size: u64) -> io::Result<Box<dyn Write>> {
+        create_parent_dirs(name)?;
+        Ok(Box::new(File::create(name)?))
+    }
+
+    fn append_writer(&self, name: &str) -> io::Result<Box<dyn Write>> {
+        create_parent_dirs(name)?;
+        Ok(Box::new(OpenOptions::new().create(true).append(true).open(name)?))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        Path::new(name).exists()
+    }
+
+    fn preallocate(&self, name: &str, size: u64) -> io::Result<()> {
+        create_parent_dirs(name)?;
+        File::create(name)?.set_len(size)
+    }
+
+    fn open_at(&self, name: &str, offset: u64) -> io::Result<Box<dyn Write + Send>> {
+        let mut file = OpenOptions::new().write(true).open(name)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Box::new(file))
+    }
+
+    fn size(&self, name: &str) -> io::Result<u64> {
+        Ok(fs::metadata(name)?.len())
+    }
+
+    fn open_reader(&self, name: &str) -> io::Result<Box<dyn Read>> {
+        Ok(Box::new(File::open(name)?))
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        fs::remove_file(name)
+    }
+
+    fn create_dir(&self, name: &str) -> io::Result<()> {
+        fs::create_dir_all(name)
+    }
+
+    /// A rename keeps the permissions and symlinks of unpacked entries; the
+    /// scratch directory is next to the destination, so it rarely has to fall
+    /// back to copying
+    fn import(&self, path: &Path, name: &str) -> io::Result<()> {
+        create_parent_dirs(name)?;
+        match fs::rename(path, name) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                fs::copy(path, name)?;
+                fs::remove_file(path)
+            },
+            result => result,
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_executable(&self, name: &str) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = fs::metadata(name)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o755);
+        fs::set_permissions(name, permissions)
+    }
+
+    /// Next to `near`, or inside it when it is `.` or a root, so unpacked
+    /// files are moved into place rather than copied across filesystems
+    fn scratch_dir(&self, near: &str) -> io::Result<PathBuf> {
+        let path = Path::new(near);
+        let dir = match path.file_name() {
+            Some(_) => {
+                create_parent_dirs(near)?;
+                unique_name(path, "d")
+            },
+            None => {
+                fs::create_dir_all(path)?;
+                unique_name(&path.join("egit"), "d")
+            },
+        };
+        fs::create_dir(&dir)?;
+        Ok(dir)
+    }
+}
@@ -0,0 +1,68 @@
+//! The `--limit-rate` bandwidth limit, shared by all downloads of a run
+
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Shared by every download in the process, so that parallel chunks and
+/// `sync` jobs stay under --limit-rate together
+static LIMITER: OnceLock<Mutex<Limiter>> = OnceLock::new();
+
+struct Limiter {
+    // Bytes per second
+    rate: u64,
+    // When the bytes received so far may all have arrived at `rate`
+    next: Instant,
+}
+
+/// Cap the combined speed of all downloads at `rate` bytes per second
+/// (`--limit-rate`). Only the first call has an effect.
+pub fn set_limit(rate: u64) {
+    let _ = LIMITER.set(Mutex::new(Limiter { rate, next: Instant::now() }));
+}
+
+/// Account for `bytes` just received, sleeping until they fit within the
+/// limit. Idle time earns no credit, so there is no burst after a pause.
+pub fn consume(bytes: usize) {
+    thread::sleep(reserve(bytes));
+}
+
+/// Account for `bytes` just received and return how long to wait before
+/// reading more, for callers that can't block the thread
+pub fn reserve(bytes: usize) -> Duration {
+    let Some(limiter) = LIMITER.get() else {
+        return Duration::ZERO;
+    };
+    let mut limiter = limiter.lock().unwrap();
+    let now = Instant::now();
+    limiter.next = limiter.next.max(now) + Duration::from_secs_f64(bytes as f64 / limiter.rate as f64);
+    limiter.next - now
+}
+
+/// Parse a rate given to `--limit-rate`: bytes per second, optionally with a
+/// `k`, `M` or `G` suffix (powers of 1024, as in curl)
+pub fn parse_rate(value: &str) -> Result<u64, String> {
+    let rate = parse_bytes(value).ok_or_else(|| format!("expected a rate like 500k or 2M, got `{}`", value.trim()))?;
+    if rate == 0 {
+        return Err("the rate must be at least 1 byte per second".to_string());
+    }
+    Ok(rate)
+}
+
+/// Parse a size given to `--max-size`, with the same suffixes as a rate
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    parse_bytes(value).ok_or_else(|| format!("expected a size like 500k or 2M, got `{}`", value.trim()))
+}
+
+fn parse_bytes(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&value[..i], 1024.0),
+        Some((i, 'm' | 'M')) => (&value[..i], 1024.0 * 1024.0),
+        Some((i, 'g' | 'G')) => (&value[..i], 1024.0 * 1024.0 * 1024.0),
+        _ => (value, 1.0),
+    };
+    number.parse::<f64>().ok()
+        .filter(|number| *number >= 0.0)
+        .map(|number| (number * multiplier) as u64)
+}
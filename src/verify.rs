@@ -0,0 +1,213 @@
+//! Checking signatures of downloaded assets with minisign, GPG or cosign,
+//! with a public key or keyless against a Sigstore identity
+
+use minisign_verify::{PublicKey, Signature};
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// Detached signature formats, told apart by the public key they are checked with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheme {
+    /// Minisign, checked natively
+    Minisign,
+    /// OpenPGP, checked with `gpg`
+    Gpg,
+    /// Sigstore, checked with `cosign`
+    Cosign,
+}
+
+impl Scheme {
+    /// Recognise the scheme from the contents of a public key file
+    pub fn detect(key: &str) -> Option<Scheme> {
+        let key = key.trim();
+        if key.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----") {
+            Some(Scheme::Gpg)
+        } else if key.starts_with("-----BEGIN PUBLIC KEY-----") {
+            Some(Scheme::Cosign)
+        } else if PublicKey::decode(key).is_ok() || PublicKey::from_base64(key).is_ok() {
+            Some(Scheme::Minisign)
+        } else {
+            None
+        }
+    }
+
+    /// Names the signature of `asset_name` is usually published under
+    pub fn signature_names(self, asset_name: &str) -> Vec<String> {
+        let suffixes: &[&str] = match self {
+            Scheme::Minisign => &[".minisig"],
+            Scheme::Gpg => &[".asc", ".sig", ".gpg"],
+            Scheme::Cosign => &[".bundle", ".cosign.bundle", ".sigstore.json", ".sig"],
+        };
+        suffixes.iter().map(|suffix| format!("{}{}", asset_name, suffix)).collect()
+    }
+}
+
+/// Who a keyless Sigstore signature must come from: the identity in the
+/// signing certificate and the OIDC issuer that vouched for it, as `cosign`
+/// takes them with `--certificate-identity` and `--certificate-oidc-issuer`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    /// Signer identity, such as an email or a CI workflow URL
+    pub identity: String,
+    /// OIDC issuer URL, such as `https://token.actions.githubusercontent.com`
+    pub oidc_issuer: String,
+}
+
+/// What the signature of a download is checked against
+#[derive(Clone, Copy, Debug)]
+pub enum Signer<'a> {
+    /// A public key, of the format `Scheme` detected from it
+    Key(Scheme, &'a Path),
+    /// The certificate of a keyless Sigstore bundle, checked with `cosign`
+    Keyless(&'a Identity),
+}
+
+impl Signer<'_> {
+    /// Names the signature of `asset_name` is usually published under. A
+    /// keyless signature needs a bundle, as a bare `.sig` lacks the
+    /// certificate.
+    pub fn signature_names(&self, asset_name: &str) -> Vec<String> {
+        match self {
+            Signer::Key(scheme, _) => scheme.signature_names(asset_name),
+            Signer::Keyless(_) => [".sigstore.json", ".sigstore", ".bundle", ".cosign.bundle"].iter()
+                .map(|suffix| format!("{}{}", asset_name, suffix))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Signer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Signer::Key(scheme, _) => write!(f, "{:?}", scheme),
+            Signer::Keyless(_) => write!(f, "keyless Sigstore"),
+        }
+    }
+}
+
+/// Check the contents of `file` against the detached `signature` (published
+/// as `signature_name`) from `signer`. Minisign is verified natively as the
+/// file is read; GPG and cosign signatures need the `gpg` and `cosign` tools,
+/// which are given a copy in a scratch directory.
+pub fn verify(signer: Signer, file: &mut dyn Read, signature_name: &str, signature: &[u8]) -> Result<(), String> {
+    let (scheme, key_path) = match signer {
+        Signer::Key(scheme, key_path) => (scheme, key_path),
+        Signer::Keyless(identity) => return verify_keyless(identity, file, signature_name, signature),
+    };
+    match scheme {
+        Scheme::Minisign => verify_minisign(key_path, file, signature),
+        Scheme::Gpg | Scheme::Cosign => {
+            let dir = scratch_dir().map_err(|e| format!("failed to create a temporary directory: {}", e))?;
+            let result = verify_external(scheme, &dir, key_path, file, signature_name, signature);
+            let _ = fs::remove_dir_all(&dir);
+            result
+        },
+    }
+}
+
+/// Check `file` against a keyless Sigstore `bundle` with `cosign
+/// verify-blob`, requiring its certificate to name `identity`
+fn verify_keyless(identity: &Identity, file: &mut dyn Read, bundle_name: &str, bundle: &[u8]) -> Result<(), String> {
+    let dir = scratch_dir().map_err(|e| format!("failed to create a temporary directory: {}", e))?;
+    let result = stage(&dir, file, bundle_name, bundle).and_then(|(bundle_path, file_path)| run(Command::new("cosign")
+        .arg("verify-blob")
+        .arg("--bundle")
+        .arg(&bundle_path)
+        .arg("--certificate-identity")
+        .arg(&identity.identity)
+        .arg("--certificate-oidc-issuer")
+        .arg(&identity.oidc_issuer)
+        .arg(&file_path)));
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+fn verify_minisign(key_path: &Path, file: &mut dyn Read, signature: &[u8]) -> Result<(), String> {
+    let key = fs::read_to_string(key_path).map_err(|e| format!("failed to read `{}`: {}", key_path.display(), e))?;
+    let key = PublicKey::decode(key.trim())
+        .or_else(|_| PublicKey::from_base64(key.trim()))
+        .map_err(|e| format!("invalid minisign public key: {}", e))?;
+    let signature = Signature::decode(&String::from_utf8_lossy(signature))
+        .map_err(|e| format!("invalid minisign signature: {}", e))?;
+    let mut verifier = key.verify_stream(&signature).map_err(|e| e.to_string())?;
+
+    let mut buffer = [0; 8192];
+    loop {
+        let n = file.read(&mut buffer).map_err(|e| format!("failed to read the download: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        verifier.update(&buffer[..n]);
+    }
+    verifier.finalize().map_err(|e| e.to_string())
+}
+
+/// Save `signature` and a copy of `file` into `dir` for an external tool.
+/// Returns their paths.
+fn stage(dir: &Path, file: &mut dyn Read, signature_name: &str, signature: &[u8]) -> Result<(PathBuf, PathBuf), String> {
+    let signature_path = dir.join(signature_name);
+    fs::write(&signature_path, signature).map_err(|e| format!("failed to save the signature: {}", e))?;
+    let file_path = dir.join("download");
+    File::create(&file_path)
+        .and_then(|mut copy| io::copy(file, &mut copy))
+        .map_err(|e| format!("failed to copy the download: {}", e))?;
+    Ok((signature_path, file_path))
+}
+
+fn verify_external(scheme: Scheme, dir: &Path, key_path: &Path, file: &mut dyn Read, signature_name: &str, signature: &[u8]) -> Result<(), String> {
+    let (signature_path, file_path) = stage(dir, file, signature_name, signature)?;
+    let file = file_path.as_path();
+
+    match scheme {
+        Scheme::Gpg => {
+            // A throwaway keyring so only the given key is trusted and the
+            // user's own keyring is left alone
+            let gpg = |args: &[&OsStr]| run(Command::new("gpg")
+                .args(["--batch", "--quiet", "--homedir"])
+                .arg(dir)
+                .args(args));
+            gpg(&[OsStr::new("--import"), key_path.as_os_str()])?;
+            gpg(&[OsStr::new("--verify"), signature_path.as_os_str(), file.as_os_str()])
+        },
+        Scheme::Cosign => {
+            let flag = if signature_name.ends_with(".sig") { "--signature" } else { "--bundle" };
+            run(Command::new("cosign")
+                .arg("verify-blob")
+                .arg("--key")
+                .arg(key_path)
+                .arg(flag)
+                .arg(&signature_path)
+                .arg(file))
+        },
+        Scheme::Minisign => unreachable!("minisign signatures are verified natively"),
+    }
+}
+
+/// Run an external verifier, turning a missing tool or a failed check into an error
+fn run(command: &mut Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|e| format!("`{}` is needed to check this signature but could not be run: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{}` rejected the signature: {}", program, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+fn scratch_dir() -> io::Result<PathBuf> {
+    let dir = env::temp_dir().join(format!("egit-verify-{}", process::id()));
+    fs::create_dir_all(&dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(dir)
+}
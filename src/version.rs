@@ -0,0 +1,110 @@
+//! Version tags and requirements: parsing, comparing and matching them
+
+use crate::assets::GitHubRelease;
+use clap::ValueEnum;
+use semver::{Version, VersionReq};
+use std::cmp::Reverse;
+
+/// Release field used to decide which release is the newest
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OrderBy {
+    /// The publication date
+    Published,
+    /// The creation date
+    Created,
+    /// The tag, compared as a semver version
+    TagSemver,
+}
+
+/// Parse a tag such as `v1.2.3` or `1.2.3` as a semver version
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    let tag = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+    Version::parse(tag).ok()
+}
+
+/// Whether `tag` is exactly the version `spec`: the same string, or the same
+/// semver version with or without a `v` prefix
+pub fn is_exact(tag: &str, spec: &str) -> bool {
+    tag == spec || parse_tag(spec).is_some_and(|v| parse_tag(tag) == Some(v))
+}
+
+/// Whether `candidate` is a newer release than `current`: a higher semver
+/// version, or simply a different tag when either is not semver
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_tag(candidate), parse_tag(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => candidate != current,
+    }
+}
+
+/// Whether a version spec is a semver requirement (`^1.0`, `>=1.2, <2`,
+/// `1.*`) rather than something that could be a tag name
+pub fn is_requirement(spec: &str) -> bool {
+    spec.contains(['^', '~', '>', '<', '=', '*', ',', ' ']) || spec.ends_with(".x")
+}
+
+/// Whether a release may be picked without naming its exact tag: drafts
+/// never are, prereleases only with `--pre`
+pub fn is_selectable(release: &GitHubRelease, pre: bool) -> bool {
+    !release.draft && (pre || !release.prerelease)
+}
+
+/// Find the release a version spec refers to: an exact tag (see `is_exact`),
+/// or else the highest selectable release satisfying a semver requirement
+/// such as `^1.0`, `~1.2.3` or `>=1.0, <2.0`. Non-semver tags never match a
+/// requirement.
+pub fn find_matching<'a>(releases: &'a [GitHubRelease], spec: &str, pre: bool) -> Option<&'a GitHubRelease> {
+    if let Some(release) = releases.iter().find(|r| !r.draft && is_exact(&r.tag_name, spec)) {
+        return Some(release);
+    }
+
+    let req = VersionReq::parse(spec.trim_start_matches(['v', 'V'])).ok()?;
+    releases
+        .iter()
+        .filter(|r| is_selectable(r, pre))
+        .filter_map(|r| parse_tag(&r.tag_name).map(|v| (v, r)))
+        .filter(|(v, _)| req.matches(v))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, r)| r)
+}
+
+/// Whether `tag` is a release the version spec may resolve to: `latest`
+/// matches any tag
+pub fn satisfies(tag: &str, spec: &str) -> bool {
+    if spec == "latest" || is_exact(tag, spec) {
+        return true;
+    }
+    let req = VersionReq::parse(spec.trim_start_matches(['v', 'V']));
+    matches!((req, parse_tag(tag)), (Ok(req), Some(version)) if req.matches(&version))
+}
+
+/// Parse a version given on the command line, tolerating a `v` prefix
+pub fn parse_version_arg(value: &str) -> Result<Version, String> {
+    parse_tag(value).ok_or_else(|| format!("`{}` is not a semver version", value))
+}
+
+/// Keep only items whose tag is a semver version newer than `since`. Tags
+/// that don't parse as semver can't be ordered, so they are dropped with a
+/// warning.
+pub fn filter_since<T>(items: Vec<T>, since: &Version, tag: impl Fn(&T) -> &str) -> Vec<T> {
+    items
+        .into_iter()
+        .filter(|item| match parse_tag(tag(item)) {
+            Some(version) => version > *since,
+            None => {
+                status!("- Skipping `{}`: not a semver tag", tag(item));
+                false
+            }
+        })
+        .collect()
+}
+
+/// Sort releases newest first by the given field; releases missing the field
+/// (or with non-semver tags) keep their relative order at the end
+pub fn sort_releases(releases: &mut [GitHubRelease], order_by: OrderBy) {
+    match order_by {
+        OrderBy::Published => releases.sort_by(|a, b| b.published_at.cmp(&a.published_at)),
+        OrderBy::Created => releases.sort_by(|a, b| b.created_at.cmp(&a.created_at)),
+        OrderBy::TagSemver => releases.sort_by_cached_key(|r| Reverse(parse_tag(&r.tag_name))),
+    }
+}
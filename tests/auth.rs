@@ -0,0 +1,52 @@
+mod common;
+
+use egit::auth::{self, Source, Store};
+
+#[test]
+fn insecure_storage_saves_the_token_per_host() {
+    common::setup();
+    let host = "ghes.example.com";
+
+    assert_eq!(auth::save(host, "ghp_saved", true).unwrap(), Store::File);
+    assert_eq!(auth::lookup(host), Some(("ghp_saved".to_string(), Store::File)));
+    assert_eq!(auth::lookup("other.example.com"), None);
+
+    // A token from the command line wins over the saved one
+    assert_eq!(auth::resolve(Some("ghp_flag".to_string()), None, host), Some(("ghp_flag".to_string(), Source::Flag)));
+
+    assert_eq!(auth::remove(host).unwrap(), [Store::File]);
+    assert_eq!(auth::lookup(host), None);
+}
+
+#[test]
+fn mask_keeps_only_the_token_kind() {
+    assert_eq!(auth::mask("ghp_0123456789abcdef"), "ghp_****");
+    assert_eq!(auth::mask("github_pat_11AAA_xyz"), "github_pat_****");
+    assert_eq!(auth::mask("0123456789abcdef"), "****");
+}
+
+#[test]
+fn netrc_password_matches_the_machine() {
+    let netrc = "\
+# GitHub
+machine api.github.com login octocat password ghp_api
+default login anonymous password guest
+
+macdef init
+machine github.com password ghp_macro
+
+machine github.com
+    login octocat
+    password ghp_web
+";
+    assert_eq!(auth::netrc_password(netrc, "github.com").as_deref(), Some("ghp_web"));
+    assert_eq!(auth::netrc_password(netrc, "api.github.com").as_deref(), Some("ghp_api"));
+    assert_eq!(auth::netrc_password(netrc, "gitlab.com"), None);
+}
+
+#[test]
+fn host_key_keeps_the_port() {
+    assert_eq!(auth::host_key("https://gitlab.com").as_deref(), Some("gitlab.com"));
+    assert_eq!(auth::host_key("https://git.example.com:8443/api/v1").as_deref(), Some("git.example.com:8443"));
+    assert_eq!(auth::host_key("not a url"), None);
+}
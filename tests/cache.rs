@@ -0,0 +1,108 @@
+mod common;
+
+use common::{MockHttp, asset_url, data, release, scratch_dir, sha256};
+use egit::assets::GitHubRelease;
+use egit::cache;
+use egit::download::{DownloadOptions, download_release_asset};
+use egit::storage::FsStorage;
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+fn options(dir: &Path) -> DownloadOptions {
+    DownloadOptions {
+        dir: Some(dir.to_path_buf()),
+        no_interactive: true,
+        assume_yes: true,
+        ..Default::default()
+    }
+}
+
+fn parse(value: serde_json::Value) -> GitHubRelease {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn store_then_lookup() {
+    common::setup();
+    let url = asset_url("store/tool", "v1.0.0", "tool.bin");
+    let data = data(1000);
+
+    assert!(cache::lookup(&url).is_none());
+    cache::store(&url, &mut &data[..]).unwrap();
+    let entry = cache::lookup(&url).unwrap();
+    assert_eq!(entry.key, "github.com/store/tool/releases/download/v1.0.0/tool.bin");
+    assert_eq!(entry.sha256, sha256(&data));
+    assert_eq!(entry.size, 1000);
+    assert_eq!(fs::read(&entry.path).unwrap(), data);
+}
+
+#[test]
+fn concurrent_stores_share_one_blob() {
+    common::setup();
+    let data = data(256 * 1024);
+
+    // Like `sync -j`: the same asset under several URLs, and the same URL
+    // several times, stored at once
+    thread::scope(|scope| {
+        for i in 0..8 {
+            let data = &data;
+            scope.spawn(move || {
+                let url = asset_url("concurrent/tool", &format!("v1.{}", i % 4), "tool.bin");
+                cache::store(&url, &mut &data[..]).unwrap();
+            });
+        }
+    });
+
+    let assets = cache::cache_dir().unwrap().join("assets/github.com/concurrent/tool/releases/download");
+    for i in 0..4 {
+        let entry = cache::lookup(&asset_url("concurrent/tool", &format!("v1.{}", i), "tool.bin")).unwrap();
+        assert_eq!(entry.sha256, sha256(&data));
+        assert_eq!(fs::read(&entry.path).unwrap(), data);
+        // Nothing but the finished key file, no temporary one left behind
+        let names: Vec<_> = fs::read_dir(assets.join(format!("v1.{}", i))).unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["tool.bin.sha256"]);
+    }
+}
+
+#[test]
+fn second_download_comes_from_the_cache() {
+    let data = data(4096);
+    let release = parse(release("cached/tool", "v1.0.0", false, &[("tool.bin", &data)]));
+    let url = asset_url("cached/tool", "v1.0.0", "tool.bin");
+
+    let http = MockHttp::new();
+    http.bytes(&url, &data);
+    let first = scratch_dir("cached-first");
+    download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], "cached/tool", &options(&first)).unwrap();
+    assert_eq!(http.requests(), [url.as_str()]);
+
+    // Nothing is registered this time, so any request would fail
+    let offline = MockHttp::new();
+    let second = scratch_dir("cached-second");
+    let digests = download_release_asset(&offline.client(), &FsStorage, &release, &release.assets[0], "cached/tool", &options(&second)).unwrap().unwrap();
+    assert_eq!(fs::read(second.join("tool.bin")).unwrap(), data);
+    assert_eq!(digests.sha256, sha256(&data));
+    assert!(offline.requests().is_empty());
+}
+
+#[test]
+fn replaced_asset_is_downloaded_again() {
+    let old = data(4096);
+    let url = asset_url("replaced/tool", "v1.0.0", "tool.bin");
+    common::setup();
+    cache::store(&url, &mut &old[..]).unwrap();
+
+    // Same URL, new contents: the digest from the API no longer matches
+    let new = b"rebuilt".repeat(100);
+    let release = parse(release("replaced/tool", "v1.0.0", false, &[("tool.bin", &new)]));
+    let http = MockHttp::new();
+    http.bytes(&url, &new);
+    let dir = scratch_dir("replaced");
+    download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], "replaced/tool", &options(&dir)).unwrap();
+    assert_eq!(fs::read(dir.join("tool.bin")).unwrap(), new);
+    assert_eq!(http.requests(), [url.as_str()]);
+    assert_eq!(cache::lookup(&url).unwrap().sha256, sha256(&new));
+}
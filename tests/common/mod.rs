@@ -0,0 +1,264 @@
+// Test harness: a `Client` whose requests are answered from fixtures
+// registered per URL, so release lookups and downloads run without a
+// network. Shared by the integration tests through `mod common;`.
+
+#![allow(dead_code)]
+
+use egit::client::{Client, ClientOptions, HttpClient};
+use egit::provider::Providers;
+use reqwest::ResponseBuilderExt;
+use reqwest::blocking::{Request, Response};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+
+pub const API: &str = "https://api.github.com";
+
+struct Fixture {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+#[derive(Default)]
+struct State {
+    fixtures: HashMap<String, Fixture>,
+    requests: Vec<String>,
+}
+
+// Answers requests by URL, with the query string or without it. Anything
+// not registered gets a 404.
+#[derive(Clone, Default)]
+pub struct MockHttp {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockHttp {
+    pub fn new() -> MockHttp {
+        setup();
+        MockHttp::default()
+    }
+
+    pub fn respond(&self, url: &str, status: u16, headers: &[(&str, &str)], body: impl Into<Vec<u8>>) -> &Self {
+        let fixture = Fixture {
+            status,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            body: body.into(),
+        };
+        self.state.lock().unwrap().fixtures.insert(url.to_string(), fixture);
+        self
+    }
+
+    pub fn json(&self, url: &str, value: Value) -> &Self {
+        self.respond(url, 200, &[("content-type", "application/json")], value.to_string())
+    }
+
+    pub fn bytes(&self, url: &str, body: &[u8]) -> &Self {
+        let length = body.len().to_string();
+        self.respond(url, 200, &[("content-type", "application/octet-stream"), ("content-length", &length)], body)
+    }
+
+    // The URLs requested so far, in order
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn client(&self) -> Client {
+        Client::with_http(self.clone(), ClientOptions::default())
+    }
+
+    pub fn providers(&self) -> Providers {
+        Providers::new(self.client(), ClientOptions::default(), None, BTreeMap::new(), BTreeMap::new())
+    }
+}
+
+impl HttpClient for MockHttp {
+    fn execute(&self, request: Request) -> reqwest::Result<Response> {
+        let url = request.url().clone();
+        let mut without_query = url.clone();
+        without_query.set_query(None);
+
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(url.to_string());
+        let fixture = state.fixtures.get(url.as_str()).or_else(|| state.fixtures.get(without_query.as_str()));
+        let mut response = http::Response::builder().url(url.clone());
+        let body = match fixture {
+            Some(fixture) => {
+                response = response.status(fixture.status);
+                for (name, value) in &fixture.headers {
+                    response = response.header(name, value);
+                }
+                fixture.body.clone()
+            },
+            None => {
+                response = response.status(404);
+                br#"{"message":"Not Found"}"#.to_vec()
+            },
+        };
+        Ok(Response::from(response.body(body).expect("valid fixture")))
+    }
+}
+
+// Keep the API response cache and the config of the tests away from the
+// user's, once per test binary
+pub fn setup() {
+    static SETUP: Once = Once::new();
+    SETUP.call_once(|| {
+        let dir = scratch_dir("home");
+        // Runs before any test gets past `setup`, so nothing reads the
+        // environment concurrently
+        unsafe {
+            env::set_var("XDG_CACHE_HOME", dir.join("cache"));
+            env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+        }
+    });
+}
+
+// An empty directory for one test
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("egit-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+// A release as the GitHub API returns it, with an asset per `(name, data)`
+// downloadable from `https://github.com/<repo>/releases/download/<tag>/<name>`
+pub fn release(repo: &str, tag: &str, prerelease: bool, assets: &[(&str, &[u8])]) -> Value {
+    let assets: Vec<Value> = assets.iter().map(|(name, data)| json!({
+        "name": name,
+        "url": format!("{}/repos/{}/releases/assets/{}", API, repo, name),
+        "browser_download_url": asset_url(repo, tag, name),
+        "size": data.len(),
+        "content_type": "application/octet-stream",
+        "digest": format!("sha256:{}", sha256(data)),
+    })).collect();
+    json!({
+        "tag_name": tag,
+        "name": tag,
+        "draft": false,
+        "prerelease": prerelease,
+        "published_at": "2026-01-01T00:00:00Z",
+        "assets": assets,
+        "zipball_url": format!("{}/repos/{}/zipball/{}", API, repo, tag),
+        "tarball_url": format!("{}/repos/{}/tarball/{}", API, repo, tag),
+    })
+}
+
+pub fn asset_url(repo: &str, tag: &str, name: &str) -> String {
+    format!("https://github.com/{}/releases/download/{}/{}", repo, tag, name)
+}
+
+// Bytes that aren't all alike, so misplaced chunks show up
+pub fn data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+#[derive(Default)]
+struct ServerState {
+    requests: Vec<String>,
+    failures: usize,
+}
+
+// A plain HTTP server on a local port serving `data` under any path, for
+// parallel downloads: they go through reqwest's async client, which the
+// `HttpClient` of `MockHttp` doesn't cover. Byte ranges are honored and
+// advertised only when `ranges` is set.
+pub struct FileServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<ServerState>>,
+}
+
+impl FileServer {
+    pub fn start(data: Vec<u8>, ranges: bool) -> FileServer {
+        setup();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(ServerState::default()));
+        let data = Arc::new(data);
+        let shared = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (state, data) = (shared.clone(), data.clone());
+                thread::spawn(move || serve(stream, &state, &data, ranges));
+            }
+        });
+        FileServer { addr, state }
+    }
+
+    // Answer the next `count` requests with a 503
+    pub fn fail_next(&self, count: usize) {
+        self.state.lock().unwrap().failures = count;
+    }
+
+    pub fn url(&self, name: &str) -> String {
+        format!("http://{}/{}", self.addr, name)
+    }
+
+    // `<method> <path>`, followed by the requested range if any
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+fn serve(stream: TcpStream, state: &Mutex<ServerState>, data: &[u8], ranges: bool) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or_default().to_string(), parts.next().unwrap_or_default().to_string());
+    let mut range = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("range") {
+            range = value.trim().strip_prefix("bytes=").and_then(|range| {
+                let (start, end) = range.split_once('-')?;
+                Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?))
+            });
+        }
+    }
+
+    let failing = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(match range {
+            Some((start, end)) => format!("{} {} {}-{}", method, path, start, end),
+            None => format!("{} {}", method, path),
+        });
+        let failing = state.failures > 0;
+        state.failures = state.failures.saturating_sub(1);
+        failing
+    };
+    let (status, headers, body) = if failing {
+        ("503 Service Unavailable", String::new(), &[][..])
+    } else {
+        match range.filter(|_| ranges) {
+            Some((start, end)) => {
+                let end = end.min(data.len() - 1);
+                ("206 Partial Content", format!("Content-Range: bytes {}-{}/{}\r\n", start, end, data.len()), &data[start..=end])
+            },
+            None => ("200 OK", String::new(), data),
+        }
+    };
+    let accept_ranges = if ranges { "Accept-Ranges: bytes\r\n" } else { "" };
+    let mut stream = &stream;
+    let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\n{}{}Connection: close\r\n\r\n", status, body.len(), accept_ranges, headers);
+    if method != "HEAD" {
+        let _ = stream.write_all(body);
+    }
+}
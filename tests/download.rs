@@ -0,0 +1,159 @@
+mod common;
+
+use common::{MockHttp, asset_url, release, scratch_dir, sha256};
+use egit::assets::GitHubRelease;
+use egit::checksum::Algorithm;
+use egit::download::{DownloadOptions, download_release_asset, preflight, select_asset};
+use egit::error::EgitError;
+use egit::storage::FsStorage;
+use egit::verify;
+use std::fs;
+use std::path::Path;
+
+const DATA: &[u8] = b"#!/bin/sh\necho hello\n";
+
+fn options(dir: &Path) -> DownloadOptions {
+    DownloadOptions {
+        dir: Some(dir.to_path_buf()),
+        no_interactive: true,
+        assume_yes: true,
+        no_cache: true,
+        ..Default::default()
+    }
+}
+
+fn parse(value: serde_json::Value) -> GitHubRelease {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn select_asset_by_pattern() {
+    let release = parse(release("select/tool", "v1.0.0", false, &[
+        ("tool-linux-x86_64.tar.gz", DATA),
+        ("tool-macos-aarch64.tar.gz", DATA),
+        ("tool-windows-x86_64.zip", DATA),
+    ]));
+    let options = DownloadOptions {
+        asset: Some("*macos*".to_string()),
+        no_interactive: true,
+        ..Default::default()
+    };
+    let asset = select_asset(&release, &options).unwrap().unwrap();
+    assert_eq!(asset.name, "tool-macos-aarch64.tar.gz");
+
+    let options = DownloadOptions { asset: Some("*freebsd*".to_string()), ..options };
+    assert!(matches!(select_asset(&release, &options), Err(EgitError::NotFound(_))));
+}
+
+#[test]
+fn download_checks_the_digest() {
+    let http = MockHttp::new();
+    let release = parse(release("fetch/tool", "v1.0.0", false, &[("tool.sh", DATA)]));
+    http.bytes(&asset_url("fetch/tool", "v1.0.0", "tool.sh"), DATA);
+    let dir = scratch_dir("download");
+
+    download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], "fetch/tool", &options(&dir)).unwrap();
+    assert_eq!(fs::read(dir.join("tool.sh")).unwrap(), DATA);
+    assert_eq!(http.requests(), [asset_url("fetch/tool", "v1.0.0", "tool.sh")]);
+}
+
+#[test]
+fn download_hashes_while_streaming() {
+    let http = MockHttp::new();
+    let release = parse(release("hash/tool", "v1.0.0", false, &[("tool.sh", DATA)]));
+    http.bytes(&asset_url("hash/tool", "v1.0.0", "tool.sh"), DATA);
+    let dir = scratch_dir("hash");
+
+    let options = DownloadOptions { hashes: vec![Algorithm::Blake3], ..options(&dir) };
+    let digests = download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], "hash/tool", &options).unwrap().unwrap();
+    assert_eq!(digests.sha256, sha256(DATA));
+    assert_eq!(digests.blake3, Some(blake3::hash(DATA).to_hex().to_string()));
+    assert_eq!(digests.sha512, None);
+}
+
+#[test]
+fn download_rejects_a_digest_mismatch() {
+    let http = MockHttp::new();
+    let release = parse(release("tampered/tool", "v1.0.0", false, &[("tool.sh", DATA)]));
+    // Same size, so only the digest tells them apart
+    http.bytes(&asset_url("tampered/tool", "v1.0.0", "tool.sh"), b"#!/bin/sh\necho HELLO\n");
+    let dir = scratch_dir("tampered");
+
+    let result = download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], "tampered/tool", &options(&dir));
+    assert!(matches!(result, Err(EgitError::Verification(_))), "{:?}", result.err());
+}
+
+#[test]
+fn digest_mismatch_keeps_the_existing_file() {
+    let http = MockHttp::new();
+    let release = parse(release("keep/tool", "v1.0.0", false, &[("tool.sh", DATA)]));
+    http.bytes(&asset_url("keep/tool", "v1.0.0", "tool.sh"), b"#!/bin/sh\necho HELLO\n");
+    let dir = scratch_dir("mismatch-keep");
+    fs::write(dir.join("tool.sh"), "mine\n").unwrap();
+
+    // Checked before the download replaces it, even with --force
+    let options = DownloadOptions { force: true, ..options(&dir) };
+    let result = download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], "keep/tool", &options);
+    assert!(matches!(result, Err(EgitError::Verification(_))), "{:?}", result.err());
+    assert_eq!(fs::read(dir.join("tool.sh")).unwrap(), b"mine\n");
+    assert!(!dir.join("tool.sh.part").exists());
+}
+
+#[test]
+fn sigstore_requires_a_bundle() {
+    let http = MockHttp::new();
+    let release = parse(release("unsigned/tool", "v1.0.0", false, &[("tool.sh", DATA), ("tool.sh.sig", b"signature")]));
+    http.bytes(&asset_url("unsigned/tool", "v1.0.0", "tool.sh"), DATA);
+    let dir = scratch_dir("unsigned");
+
+    // A bare `.sig` has no certificate to check the identity against
+    let sigstore = verify::Identity {
+        identity: "https://github.com/unsigned/tool/.github/workflows/release.yml@refs/tags/v1.0.0".to_string(),
+        oidc_issuer: "https://token.actions.githubusercontent.com".to_string(),
+    };
+    let options = DownloadOptions { sigstore: Some(sigstore), ..options(&dir) };
+    let result = download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], "unsigned/tool", &options);
+    assert!(matches!(result, Err(EgitError::Verification(_))), "{:?}", result.err());
+    assert!(!dir.join("tool.sh").exists());
+}
+
+#[test]
+fn download_reports_a_missing_asset() {
+    let http = MockHttp::new();
+    let release = parse(release("gone/tool", "v1.0.0", false, &[("tool.sh", DATA)]));
+    let dir = scratch_dir("gone");
+
+    let result = download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], "gone/tool", &options(&dir));
+    assert!(result.is_err());
+    assert!(!dir.join("tool.sh").exists());
+}
+
+#[test]
+fn preflight_refuses_a_checksum_file() {
+    let release = parse(release("sidecar/tool", "v1.0.0", false, &[
+        ("tool.sh", DATA),
+        ("tool.sh.sha256", b"0000  tool.sh\n"),
+    ]));
+    let options = DownloadOptions { asset: Some("*.sha256".to_string()), ..Default::default() };
+    let asset = select_asset(&release, &options).unwrap().unwrap();
+    assert!(matches!(preflight(asset, &options), Err(EgitError::Other(_))));
+
+    let options = DownloadOptions { asset: Some("tool.sh.sha256".to_string()), ..Default::default() };
+    assert!(preflight(asset, &options).is_ok());
+}
+
+#[test]
+fn preflight_checks_size_and_content_type() {
+    let release = parse(release("limits/tool", "v1.0.0", false, &[("tool.sh", DATA)]));
+    let asset = &release.assets[0];
+
+    let options = DownloadOptions { max_size: Some(DATA.len() as u64 - 1), ..Default::default() };
+    assert!(matches!(preflight(asset, &options), Err(EgitError::Verification(_))));
+    let options = DownloadOptions { max_size: Some(DATA.len() as u64), ..Default::default() };
+    assert!(preflight(asset, &options).is_ok());
+
+    let options = DownloadOptions { content_type: Some("application/gzip".to_string()), ..Default::default() };
+    assert!(matches!(preflight(asset, &options), Err(EgitError::Verification(_))));
+    let options = DownloadOptions { content_type: Some("Application/*".to_string()), ..Default::default() };
+    assert!(preflight(asset, &options).is_ok());
+}
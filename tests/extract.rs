@@ -0,0 +1,95 @@
+mod common;
+
+use common::{MockHttp, asset_url, release, scratch_dir};
+use egit::assets::GitHubRelease;
+use egit::confirm;
+use egit::download::{DownloadOptions, download_release_asset};
+use egit::error::EgitError;
+use egit::storage::FsStorage;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const README: &[u8] = b"from the archive\n";
+const TOOL: &[u8] = b"#!/bin/sh\necho hello\n";
+
+// `tool-1.0.tar.gz`, with everything under a `tool-1.0/` top directory
+fn archive() -> Vec<u8> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (path, data) in [("tool-1.0/README", README), ("tool-1.0/bin/tool", TOOL)] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+// Download the archive of `repo` into `dir` and extract it into `dest`
+fn download_and_extract(repo: &str, dir: &Path, dest: &Path, options: DownloadOptions) -> Result<(), EgitError> {
+    let archive = archive();
+    let release: GitHubRelease = serde_json::from_value(release(repo, "v1.0", false, &[("tool-1.0.tar.gz", &archive)])).unwrap();
+    let http = MockHttp::new();
+    http.bytes(&asset_url(repo, "v1.0", "tool-1.0.tar.gz"), &archive);
+    let options = DownloadOptions {
+        dir: Some(dir.to_path_buf()),
+        extract: true,
+        extract_to: Some(dest.to_path_buf()),
+        no_interactive: true,
+        no_cache: true,
+        ..options
+    };
+    download_release_asset(&http.client(), &FsStorage, &release, &release.assets[0], repo, &options).map(|_| ())
+}
+
+// A destination that already holds a file of the user's, and `README` when
+// `conflict` is set
+fn existing_dest(name: &str, conflict: bool) -> (PathBuf, PathBuf) {
+    let dir = scratch_dir(name);
+    let dest = dir.join("dest");
+    fs::create_dir_all(&dest).unwrap();
+    fs::write(dest.join("notes.txt"), "mine\n").unwrap();
+    if conflict {
+        fs::write(dest.join("README"), "mine\n").unwrap();
+    }
+    (dir, dest)
+}
+
+#[test]
+fn extract_into_an_existing_directory_keeps_its_files() {
+    let (dir, dest) = existing_dest("extract-keep", false);
+
+    download_and_extract("keep/tool", &dir, &dest, DownloadOptions::default()).unwrap();
+    assert_eq!(fs::read(dest.join("notes.txt")).unwrap(), b"mine\n");
+    // The top directory of the archive is stripped
+    assert_eq!(fs::read(dest.join("README")).unwrap(), README);
+    assert_eq!(fs::read(dest.join("bin/tool")).unwrap(), TOOL);
+    assert!(!dir.join("tool-1.0.tar.gz").exists());
+}
+
+#[test]
+fn extract_replaces_files_with_force() {
+    let (dir, dest) = existing_dest("extract-force", true);
+
+    download_and_extract("force/tool", &dir, &dest, DownloadOptions { force: true, ..Default::default() }).unwrap();
+    assert_eq!(fs::read(dest.join("README")).unwrap(), README);
+    assert_eq!(fs::read(dest.join("notes.txt")).unwrap(), b"mine\n");
+}
+
+#[test]
+fn extract_refuses_to_replace_files_unattended() {
+    // On a terminal this would ask instead
+    if confirm::can_prompt() {
+        return;
+    }
+    let (dir, dest) = existing_dest("extract-refuse", true);
+
+    let result = download_and_extract("refuse/tool", &dir, &dest, DownloadOptions::default());
+    assert!(matches!(result, Err(EgitError::Other(_))), "{:?}", result.err());
+    // Checked before anything is written
+    assert_eq!(fs::read(dest.join("README")).unwrap(), b"mine\n");
+    assert_eq!(fs::read(dest.join("notes.txt")).unwrap(), b"mine\n");
+    assert!(!dest.join("bin").exists());
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{FileServer, data, release, scratch_dir, sha256};
+use egit::assets::GitHubRelease;
+use egit::checksum::Algorithm;
+use egit::client::{self, ClientOptions};
+use egit::download::{DownloadOptions, download_release_asset};
+use egit::storage::FsStorage;
+use std::fs;
+use std::path::Path;
+
+// 256 KB chunks, the smallest the queue uses, so this makes 6 of them
+const SIZE: usize = 6 * 256 * 1024;
+
+fn options(dir: &Path) -> DownloadOptions {
+    DownloadOptions {
+        dir: Some(dir.to_path_buf()),
+        multithread: true,
+        threads: 4,
+        no_interactive: true,
+        assume_yes: true,
+        no_cache: true,
+        retries: 2,
+        ..Default::default()
+    }
+}
+
+// A release whose only asset is served by `server`
+fn served_release(server: &FileServer, repo: &str, data: &[u8]) -> GitHubRelease {
+    let mut release: GitHubRelease = serde_json::from_value(release(repo, "v1.0.0", false, &[("tool.bin", data)])).unwrap();
+    release.assets[0].browser_download_url = server.url("tool.bin");
+    release
+}
+
+fn download(release: &GitHubRelease, options: &DownloadOptions) -> egit::checksum::Digests {
+    let client = client::build_client(&ClientOptions::default()).unwrap();
+    download_release_asset(&client, &FsStorage, release, &release.assets[0], "parallel/tool", options).unwrap().unwrap()
+}
+
+#[test]
+fn parallel_download_fetches_every_range() {
+    let data = data(SIZE);
+    let server = FileServer::start(data.clone(), true);
+    let release = served_release(&server, "ranges/tool", &data);
+    let dir = scratch_dir("parallel");
+
+    let options = DownloadOptions { hashes: vec![Algorithm::Sha512], ..options(&dir) };
+    let digests = download(&release, &options);
+    assert_eq!(fs::read(dir.join("tool.bin")).unwrap(), data);
+    assert!(!dir.join("tool.bin.tmp").exists());
+    // Hashed in file order as the chunks arrived
+    assert_eq!(digests.sha256, sha256(&data));
+    assert!(digests.sha512.is_some());
+
+    let mut requests = server.requests();
+    assert_eq!(requests.remove(0), "HEAD /tool.bin");
+    // The workers take the chunks in any order
+    let mut chunks: Vec<String> = (0..6).map(|i| format!("GET /tool.bin {}-{}", i * 262144, (i + 1) * 262144 - 1)).collect();
+    requests.sort();
+    chunks.sort();
+    assert_eq!(requests, chunks);
+}
+
+#[test]
+fn parallel_download_falls_back_without_range_support() {
+    let data = data(SIZE);
+    let server = FileServer::start(data.clone(), false);
+    let release = served_release(&server, "noranges/tool", &data);
+    let dir = scratch_dir("noranges");
+
+    let digests = download(&release, &options(&dir));
+    assert_eq!(fs::read(dir.join("tool.bin")).unwrap(), data);
+    assert_eq!(digests.sha256, sha256(&data));
+    assert_eq!(server.requests(), ["HEAD /tool.bin", "GET /tool.bin"]);
+}
+
+#[test]
+fn parallel_download_retries_the_range_check() {
+    let data = data(SIZE);
+    let server = FileServer::start(data.clone(), true);
+    server.fail_next(1);
+    let release = served_release(&server, "flaky/tool", &data);
+    let dir = scratch_dir("flaky");
+
+    download(&release, &options(&dir));
+    assert_eq!(fs::read(dir.join("tool.bin")).unwrap(), data);
+    let requests = server.requests();
+    assert_eq!(requests[..2], ["HEAD /tool.bin", "HEAD /tool.bin"]);
+    assert_eq!(requests.len(), 2 + 6);
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::MockHttp;
+use egit::client::{Client, ClientOptions};
+use egit::provider::Providers;
+use std::collections::BTreeMap;
+
+// Providers whose GitHub client sends `ghp_github`, with per-host tokens for
+// gitlab.com and a self-hosted Gitea from the config
+fn providers(host: Option<&str>) -> Providers {
+    let github = Client::with_http(MockHttp::new(), ClientOptions { token: Some("ghp_github".to_string()), ..Default::default() });
+    let hosts = BTreeMap::from([("gitea".to_string(), "https://git.example.com:3000".to_string())]);
+    let tokens = BTreeMap::from([
+        ("gitlab.com".to_string(), "glpat-gitlab".to_string()),
+        ("git.example.com:3000".to_string(), "gitea-token".to_string()),
+        ("gitlab.internal".to_string(), "glpat-internal".to_string()),
+    ]);
+    Providers::new(github, ClientOptions::default(), host.map(str::to_string), hosts, tokens)
+}
+
+// The token `package` would be fetched with, and the package name without
+// the provider prefix
+fn token_for<'a>(providers: &Providers, package: &'a str) -> (Option<String>, &'a str) {
+    let (provider, rest) = providers.for_package(package).unwrap();
+    (provider.client().options().token.clone(), rest)
+}
+
+#[test]
+fn each_host_gets_its_own_token() {
+    let providers = providers(None);
+
+    assert_eq!(token_for(&providers, "owner/repo"), (Some("ghp_github".to_string()), "owner/repo"));
+    assert_eq!(token_for(&providers, "github:owner/repo"), (Some("ghp_github".to_string()), "owner/repo"));
+    assert_eq!(token_for(&providers, "gitlab:group/project"), (Some("glpat-gitlab".to_string()), "group/project"));
+    // The Gitea host comes from the config, and its token is looked up by
+    // host and port
+    assert_eq!(token_for(&providers, "gitea:owner/repo"), (Some("gitea-token".to_string()), "owner/repo"));
+}
+
+#[test]
+fn hosts_without_a_token_get_none() {
+    let providers = providers(None);
+
+    // Neither the GitHub token nor another host's is sent to Codeberg
+    assert_eq!(token_for(&providers, "codeberg:owner/repo"), (None, "owner/repo"));
+    assert!(providers.for_package("bitbucket:owner/repo").is_err());
+}
+
+#[test]
+fn host_flag_picks_the_token_of_that_host() {
+    let providers = providers(Some("https://gitlab.internal"));
+
+    assert_eq!(token_for(&providers, "gitlab:group/project"), (Some("glpat-internal".to_string()), "group/project"));
+    assert_eq!(token_for(&providers, "owner/repo"), (Some("ghp_github".to_string()), "owner/repo"));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{API, MockHttp, release};
+use egit::error::EgitError;
+use egit::release::{changelog_releases, open_package, resolve_release};
+use serde_json::json;
+
+#[test]
+fn latest_uses_the_latest_release_endpoint() {
+    let http = MockHttp::new();
+    http.json(&format!("{}/repos/latest/tool/releases/latest", API), release("latest/tool", "v2.0.0", false, &[]));
+
+    let (provider, owner, repo, version) = open_package(&http.providers(), "latest/tool").unwrap();
+    let found = resolve_release(provider.as_ref(), &owner, &repo, None, version.as_deref(), false).unwrap();
+    assert_eq!(found.tag_name, "v2.0.0");
+    assert_eq!(http.requests(), [format!("{}/repos/latest/tool/releases/latest", API)]);
+}
+
+#[test]
+fn bare_version_falls_back_to_v_prefixed_tag() {
+    let http = MockHttp::new();
+    http.json(&format!("{}/repos/prefix/tool/releases/tags/v1.4.0", API), release("prefix/tool", "v1.4.0", false, &[]));
+
+    let (provider, owner, repo, version) = open_package(&http.providers(), "prefix/tool@1.4.0").unwrap();
+    let found = resolve_release(provider.as_ref(), &owner, &repo, None, version.as_deref(), false).unwrap();
+    assert_eq!(found.tag_name, "v1.4.0");
+    assert_eq!(http.requests(), [
+        format!("{}/repos/prefix/tool/releases/tags/1.4.0", API),
+        format!("{}/repos/prefix/tool/releases/tags/v1.4.0", API),
+    ]);
+}
+
+#[test]
+fn requirement_picks_newest_matching_stable_release() {
+    let http = MockHttp::new();
+    http.json(&format!("{}/repos/semver/tool/releases", API), json!([
+        release("semver/tool", "v2.0.0", false, &[]),
+        release("semver/tool", "v1.3.0-rc1", true, &[]),
+        release("semver/tool", "v1.2.0", false, &[]),
+        release("semver/tool", "v1.1.0", false, &[]),
+    ]));
+
+    let (provider, owner, repo, version) = open_package(&http.providers(), "semver/tool@^1").unwrap();
+    let found = resolve_release(provider.as_ref(), &owner, &repo, None, version.as_deref(), false).unwrap();
+    assert_eq!(found.tag_name, "v1.2.0");
+}
+
+#[test]
+fn only_prereleases_asks_for_pre() {
+    let http = MockHttp::new();
+    http.json(&format!("{}/repos/pre/tool/releases", API), json!([
+        release("pre/tool", "v0.2.0-beta", true, &[]),
+    ]));
+
+    let (provider, owner, repo, _) = open_package(&http.providers(), "pre/tool").unwrap();
+    let error = resolve_release(provider.as_ref(), &owner, &repo, None, None, false).unwrap_err();
+    assert!(matches!(&error, EgitError::NotFound(message) if message.contains("--pre")), "{}", error);
+
+    let found = resolve_release(provider.as_ref(), &owner, &repo, None, None, true).unwrap();
+    assert_eq!(found.tag_name, "v0.2.0-beta");
+}
+
+#[test]
+fn missing_version_lists_available_ones() {
+    let http = MockHttp::new();
+    http.json(&format!("{}/repos/missing/tool/releases", API), json!([
+        release("missing/tool", "v1.1.0", false, &[]),
+        release("missing/tool", "v1.0.0", false, &[]),
+    ]));
+
+    let (provider, owner, repo, _) = open_package(&http.providers(), "missing/tool").unwrap();
+    let error = resolve_release(provider.as_ref(), &owner, &repo, None, Some("^3"), false).unwrap_err();
+    assert!(matches!(&error, EgitError::NotFound(message) if message.contains("v1.1.0, v1.0.0")), "{}", error);
+}
+
+#[test]
+fn changelog_since_version_orders_by_semver() {
+    let http = MockHttp::new();
+    http.json(&format!("{}/repos/changes/tool/releases", API), json!([
+        release("changes/tool", "v2.0.0", false, &[]),
+        release("changes/tool", "nightly", false, &[]),
+        release("changes/tool", "v1.5.0", false, &[]),
+        release("changes/tool", "v1.4.0", false, &[]),
+        release("changes/tool", "v1.3.0", false, &[]),
+    ]));
+
+    let (provider, owner, repo, _) = open_package(&http.providers(), "changes/tool").unwrap();
+    let since = semver::Version::new(1, 4, 0);
+    let releases = changelog_releases(provider.as_ref(), &owner, &repo, None, Some(&since), None, false).unwrap();
+    let tags: Vec<&str> = releases.iter().map(|r| r.tag_name.as_str()).collect();
+    assert_eq!(tags, ["v2.0.0", "v1.5.0"]);
+}